@@ -0,0 +1,128 @@
+use tokenizer::{tokenize, Token, TokenizationError};
+use parser::parse;
+use interp::{Interp, PolicyDecision, RunState};
+use diagnostic::{check_all, has_errors, Diagnostic};
+use pragma::read_pragmas;
+use run::GOLDEN_SEED;
+use leg_sdl::GRAPHICS_BUILTINS;
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+/// How many nodes the simulated run may evaluate before analysis gives
+/// up on it, so scripts that never finish can still be analyzed.
+const MAX_ANALYSIS_STEPS: usize = 1_000_000;
+
+/// What a script would do if it were run, found without letting it affect
+/// anything outside the interpreter.
+pub struct Analysis {
+    pub diagnostics: Vec<Diagnostic>,
+    /// Calls per side effecting builtin, grouped by the capability it needs.
+    pub capabilities: BTreeMap<&'static str, BTreeMap<String, usize>>,
+    /// Files the script would read or write.
+    pub files: Vec<String>,
+    /// Why the simulated run stopped early, if it did.
+    pub error: Option<String>,
+    pub finished: bool
+}
+
+/// The capability a host has to grant for `builtin` to have an effect.
+fn capability(builtin: &str) -> &'static str {
+    match builtin {
+        "print" | "println" | "debug" => { "output" }
+        _ if GRAPHICS_BUILTINS.contains(&builtin) => { "graphics" }
+        _ => { "unknown" }
+    }
+}
+
+/// Checks `source` and, when it has no errors, simulates running it.
+/// Side effecting builtins are recorded and skipped as in a dry run.
+pub fn analyze(source: &str) -> Analysis {
+    let mut analysis = Analysis {
+        diagnostics: check_all(source),
+        capabilities: BTreeMap::new(),
+        files: Vec::new(),
+        error: None,
+        finished: false
+    };
+    if has_errors(&analysis.diagnostics) {
+        return analysis;
+    }
+
+    // Both already succeeded in check_all
+    let tokens = match tokenize(source) {
+        Ok(tokens) => { tokens }
+        Err(_) => { return analysis; }
+    };
+    let ast = match parse(tokens.iter().cloned().map(Ok::<Token, TokenizationError>)) {
+        Ok(ast) => { ast }
+        Err(_) => { return analysis; }
+    };
+
+    let calls: Rc<RefCell<BTreeMap<String, usize>>> = Rc::new(RefCell::new(BTreeMap::new()));
+    let mut interp = Interp::new();
+    interp.seed_rng(GOLDEN_SEED);
+    let recorded = calls.clone();
+    interp.set_policy(Box::new(move |name, _args| {
+        *recorded.borrow_mut().entry(String::from(name)).or_insert(0) += 1;
+        PolicyDecision::DryRun
+    }));
+
+    let res = read_pragmas(&tokens)
+        .and_then(|pragmas| {
+            interp.set_strict(pragmas.strict);
+            interp.set_bignum(pragmas.bignum)
+        });
+    if let Err(error) = res {
+        analysis.error = Some(error.to_string());
+        return analysis;
+    }
+
+    interp.set_yield_interval(Some(MAX_ANALYSIS_STEPS));
+    let mut execution = interp.start(&ast);
+    match interp.resume(&mut execution) {
+        Ok(RunState::Finished(_)) => { analysis.finished = true; }
+        Ok(RunState::Interrupted) => {}
+        Err(error) => { analysis.error = Some(error.to_string()); }
+    }
+
+    for (name, count) in calls.borrow().iter() {
+        analysis.capabilities.entry(capability(name)).or_default().insert(name.clone(), *count);
+    }
+    analysis
+}
+
+impl Analysis {
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for diagnostic in &self.diagnostics {
+            report.push_str(&format!("{}\n", diagnostic));
+        }
+        if has_errors(&self.diagnostics) {
+            return report;
+        }
+
+        if self.capabilities.is_empty() {
+            report.push_str("capabilities: none\n");
+        }
+        for (capability, calls) in &self.capabilities {
+            let calls: Vec<String> = calls.iter().map(|(name, count)| format!("{} x{}", name, count)).collect();
+            report.push_str(&format!("capability {}: {}\n", capability, calls.join(", ")));
+        }
+
+        if self.files.is_empty() {
+            report.push_str("files: none\n");
+        }
+        for file in &self.files {
+            report.push_str(&format!("file {}\n", file));
+        }
+
+        if let Some(ref error) = self.error {
+            report.push_str(&format!("evaluation stopped at an error, later effects are not included:\n{}", error));
+        } else if !self.finished {
+            report.push_str(&format!("evaluation did not finish within {} steps, later effects are not included\n", MAX_ANALYSIS_STEPS));
+        }
+        report
+    }
+}
@@ -0,0 +1,411 @@
+use interp::{InterpValue, InterpError};
+use persistent::{PersistentVec, PersistentMap};
+use format::{format_value, with_article, counted};
+use error_codes;
+
+#[cfg(feature = "bignum")]
+use bigdecimal::ToPrimitive;
+#[cfg(feature = "bignum")]
+use bigdecimal::num_bigint::Sign;
+
+use std::cmp::Ordering;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+type Elements = Rc<RefCell<Vec<InterpValue>>>;
+
+/// The values that can be used as `imap` keys. Keys of different types are
+/// never equal and are ordered booleans, numbers, strings.
+#[derive(Clone, Debug)]
+pub enum MapKey {
+    Boolean(bool),
+    Number(f64),
+    String(String)
+}
+
+impl MapKey {
+    fn from_value(value: &InterpValue) -> Result<MapKey, InterpError> {
+        match value {
+            &InterpValue::InterpBoolean(val) => { Ok(MapKey::Boolean(val)) }
+            // -0 and 0 are the same key
+            &InterpValue::InterpNumber(num) => { Ok(MapKey::Number(num + 0.0)) }
+            // 1 and 1.0 are the same key too
+            &InterpValue::InterpInt(num) => { Ok(MapKey::Number(num as f64)) }
+            InterpValue::InterpString(val) => { Ok(MapKey::String(val.clone())) }
+            other => {
+                let msg = format!("Can not use {} as a map key", with_article(other.type_name()));
+                Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+            }
+        }
+    }
+
+    pub fn to_value(&self) -> InterpValue {
+        match self {
+            &MapKey::Boolean(val) => { InterpValue::InterpBoolean(val) }
+            &MapKey::Number(num) => { InterpValue::InterpNumber(num) }
+            MapKey::String(val) => { InterpValue::InterpString(val.clone()) }
+        }
+    }
+
+    fn rank(&self) -> usize {
+        match *self {
+            MapKey::Boolean(_) => { 0 }
+            MapKey::Number(_) => { 1 }
+            MapKey::String(_) => { 2 }
+        }
+    }
+}
+
+impl Ord for MapKey {
+    fn cmp(&self, other: &MapKey) -> Ordering {
+        match (self, other) {
+            (&MapKey::Boolean(lhs), &MapKey::Boolean(rhs)) => { lhs.cmp(&rhs) }
+            (&MapKey::Number(lhs), &MapKey::Number(rhs)) => { lhs.total_cmp(&rhs) }
+            (MapKey::String(lhs), MapKey::String(rhs)) => { lhs.cmp(rhs) }
+            _ => { self.rank().cmp(&other.rank()) }
+        }
+    }
+}
+
+impl PartialOrd for MapKey {
+    fn partial_cmp(&self, other: &MapKey) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for MapKey {
+    fn eq(&self, other: &MapKey) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MapKey {}
+
+fn index(value: &InterpValue) -> Result<usize, InterpError> {
+    match value {
+        &InterpValue::InterpInt(num) if num >= 0 => { Ok(num as usize) }
+        &InterpValue::InterpNumber(num) if num >= 0.0 && num.fract() == 0.0 => { Ok(num as usize) }
+        &InterpValue::InterpInt(num) => {
+            let msg = format!("Indices must be non negative integers, found {}", num);
+            Err(InterpError::new(&error_codes::INTEGER_REQUIRED, msg))
+        }
+        &InterpValue::InterpNumber(num) => {
+            let msg = format!("Indices must be non negative integers, found {}", num);
+            Err(InterpError::new(&error_codes::INTEGER_REQUIRED, msg))
+        }
+        // Exact numbers past the end of any list are out of bounds like the
+        // others, so they are only narrowed as far as usize::MAX
+        #[cfg(feature = "bignum")]
+        InterpValue::InterpBigNumber(num) if num.is_integer() && num.sign() != Sign::Minus => {
+            Ok(num.to_usize().unwrap_or(usize::MAX))
+        }
+        #[cfg(feature = "bignum")]
+        InterpValue::InterpBigNumber(num) => {
+            let msg = format!("Indices must be non negative integers, found {}", num.normalized());
+            Err(InterpError::new(&error_codes::INTEGER_REQUIRED, msg))
+        }
+        other => {
+            let msg = format!("Indices must be integers, found {}", with_article(other.type_name()));
+            Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+        }
+    }
+}
+
+fn out_of_bounds(index: usize, length: usize) -> InterpError {
+    let msg = format!("Index {} is out of bounds for a length of {}", index, length);
+    InterpError::new(&error_codes::INDEX_OUT_OF_BOUNDS, msg)
+}
+
+fn arity_error(name: &str, expected: &str, found: usize) -> InterpError {
+    let msg = format!("{} expects {} but was called with {}", name, expected, counted(found, "argument"));
+    InterpError::new(&error_codes::ARITY_MISMATCH, msg)
+}
+
+fn not_a_collection(name: &str, value: &InterpValue) -> InterpError {
+    let msg = format!("{} expects an IList or IMap but was given {}", name, with_article(value.type_name()));
+    InterpError::new(&error_codes::TYPE_MISMATCH, msg)
+}
+
+/// `ilist(a, b, ...)`
+pub fn ilist(args: Vec<InterpValue>) -> InterpValue {
+    let list = args.into_iter().fold(PersistentVec::new(), |list, value| list.push(value));
+    InterpValue::InterpList(list)
+}
+
+/// `imap(key, value, key, value, ...)`
+pub fn imap(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    if !args.len().is_multiple_of(2) {
+        return Err(arity_error("imap", "keys and values in pairs", args.len()));
+    }
+    let mut map = PersistentMap::new();
+    for pair in args.chunks(2) {
+        map = map.insert(MapKey::from_value(&pair[0])?, pair[1].clone());
+    }
+    Ok(InterpValue::InterpMap(map))
+}
+
+/// `with(list, value)` appends, `with(list, index, value)` replaces and
+/// `with(map, key, value)` inserts, all returning a new collection.
+pub fn with(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    match (args.first(), args.len()) {
+        (Some(InterpValue::InterpList(list)), 2) => {
+            Ok(InterpValue::InterpList(list.push(args[1].clone())))
+        }
+        (Some(InterpValue::InterpList(list)), 3) => {
+            let index = index(&args[1])?;
+            match list.set(index, args[2].clone()) {
+                Some(list) => { Ok(InterpValue::InterpList(list)) }
+                None => { Err(out_of_bounds(index, list.len())) }
+            }
+        }
+        (Some(InterpValue::InterpMap(map)), 3) => {
+            let key = MapKey::from_value(&args[1])?;
+            Ok(InterpValue::InterpMap(map.insert(key, args[2].clone())))
+        }
+        (Some(&InterpValue::InterpList(_)), count) => {
+            Err(arity_error("with", "a list and a value, or a list, an index and a value", count))
+        }
+        (Some(&InterpValue::InterpMap(_)), count) => {
+            Err(arity_error("with", "a map, a key and a value", count))
+        }
+        (Some(other), _) => { Err(not_a_collection("with", other)) }
+        (None, count) => { Err(arity_error("with", "a collection", count)) }
+    }
+}
+
+/// `without(list, index)` and `without(map, key)` return a new collection
+/// without that element.
+pub fn without(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    if args.len() != 2 {
+        return Err(arity_error("without", "a collection and an index or key", args.len()));
+    }
+    match args[0] {
+        InterpValue::InterpList(ref list) => {
+            let index = index(&args[1])?;
+            match list.remove(index) {
+                Some(list) => { Ok(InterpValue::InterpList(list)) }
+                None => { Err(out_of_bounds(index, list.len())) }
+            }
+        }
+        InterpValue::InterpMap(ref map) => {
+            let key = MapKey::from_value(&args[1])?;
+            Ok(InterpValue::InterpMap(map.remove(&key)))
+        }
+        ref other => { Err(not_a_collection("without", other)) }
+    }
+}
+
+/// `get(list, index)` or `get(map, key)`, which is null for a missing key.
+pub fn get(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    if args.len() != 2 {
+        return Err(arity_error("get", "a collection and an index or key", args.len()));
+    }
+    lookup(&args[0], &args[1])
+}
+
+/// The element of an array or list at an index, or the value of a map for a
+/// key. Used by both `get` and `value[key]`.
+pub fn lookup(collection: &InterpValue, key: &InterpValue) -> Result<InterpValue, InterpError> {
+    match collection {
+        InterpValue::InterpArray(values) => {
+            let values = values.borrow();
+            let index = index(key)?;
+            match values.get(index) {
+                Some(value) => { Ok(value.clone()) }
+                None => { Err(out_of_bounds(index, values.len())) }
+            }
+        }
+        InterpValue::InterpList(list) => {
+            let index = index(key)?;
+            match list.get(index) {
+                Some(value) => { Ok(value.clone()) }
+                None => { Err(out_of_bounds(index, list.len())) }
+            }
+        }
+        InterpValue::InterpMap(map) => {
+            let key = MapKey::from_value(key)?;
+            Ok(map.get(&key).cloned().unwrap_or(InterpValue::InterpVoid))
+        }
+        other => {
+            let msg = format!("Can not index {}", with_article(other.type_name()));
+            Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+        }
+    }
+}
+
+pub fn len(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    if args.len() != 1 {
+        return Err(arity_error("len", "a collection", args.len()));
+    }
+    match args[0] {
+        InterpValue::InterpArray(ref values) => { Ok(InterpValue::InterpInt(values.borrow().len() as i64)) }
+        InterpValue::InterpList(ref list) => { Ok(InterpValue::InterpInt(list.len() as i64)) }
+        InterpValue::InterpMap(ref map) => { Ok(InterpValue::InterpInt(map.len() as i64)) }
+        ref other => {
+            let msg = format!("len expects an Array, IList or IMap but was given {}", with_article(other.type_name()));
+            Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+        }
+    }
+}
+
+fn not_an_array(name: &str, value: &InterpValue) -> InterpError {
+    let msg = format!("{} expects an Array but was given {}", name, with_article(value.type_name()));
+    InterpError::new(&error_codes::TYPE_MISMATCH, msg)
+}
+
+fn not_a_list(name: &str, value: &InterpValue) -> InterpError {
+    let msg = format!("{} expects an Array or IList but was given {}", name, with_article(value.type_name()));
+    InterpError::new(&error_codes::TYPE_MISMATCH, msg)
+}
+
+/// The shared elements of the Array the builtin `name` changes in place.
+fn array(name: &str, value: &InterpValue) -> Result<Elements, InterpError> {
+    match value {
+        InterpValue::InterpArray(values) => { Ok(values.clone()) }
+        other => { Err(not_an_array(name, other)) }
+    }
+}
+
+/// A copy of the elements of an Array or IList.
+fn elements(name: &str, value: &InterpValue) -> Result<Vec<InterpValue>, InterpError> {
+    match value {
+        InterpValue::InterpArray(values) => { Ok(values.borrow().clone()) }
+        InterpValue::InterpList(list) => { Ok(list.iter().cloned().collect()) }
+        other => { Err(not_a_list(name, other)) }
+    }
+}
+
+/// The elements of the only argument of `name`, an Array or IList.
+pub fn list_argument(name: &str, args: &[InterpValue]) -> Result<Vec<InterpValue>, InterpError> {
+    if args.len() != 1 {
+        return Err(arity_error(name, "an Array or IList", args.len()));
+    }
+    elements(name, &args[0])
+}
+
+/// Gives an Array the elements `values` in place and returns it, or makes a
+/// new IList of them for an IList.
+pub fn replace_elements(list: &InterpValue, values: Vec<InterpValue>) -> InterpValue {
+    match list {
+        InterpValue::InterpArray(elements) => {
+            *elements.borrow_mut() = values;
+            list.clone()
+        }
+        _ => { ilist(values) }
+    }
+}
+
+/// `values` as the same kind of list as `like`, a new Array for an Array.
+fn same_kind(like: &InterpValue, values: Vec<InterpValue>) -> InterpValue {
+    match like {
+        &InterpValue::InterpList(_) => { ilist(values) }
+        _ => { InterpValue::InterpArray(Rc::new(RefCell::new(values))) }
+    }
+}
+
+/// `push(array, a, b, ...)` appends to the array in place, so every copy of
+/// it sees the new elements, and returns it.
+pub fn push(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    let mut args = args.into_iter();
+    let array = match args.next() {
+        Some(value) => { value }
+        None => { return Err(arity_error("push", "an Array to push to", 0)); }
+    };
+    self::array("push", &array)?.borrow_mut().extend(args);
+    Ok(array)
+}
+
+/// `pop(array)` removes the last element of the array and returns it, or
+/// null when the array is empty.
+pub fn pop(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    if args.len() != 1 {
+        return Err(arity_error("pop", "an Array", args.len()));
+    }
+    let values = array("pop", &args[0])?;
+    let last = values.borrow_mut().pop();
+    Ok(last.unwrap_or(InterpValue::InterpVoid))
+}
+
+/// `insert(array, index, value)` puts the value at the index, moving the
+/// elements from there on back by one. The index may be the length.
+pub fn insert(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    if args.len() != 3 {
+        return Err(arity_error("insert", "an Array, an index and a value", args.len()));
+    }
+    let values = array("insert", &args[0])?;
+    let index = index(&args[1])?;
+    let length = values.borrow().len();
+    if index > length {
+        return Err(out_of_bounds(index, length));
+    }
+    values.borrow_mut().insert(index, args[2].clone());
+    Ok(InterpValue::InterpVoid)
+}
+
+/// `remove(array, index)` takes the element at the index out of the array
+/// and returns it.
+pub fn remove(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    if args.len() != 2 {
+        return Err(arity_error("remove", "an Array and an index", args.len()));
+    }
+    let values = array("remove", &args[0])?;
+    let index = index(&args[1])?;
+    let length = values.borrow().len();
+    if index >= length {
+        return Err(out_of_bounds(index, length));
+    }
+    return Ok(values.borrow_mut().remove(index));
+}
+
+/// `slice(list, start)` and `slice(list, start, end)` copy the elements from
+/// start up to, but not including, end into a new list of the same kind.
+pub fn slice(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    if args.len() != 2 && args.len() != 3 {
+        return Err(arity_error("slice", "a list, a start and optionally an end", args.len()));
+    }
+    let values = elements("slice", &args[0])?;
+    let start = index(&args[1])?;
+    let end = match args.get(2) {
+        Some(end) => { index(end)? }
+        None => { values.len() }
+    };
+    if end > values.len() {
+        return Err(out_of_bounds(end, values.len()));
+    }
+    if start > end {
+        let msg = format!("slice starts at {} which is after its end {}", start, end);
+        return Err(InterpError::new(&error_codes::INDEX_OUT_OF_BOUNDS, msg));
+    }
+    Ok(same_kind(&args[0], values[start..end].to_vec()))
+}
+
+/// `concat(a, b, ...)` is a new list with the elements of every list in
+/// order, of the same kind as the first. None of the lists change.
+pub fn concat(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    let first = match args.first() {
+        Some(first) => { first }
+        None => { return Err(arity_error("concat", "at least one list", 0)); }
+    };
+    let mut values = Vec::new();
+    for list in &args {
+        values.extend(elements("concat", list)?);
+    }
+    Ok(same_kind(first, values))
+}
+
+/// `join(list, separator)` is the printed form of every element with the
+/// separator between them.
+pub fn join(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    if args.len() != 2 {
+        return Err(arity_error("join", "a list and a separator", args.len()));
+    }
+    let separator = match args[1] {
+        InterpValue::InterpString(ref separator) => { separator.clone() }
+        ref other => {
+            let msg = format!("join expects a String separator but was given {}", with_article(other.type_name()));
+            return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+        }
+    };
+    let parts: Vec<String> = elements("join", &args[0])?.into_iter().map(format_value).collect();
+    Ok(InterpValue::InterpString(parts.join(&separator)))
+}
@@ -0,0 +1,190 @@
+use interp::{InterpValue, InterpError};
+use persistent::PersistentMap;
+use collections::MapKey;
+use format::with_article;
+use error_codes;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use std::fmt;
+use std::error::Error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+/// A value that does not have the type a Rust value was asked for, like a
+/// native function given a String where it takes an `i64`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionError {
+    pub expected: &'static str,
+    pub found: &'static str
+}
+
+impl ConversionError {
+    fn new(expected: &'static str, value: &InterpValue) -> ConversionError {
+        ConversionError {
+            expected,
+            found: value.type_name()
+        }
+    }
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Expected {} but found {}", with_article(self.expected), with_article(self.found))
+    }
+}
+
+impl Error for ConversionError {
+    fn description(&self) -> &str {
+        "Conversion error"
+    }
+}
+
+/// Lets native functions use `?` on conversions of their arguments.
+impl From<ConversionError> for InterpError {
+    fn from(error: ConversionError) -> InterpError {
+        InterpError::new(&error_codes::TYPE_MISMATCH, error.to_string())
+    }
+}
+
+impl From<f64> for InterpValue {
+    fn from(value: f64) -> InterpValue {
+        InterpValue::InterpNumber(value)
+    }
+}
+
+impl From<i64> for InterpValue {
+    fn from(value: i64) -> InterpValue {
+        InterpValue::InterpInt(value)
+    }
+}
+
+impl From<bool> for InterpValue {
+    fn from(value: bool) -> InterpValue {
+        InterpValue::InterpBoolean(value)
+    }
+}
+
+impl From<String> for InterpValue {
+    fn from(value: String) -> InterpValue {
+        InterpValue::InterpString(value)
+    }
+}
+
+impl<'s> From<&'s str> for InterpValue {
+    fn from(value: &'s str) -> InterpValue {
+        InterpValue::InterpString(String::from(value))
+    }
+}
+
+impl From<()> for InterpValue {
+    fn from(_: ()) -> InterpValue {
+        InterpValue::InterpVoid
+    }
+}
+
+/// Becomes an Array, which scripts can change in place.
+impl<T: Into<InterpValue>> From<Vec<T>> for InterpValue {
+    fn from(values: Vec<T>) -> InterpValue {
+        let values = values.into_iter().map(Into::into).collect();
+        InterpValue::InterpArray(Rc::new(RefCell::new(values)))
+    }
+}
+
+/// Becomes an IMap with String keys.
+impl<T: Into<InterpValue>> From<HashMap<String, T>> for InterpValue {
+    fn from(entries: HashMap<String, T>) -> InterpValue {
+        let mut map = PersistentMap::new();
+        for (key, value) in entries {
+            map = map.insert(MapKey::String(key), value.into());
+        }
+        InterpValue::InterpMap(map)
+    }
+}
+
+/// Ints are promoted to Numbers, as when they are mixed in arithmetic.
+impl TryFrom<InterpValue> for f64 {
+    type Error = ConversionError;
+
+    fn try_from(value: InterpValue) -> Result<f64, ConversionError> {
+        match value {
+            InterpValue::InterpNumber(num) => { Ok(num) }
+            InterpValue::InterpInt(num) => { Ok(num as f64) }
+            other => { Err(ConversionError::new("Number", &other)) }
+        }
+    }
+}
+
+/// Only Ints convert, a Number is never rounded.
+impl TryFrom<InterpValue> for i64 {
+    type Error = ConversionError;
+
+    fn try_from(value: InterpValue) -> Result<i64, ConversionError> {
+        match value {
+            InterpValue::InterpInt(num) => { Ok(num) }
+            other => { Err(ConversionError::new("Int", &other)) }
+        }
+    }
+}
+
+impl TryFrom<InterpValue> for bool {
+    type Error = ConversionError;
+
+    fn try_from(value: InterpValue) -> Result<bool, ConversionError> {
+        match value {
+            InterpValue::InterpBoolean(val) => { Ok(val) }
+            other => { Err(ConversionError::new("Boolean", &other)) }
+        }
+    }
+}
+
+impl TryFrom<InterpValue> for String {
+    type Error = ConversionError;
+
+    fn try_from(value: InterpValue) -> Result<String, ConversionError> {
+        match value {
+            InterpValue::InterpString(val) => { Ok(val) }
+            other => { Err(ConversionError::new("String", &other)) }
+        }
+    }
+}
+
+/// Arrays, ILists and Tuples convert, with every element converted to `T`.
+impl<T: TryFrom<InterpValue, Error = ConversionError>> TryFrom<InterpValue> for Vec<T> {
+    type Error = ConversionError;
+
+    fn try_from(value: InterpValue) -> Result<Vec<T>, ConversionError> {
+        let values: Vec<InterpValue> = match value {
+            InterpValue::InterpArray(ref values) => { values.borrow().clone() }
+            InterpValue::InterpList(ref list) => { list.iter().cloned().collect() }
+            InterpValue::InterpTuple(ref values) => { (**values).clone() }
+            other => { return Err(ConversionError::new("Array", &other)); }
+        };
+        values.into_iter().map(T::try_from).collect()
+    }
+}
+
+/// IMaps convert when every key is a String.
+impl<T: TryFrom<InterpValue, Error = ConversionError>> TryFrom<InterpValue> for HashMap<String, T> {
+    type Error = ConversionError;
+
+    fn try_from(value: InterpValue) -> Result<HashMap<String, T>, ConversionError> {
+        let map = match value {
+            InterpValue::InterpMap(map) => { map }
+            other => { return Err(ConversionError::new("IMap", &other)); }
+        };
+
+        let mut entries = HashMap::new();
+        for (key, value) in map.iter() {
+            let key = match key {
+                MapKey::String(key) => { key.clone() }
+                other => { return Err(ConversionError::new("String", &other.to_value())); }
+            };
+            entries.insert(key, T::try_from(value.clone())?);
+        }
+        Ok(entries)
+    }
+}
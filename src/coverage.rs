@@ -0,0 +1,78 @@
+use interp::InterpHook;
+use parser::{Ast, AstStatement};
+use visitor::Visitor;
+
+use std::collections::BTreeMap;
+
+/// Execution counts for every statement of a script, keyed by the line and
+/// column the statement starts at.
+pub struct Coverage {
+    statements: BTreeMap<(usize, usize), usize>
+}
+
+impl InterpHook for Coverage {
+    fn before_statement(&mut self, statement: &AstStatement) {
+        let key = (statement.file_info.line_number_from, statement.file_info.column_number_from);
+        *self.statements.entry(key).or_insert(0) += 1;
+    }
+}
+
+impl Visitor for Coverage {
+    fn visit_statement(&mut self, statement: &AstStatement) {
+        let key = (statement.file_info.line_number_from, statement.file_info.column_number_from);
+        self.statements.insert(key, 0);
+        self.visit_node(&statement.node);
+    }
+}
+
+impl Coverage {
+    /// Registers every statement of the script as not yet executed.
+    pub fn new(ast: &Ast) -> Coverage {
+        let mut coverage = Coverage {
+            statements: BTreeMap::new()
+        };
+        coverage.visit_node(&ast.root);
+        coverage
+    }
+
+    /// Executions per line. A line holding several statements reports the
+    /// most executed one, so `a := 1; b := 2;` run once counts as once.
+    fn line_counts(&self) -> BTreeMap<usize, usize> {
+        let mut lines: BTreeMap<usize, usize> = BTreeMap::new();
+        for (&(line, _), &count) in &self.statements {
+            let line_count = lines.entry(line).or_insert(0);
+            *line_count = (*line_count).max(count);
+        }
+        lines
+    }
+
+    pub fn lcov(&self, path: &str) -> String {
+        let lines = self.line_counts();
+        let hit = lines.values().filter(|&&count| count > 0).count();
+
+        let mut report = String::from("TN:\n");
+        report.push_str(&format!("SF:{}\n", path));
+        for (line, count) in &lines {
+            report.push_str(&format!("DA:{},{}\n", line, count));
+        }
+        report.push_str(&format!("LF:{}\nLH:{}\nend_of_record\n", lines.len(), hit));
+        report
+    }
+
+    /// The source with gcov style counts in front of each line, `#####` for
+    /// lines that never ran and `-` for lines without statements.
+    pub fn annotate(&self, source: &str) -> String {
+        let lines = self.line_counts();
+
+        let mut report = String::new();
+        for (index, text) in source.lines().enumerate() {
+            let count = match lines.get(&(index + 1)) {
+                Some(&0) => { String::from("#####") }
+                Some(count) => { count.to_string() }
+                None => { String::from("-") }
+            };
+            report.push_str(&format!("{:>9}:{:>5}:{}\n", count, index + 1, text));
+        }
+        report
+    }
+}
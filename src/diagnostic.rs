@@ -0,0 +1,210 @@
+use tokenizer::{Tokenizer, TokenizationError};
+use parser::{parse, ParsingError};
+use interp::InterpError;
+use error_codes::ErrorCode;
+use file_info::CodePoint;
+use resolver;
+use lint;
+use typecheck;
+
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Severity {
+    Error,
+    /// Worth a look, but does not stop the script from running.
+    Warning
+}
+
+impl Severity {
+    pub fn label(&self) -> &'static str {
+        match *self {
+            Severity::Error => { "error" }
+            Severity::Warning => { "warning" }
+        }
+    }
+}
+
+/// A problem found in a script, independent of the phase that found it.
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static ErrorCode,
+    pub message: String,
+    pub file_info: CodePoint
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{} {} {} {}", self.file_info.line_number_from, self.file_info.column_number_from, self.severity.label(),
+               self.code.code, self.message)
+    }
+}
+
+impl Diagnostic {
+    /// Whether the warning is one of the `allowed` codes, or `all` of them
+    /// are allowed. Errors can not be allowed.
+    pub fn is_allowed(&self, allowed: &[String]) -> bool {
+        self.severity == Severity::Warning
+            && allowed.iter().any(|code| code == "all" || code.eq_ignore_ascii_case(self.code.code))
+    }
+
+    /// The diagnostic as one line of JSON, for editors and other tools. The
+    /// span is null when the position is unknown.
+    pub fn to_json(&self, file_name: &str) -> String {
+        let file_info = &self.file_info;
+        let span = if file_info.line_number_from == 0 {
+            String::from("null")
+        } else {
+            format!("{{\"line_from\":{},\"column_from\":{},\"line_to\":{},\"column_to\":{}}}",
+                    file_info.line_number_from, file_info.column_number_from, file_info.line_number_to, file_info.column_number_to)
+        };
+        format!("{{\"severity\":{},\"code\":{},\"message\":{},\"file\":{},\"span\":{}}}",
+                       json_string(self.severity.label()), json_string(self.code.code), json_string(&self.message),
+                       json_string(file_name), span)
+    }
+
+    /// Renders the diagnostic like rustc does: the message, where in
+    /// `file_name` it is, the line of `source` it points at with the code
+    /// underlined, and where to read more about it. Ends with a blank line
+    /// to separate it from the next one. Without a known position only the
+    /// message and where to read more are shown.
+    pub fn render(&self, source: &str, file_name: &str) -> String {
+        let file_info = &self.file_info;
+        let line_number = file_info.line_number_from;
+        let column = file_info.column_number_from.max(1);
+        let gutter = self.gutter();
+
+        let mut text = format!("{}[{}]: {}\n", self.severity.label(), self.code.code, self.message);
+        if line_number > 0 {
+            text.push_str(&format!("{}--> {}:{}:{}\n", gutter, file_name, line_number, column));
+        }
+        if let Some(line) = line_number.checked_sub(1).and_then(|index| source.lines().nth(index)) {
+            // The underline ends with the line when the code continues on the next
+            let last = if file_info.line_number_to == file_info.line_number_from {
+                file_info.column_number_to
+            } else {
+                line.chars().count()
+            };
+            let width = (last + 1).saturating_sub(column).max(1);
+            // Tabs are kept so the carets line up however wide they are shown
+            let indent: String = line.chars().take(column - 1).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+
+            text.push_str(&format!("{} |\n", gutter));
+            text.push_str(&format!("{} | {}\n", line_number, line));
+            text.push_str(&format!("{} | {}{}\n", gutter, indent, "^".repeat(width)));
+        }
+        text.push_str(&format!("{} = help: {}, see `lang1 explain {}`\n\n", gutter, self.code.title, self.code.code));
+        text
+    }
+
+    /// The space left of the `|` of a rendered diagnostic, as wide as the
+    /// number of the line it points at.
+    fn gutter(&self) -> String {
+        " ".repeat(self.file_info.line_number_from.max(1).to_string().len())
+    }
+}
+
+/// Renders a runtime error like `Diagnostic::render`, with a note for each
+/// function call it happened inside of, innermost first.
+pub fn render_interp_error(error: &InterpError, source: &str, file_name: &str) -> String {
+    let diagnostic = Diagnostic::from(error);
+    let gutter = diagnostic.gutter();
+    let mut text = diagnostic.render(source, file_name);
+    // The notes go before the blank line that ends the diagnostic
+    text.pop();
+    for frame in error.backtrace() {
+        text.push_str(&format!("{} = note: in {} called at {}:{}:{}\n", gutter, frame.name, file_name,
+                               frame.call_site.line_number_from, frame.call_site.column_number_from));
+    }
+    text.push('\n');
+    text
+}
+
+impl<'a> From<&'a InterpError> for Diagnostic {
+    /// Errors raised before any node was evaluated point at line 0.
+    fn from(error: &'a InterpError) -> Diagnostic {
+        let file_info = match error.span() {
+            Some(span) => { span.clone() }
+            None => {
+                CodePoint { line_number_from: 0, line_number_to: 0, column_number_from: 0, column_number_to: 0 }
+            }
+        };
+        Diagnostic {
+            severity: Severity::Error,
+            code: error.code(),
+            message: String::from(error.message()),
+            file_info
+        }
+    }
+}
+
+/// `text` as a JSON string literal.
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => { json.push_str("\\\""); }
+            '\\' => { json.push_str("\\\\"); }
+            '\n' => { json.push_str("\\n"); }
+            '\r' => { json.push_str("\\r"); }
+            '\t' => { json.push_str("\\t"); }
+            c if (c as u32) < 0x20 => { json.push_str(&format!("\\u{:04x}", c as u32)); }
+            c => { json.push(c); }
+        }
+    }
+    json.push('"');
+    json
+}
+
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error)
+}
+
+impl<'a> From<&'a TokenizationError> for Diagnostic {
+    fn from(error: &'a TokenizationError) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code: error.code(),
+            message: String::from(error.message()),
+            file_info: error.get_file_info()
+        }
+    }
+}
+
+impl<'a> From<&'a ParsingError> for Diagnostic {
+    fn from(error: &'a ParsingError) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code: error.code(),
+            message: String::from(error.message()),
+            file_info: error.get_file_info()
+        }
+    }
+}
+
+/// Runs every phase that can find problems without running the script and
+/// returns all diagnostics sorted by position, warnings included. Phases keep
+/// going after the tokenizer failed, on the tokens it could read.
+pub fn check_all(source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    match parse(Tokenizer::new(source)) {
+        Ok(ast) => {
+            diagnostics.extend(resolver::resolve(&ast));
+            diagnostics.extend(lint::lint(&ast));
+            diagnostics.extend(typecheck::check(&ast));
+        }
+        Err(errors) => { diagnostics.extend(errors.iter().map(Diagnostic::from)); }
+    }
+
+    diagnostics.sort_by_key(|diagnostic| {
+        (diagnostic.file_info.line_number_from, diagnostic.file_info.column_number_from, diagnostic.code.code)
+    });
+    diagnostics.dedup_by(|a, b| {
+        a.code.code == b.code.code && a.message == b.message
+            && a.file_info.line_number_from == b.file_info.line_number_from
+            && a.file_info.column_number_from == b.file_info.column_number_from
+    });
+    diagnostics
+}
@@ -0,0 +1,114 @@
+use tokenizer::Tokenizer;
+use parser::{parse, Ast};
+use interp::{Interp, InterpOptions, InterpValue, InterpError};
+use error::LegError;
+use error_codes;
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::ptr::NonNull;
+
+/// An interpreter that outlives the scripts it runs, for hosts that run
+/// several scripts against the same globals. What one script declares at
+/// its top level is visible to every script evaluated after it.
+pub struct Engine {
+    interp: Interp<'static>,
+    /// Every script run so far. Functions they declare are referenced by
+    /// the interpreter, so they are only freed once it is gone.
+    asts: Vec<NonNull<Ast>>
+}
+
+impl Default for Engine {
+    fn default() -> Engine {
+        Engine::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine {
+            interp: Interp::new(),
+            asts: Vec::new()
+        }
+    }
+
+    /// Runs `source`, returning the value of its last statement. An error
+    /// leaves everything declared before the failing statement in place.
+    pub fn eval_str(&mut self, source: &str) -> Result<InterpValue, LegError> {
+        let ast = parse(Tokenizer::new(source))?;
+        Ok(self.eval_ast(ast)?)
+    }
+
+    /// Runs an already parsed script, which the engine keeps until it is
+    /// dropped.
+    pub fn eval_ast(&mut self, ast: Ast) -> Result<InterpValue, InterpError> {
+        let ast = NonNull::from(Box::leak(Box::new(ast)));
+        self.asts.push(ast);
+        // SAFETY: the AST is only freed when the engine is dropped, after the
+        // interpreter, and the interpreter is never handed out, so nothing
+        // can hold on to the reference for longer.
+        let ast: &'static Ast = unsafe { ast.as_ref() };
+        self.interp.run(ast)
+    }
+
+    pub fn eval_file<P: AsRef<Path>>(&mut self, path: P) -> Result<InterpValue, LegError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|error| {
+            let msg = format!("Failed to read script {}: {}", path.display(), error);
+            InterpError::new(&error_codes::SCRIPT_UNREADABLE, msg)
+        })?;
+        self.eval_str(&source)
+    }
+
+    /// The value of a global, declared by a script or with `set_global`.
+    pub fn get_global(&self, name: &str) -> Option<InterpValue> {
+        self.interp.get_global(name)
+    }
+
+    /// Declares `name` for every script run afterwards. Scripts can shadow
+    /// or assign to it like any global.
+    pub fn set_global(&mut self, name: &str, value: InterpValue) {
+        // The root scope exists for as long as the interpreter does
+        let _ = self.interp.set_global(name, value);
+    }
+
+    /// Exposes a Rust function to scripts as the builtin `name`, called with
+    /// the evaluated arguments.
+    pub fn register_fn<F>(&mut self, name: &str, function: F)
+        where F: FnMut(&[InterpValue]) -> Result<InterpValue, InterpError> + 'static {
+        self.interp.register_fn(name, function);
+    }
+
+    /// Replaces where scripts print to, which is stdout by default. Give it
+    /// a clone of a `SharedBuffer` to read the output back.
+    pub fn set_output<W: Write + 'static>(&mut self, output: W) {
+        self.interp.set_output(Box::new(output));
+    }
+
+    pub fn set_options(&mut self, options: InterpOptions) {
+        self.interp.set_options(options);
+    }
+
+    /// Runs the scripts in the strict dialect, see `Interp::set_strict`.
+    pub fn set_strict(&mut self, enabled: bool) {
+        self.interp.set_strict(enabled);
+    }
+
+    /// The names scripts have declared and can still see, for completion.
+    pub fn visible_names(&self) -> Vec<String> {
+        self.interp.visible_names()
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        // The interpreter refers to the ASTs, so it has to go first
+        self.interp = Interp::new();
+        for ast in self.asts.drain(..) {
+            // SAFETY: the pointer came from `Box::leak` in `eval_ast` and
+            // nothing refers to the AST any more.
+            drop(unsafe { Box::from_raw(ast.as_ptr()) });
+        }
+    }
+}
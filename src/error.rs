@@ -0,0 +1,76 @@
+use tokenizer::TokenizationError;
+use parser::ParsingError;
+use interp::InterpError;
+use error_codes::ErrorCode;
+
+use std::fmt;
+use std::error::Error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+/// Any error running a script can end in, from tokenizing to evaluation.
+#[derive(Debug)]
+pub enum LegError {
+    Tokenization(TokenizationError),
+    /// Every statement that failed to parse and every token that could not
+    /// be read, at least one.
+    Parsing(Vec<ParsingError>),
+    Interp(InterpError)
+}
+
+impl LegError {
+    pub fn code(&self) -> &'static ErrorCode {
+        match *self {
+            LegError::Tokenization(ref error) => { error.code() }
+            LegError::Parsing(ref errors) => { errors[0].code() }
+            LegError::Interp(ref error) => { error.code() }
+        }
+    }
+}
+
+impl Display for LegError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LegError::Tokenization(ref error) => { error.fmt(f) }
+            LegError::Parsing(ref errors) => {
+                for error in errors {
+                    error.fmt(f)?;
+                }
+                Ok(())
+            }
+            LegError::Interp(ref error) => { error.fmt(f) }
+        }
+    }
+}
+
+impl Error for LegError {
+    fn description(&self) -> &str {
+        "Leg error"
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            LegError::Tokenization(ref error) => { Some(error) }
+            LegError::Parsing(ref errors) => { Some(&errors[0]) }
+            LegError::Interp(ref error) => { Some(error) }
+        }
+    }
+}
+
+impl From<TokenizationError> for LegError {
+    fn from(error: TokenizationError) -> LegError {
+        LegError::Tokenization(error)
+    }
+}
+
+impl From<Vec<ParsingError>> for LegError {
+    fn from(errors: Vec<ParsingError>) -> LegError {
+        LegError::Parsing(errors)
+    }
+}
+
+impl From<InterpError> for LegError {
+    fn from(error: InterpError) -> LegError {
+        LegError::Interp(error)
+    }
+}
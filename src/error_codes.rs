@@ -0,0 +1,392 @@
+/// A stable identifier for a kind of error, with the longer explanation
+/// `leg explain <code>` prints. Codes start with L for the tokenizer, P for
+/// the parser, R for the interpreter, T for the type checker and W for
+/// warnings, and are never reused.
+#[derive(Debug)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    pub example: &'static str
+}
+
+pub static MALFORMED_NUMBER: ErrorCode = ErrorCode {
+    code: "L0001",
+    title: "Malformed number literal",
+    description: "Numbers are digits with at most one decimal point, which has to be followed by digits, and an optional exponent. A _ may only separate two digits.",
+    example: "x :: 1.2.3;"
+};
+
+pub static UNTERMINATED_STRING: ErrorCode = ErrorCode {
+    code: "L0002",
+    title: "Unterminated string",
+    description: "The input ended before the closing \" of a string.",
+    example: "x :: \"hello;"
+};
+
+pub static UNTERMINATED_COMMENT: ErrorCode = ErrorCode {
+    code: "L0003",
+    title: "Unterminated comment",
+    description: "Every /* has to be closed by a */, including the ones of comments nested inside the comment.",
+    example: "x :: 1; /* a /* nested */ comment"
+};
+
+pub static INCOMPLETE_TOKEN: ErrorCode = ErrorCode {
+    code: "L0004",
+    title: "Input ended in the middle of a token",
+    description: "The input ended after a character that always starts a longer token, such as the : of :: or :=.",
+    example: "x :"
+};
+
+pub static INVALID_COLON: ErrorCode = ErrorCode {
+    code: "L0005",
+    title: "Invalid character after :",
+    description: "A : has to start :: or :=, or be directly followed by a type name.",
+    example: "x : Number := 1;"
+};
+
+pub static INVALID_CHARACTER: ErrorCode = ErrorCode {
+    code: "L0006",
+    title: "Invalid character",
+    description: "The character can not start any token.",
+    example: "x :: 1 @ 2;"
+};
+
+pub static INVALID_ESCAPE: ErrorCode = ErrorCode {
+    code: "L0007",
+    title: "Invalid escape sequence",
+    description: "Strings support the escapes \\n, \\t, \\\\, \\\" and \\u{XXXX}, where XXXX is the hexadecimal code of a character.",
+    example: "x :: \"C:\\dir\";"
+};
+
+pub static UNEXPECTED_TOKEN: ErrorCode = ErrorCode {
+    code: "P0101",
+    title: "Unexpected token",
+    description: "The token can not appear at this point of the statement being parsed.",
+    example: "f :: (a b) { };"
+};
+
+pub static UNEXPECTED_END_OF_INPUT: ErrorCode = ErrorCode {
+    code: "P0102",
+    title: "Unexpected end of input",
+    description: "The input ended before the statement being parsed was complete.",
+    example: "P :: { a"
+};
+
+pub static INVALID_NUMBER: ErrorCode = ErrorCode {
+    code: "P0103",
+    title: "Invalid number",
+    description: "The number literal could not be converted to a number.",
+    example: ""
+};
+
+pub static INVALID_STRING: ErrorCode = ErrorCode {
+    code: "P0104",
+    title: "Invalid string",
+    description: "A string literal has to start and end with \".",
+    example: "x :: \"hello;"
+};
+
+pub static MISSING_CLOSING_PARENTHESIS: ErrorCode = ErrorCode {
+    code: "P0105",
+    title: "Missing closing parenthesis",
+    description: "Every ( in an expression has to be closed by a ).",
+    example: "x :: (1 + 2;"
+};
+
+pub static MISSING_OPERAND: ErrorCode = ErrorCode {
+    code: "P0106",
+    title: "Missing operand",
+    description: "An operator is missing the value it applies to.",
+    example: "x :: 1 +;"
+};
+
+pub static INVALID_OPERATOR: ErrorCode = ErrorCode {
+    code: "P0107",
+    title: "Invalid operator",
+    description: "The operator can not be used in an expression.",
+    example: "x :: 1 ~ 2;"
+};
+
+pub static CHAINED_COMPARISON: ErrorCode = ErrorCode {
+    code: "P0108",
+    title: "Chained comparison",
+    description: "Comparisons can not be chained, compare each pair separately or add parentheses.",
+    example: "x :: 1 < 2 < 3;"
+};
+
+pub static NULL_ASSIGNMENT: ErrorCode = ErrorCode {
+    code: "P0109",
+    title: "Assignment to a literal",
+    description: "null, true and false are values, not variables, and can not be assigned to.",
+    example: "null := 1;"
+};
+
+pub static MISSING_SEMICOLON: ErrorCode = ErrorCode {
+    code: "P0110",
+    title: "Missing semicolon",
+    description: "Statements have to end with a ;.",
+    example: "x :: 1\ny :: 2;"
+};
+
+pub static INDEX_ASSIGNMENT: ErrorCode = ErrorCode {
+    code: "P0111",
+    title: "Assignment to an index",
+    description: "Elements can not be assigned to through an index, `with` returns a list with the element replaced.",
+    example: "l :: ilist(1, 2);\nl[0] = 3;"
+};
+
+pub static LOOP_CONTROL_OUTSIDE_LOOP: ErrorCode = ErrorCode {
+    code: "P0112",
+    title: "Break outside a loop",
+    description: "break and continue leave or restart the loop around them, so they can only be used inside the body of a while or for loop. A function called from a loop is not inside it.",
+    example: "f :: () { break; };"
+};
+
+pub static RETURN_OUTSIDE_FUNCTION: ErrorCode = ErrorCode {
+    code: "P0113",
+    title: "Return outside a function",
+    description: "return leaves the function around it, so it can only be used inside a function body.",
+    example: "return 1;"
+};
+
+pub static UNKNOWN_PRAGMA: ErrorCode = ErrorCode {
+    code: "P0114",
+    title: "Unknown pragma",
+    description: "A `#pragma` line names a setting that does not exist. The pragmas are bignum and strict.",
+    example: "#pragma fast"
+};
+
+pub static UNKNOWN_NAME: ErrorCode = ErrorCode {
+    code: "R0201",
+    title: "Unknown name",
+    description: "No variable or function with this name is visible from where it is used.",
+    example: "x :: y + 1;"
+};
+
+pub static NOT_A_FUNCTION: ErrorCode = ErrorCode {
+    code: "R0202",
+    title: "Not a function",
+    description: "Only functions can be called.",
+    example: "x :: 1;\nx();"
+};
+
+pub static ARITY_MISMATCH: ErrorCode = ErrorCode {
+    code: "R0203",
+    title: "Wrong number of arguments",
+    description: "A function was called with a different number of arguments than it declares.",
+    example: "f :: (a, b) { a + b; };\nf(1);"
+};
+
+pub static TYPE_MISMATCH: ErrorCode = ErrorCode {
+    code: "R0204",
+    title: "Type mismatch",
+    description: "The value has a type the operation can not be applied to.",
+    example: "x :: \"a\" * 2;"
+};
+
+pub static NULL_OPERAND: ErrorCode = ErrorCode {
+    code: "R0205",
+    title: "Operator applied to null",
+    description: "Operators other than == and != can not be applied to null.",
+    example: "x :: null + 1;"
+};
+
+pub static INTEGER_REQUIRED: ErrorCode = ErrorCode {
+    code: "R0206",
+    title: "Integer required",
+    description: "The operation only works on whole numbers.",
+    example: "x :: 1.5 & 1;"
+};
+
+pub static INVALID_SHIFT: ErrorCode = ErrorCode {
+    code: "R0207",
+    title: "Invalid shift amount",
+    description: "Shifts have to be by 0 to 63 bits.",
+    example: "x :: 1 << 64;"
+};
+
+pub static OVERFLOW: ErrorCode = ErrorCode {
+    code: "R0208",
+    title: "Arithmetic overflow",
+    description: "With --strict-overflow, a result too large for a Number or an Int is an error instead of an infinity or an Int that wraps around.",
+    example: "x :: 10 ^ 400;"
+};
+
+pub static DIVISION_BY_ZERO: ErrorCode = ErrorCode {
+    code: "R0209",
+    title: "Division by zero",
+    description: "Exact arithmetic, and arithmetic with --strict-division, can not divide by zero.",
+    example: "#pragma bignum\nx :: 1 / 0;"
+};
+
+pub static STACK_OVERFLOW: ErrorCode = ErrorCode {
+    code: "R0210",
+    title: "Stack overflow",
+    description: "Calls nested deeper than the interpreter allows, usually from recursion without a base case.",
+    example: "f :: () { f(); };\nf();"
+};
+
+pub static INVALID_STRUCT: ErrorCode = ErrorCode {
+    code: "R0211",
+    title: "Invalid struct declaration",
+    description: "Struct fields have to be unique and each have a type.",
+    example: "P :: { x :Number x :Number };"
+};
+
+pub static UNKNOWN_FIELD: ErrorCode = ErrorCode {
+    code: "R0212",
+    title: "Unknown field",
+    description: "The struct does not declare a field with this name.",
+    example: ""
+};
+
+pub static NOT_ALLOWED: ErrorCode = ErrorCode {
+    code: "R0213",
+    title: "Builtin not allowed",
+    description: "The policy of the embedding program denied a call to a side effecting builtin.",
+    example: "print(1);"
+};
+
+pub static MISSING_BODY: ErrorCode = ErrorCode {
+    code: "R0214",
+    title: "Missing body",
+    description: "if and while have to be followed by a block.",
+    example: "if(1);"
+};
+
+pub static OUTPUT_FAILED: ErrorCode = ErrorCode {
+    code: "R0215",
+    title: "Failed to write output",
+    description: "print or debug could not write to the output.",
+    example: "print(1);"
+};
+
+pub static BIGNUM_UNAVAILABLE: ErrorCode = ErrorCode {
+    code: "R0216",
+    title: "Exact arithmetic unavailable",
+    description: "#pragma bignum needs a build with --features bignum, and only works on finite numbers.",
+    example: "#pragma bignum\nx :: 1;"
+};
+
+pub static REPLAY_MISMATCH: ErrorCode = ErrorCode {
+    code: "R0217",
+    title: "Replay log does not match",
+    description: "When replaying, the script has to call the nondeterministic builtins in the same order as the recorded run.",
+    example: ""
+};
+
+pub static RESOURCE_LIMIT: ErrorCode = ErrorCode {
+    code: "R0218",
+    title: "Resource limit exceeded",
+    description: "The script made a string or array longer, declared more structs, used more memory, took more steps or ran for longer than the embedding program or the --max-memory, --max-steps and --timeout flags allow.",
+    example: ""
+};
+
+pub static INDEX_OUT_OF_BOUNDS: ErrorCode = ErrorCode {
+    code: "R0219",
+    title: "Index out of bounds",
+    description: "An element was read, replaced or removed at an index past the end of the list.",
+    example: "get(ilist(1, 2), 2);"
+};
+
+pub static NOT_A_NUMBER: ErrorCode = ErrorCode {
+    code: "R0220",
+    title: "Result is not a number",
+    description: "With --strict-division, arithmetic that has no numeric result is an error instead of NaN.",
+    example: "x :: (0 - 1) ^ 0.5;"
+};
+
+pub static SCRIPT_UNREADABLE: ErrorCode = ErrorCode {
+    code: "R0221",
+    title: "Script could not be read",
+    description: "The embedding program asked to run a script file that does not exist or could not be read as UTF-8 text.",
+    example: ""
+};
+
+pub static INVALID_FORMAT: ErrorCode = ErrorCode {
+    code: "R0222",
+    title: "Invalid format string",
+    description: "A format string has a brace that is not part of a {} or {:?} placeholder. Write {{ and }} for literal braces.",
+    example: "print(format(\"{x}\", 1));"
+};
+
+pub static GRAPHICS_FAILED: ErrorCode = ErrorCode {
+    code: "R0223",
+    title: "Graphics failed",
+    description: "A window or drawing builtin could not run. They need a build with --features sdl and a display, and window handles must be of a window window_open returned.",
+    example: "window_open(\"game\", 640, 480);"
+};
+
+pub static UNKNOWN_TYPE: ErrorCode = ErrorCode {
+    code: "R0224",
+    title: "Unknown type",
+    description: "A struct field is declared with a type that is neither a builtin type like Int or String nor a struct visible where the struct is used.",
+    example: "P :: { x :int };\nP(1);"
+};
+
+pub static INTERNAL: ErrorCode = ErrorCode {
+    code: "R0299",
+    title: "Internal interpreter error",
+    description: "The interpreter reached a state it should never be in. Please report the script that caused it.",
+    example: ""
+};
+
+pub static ANNOTATION_MISMATCH: ErrorCode = ErrorCode {
+    code: "T0301",
+    title: "Type annotation mismatch",
+    description: "With --typecheck or in strict mode, a value given to a name or parameter annotated with a :Type, or an operand, has a type that is known before running and does not fit.",
+    example: "x :String := 5;"
+};
+
+pub static UNUSED_BINDING: ErrorCode = ErrorCode {
+    code: "W0001",
+    title: "Unused binding",
+    description: "A name is declared but never read. Names starting with _ are never reported.",
+    example: "f :: (a, b) {\n    return a;\n};"
+};
+
+pub static UNREACHABLE_CODE: ErrorCode = ErrorCode {
+    code: "W0002",
+    title: "Unreachable code",
+    description: "A statement follows a return, break or continue in the same block, and so never runs.",
+    example: "f :: () {\n    return 1;\n    print(2);\n};"
+};
+
+pub static SHADOWED_NAME: ErrorCode = ErrorCode {
+    code: "W0003",
+    title: "Shadowed name",
+    description: "A let declares a name that an enclosing scope, or an earlier statement of the same block, already declares. The earlier name can not be reached until the block ends.",
+    example: "x := 1;\n{\n    let x = 2;\n};"
+};
+
+pub static ERROR_CODES: [&ErrorCode; 50] = [
+    &MALFORMED_NUMBER, &UNTERMINATED_STRING, &UNTERMINATED_COMMENT, &INCOMPLETE_TOKEN, &INVALID_COLON, &INVALID_CHARACTER,
+    &INVALID_ESCAPE,
+    &UNEXPECTED_TOKEN, &UNEXPECTED_END_OF_INPUT, &INVALID_NUMBER, &INVALID_STRING, &MISSING_CLOSING_PARENTHESIS,
+    &MISSING_OPERAND, &INVALID_OPERATOR, &CHAINED_COMPARISON, &NULL_ASSIGNMENT, &MISSING_SEMICOLON,
+    &INDEX_ASSIGNMENT, &LOOP_CONTROL_OUTSIDE_LOOP, &RETURN_OUTSIDE_FUNCTION, &UNKNOWN_PRAGMA,
+    &UNKNOWN_NAME, &NOT_A_FUNCTION, &ARITY_MISMATCH, &TYPE_MISMATCH, &NULL_OPERAND, &INTEGER_REQUIRED, &INVALID_SHIFT,
+    &OVERFLOW, &DIVISION_BY_ZERO, &STACK_OVERFLOW, &INVALID_STRUCT, &UNKNOWN_FIELD, &NOT_ALLOWED, &MISSING_BODY,
+    &OUTPUT_FAILED, &BIGNUM_UNAVAILABLE, &REPLAY_MISMATCH, &RESOURCE_LIMIT, &INDEX_OUT_OF_BOUNDS, &NOT_A_NUMBER,
+    &SCRIPT_UNREADABLE, &INVALID_FORMAT, &GRAPHICS_FAILED, &UNKNOWN_TYPE, &INTERNAL,
+    &ANNOTATION_MISMATCH,
+    &UNUSED_BINDING, &UNREACHABLE_CODE, &SHADOWED_NAME
+];
+
+pub fn find(code: &str) -> Option<&'static ErrorCode> {
+    ERROR_CODES.iter().find(|error_code| error_code.code.eq_ignore_ascii_case(code)).cloned()
+}
+
+/// The text `leg explain` prints for `code`.
+pub fn explain(code: &str) -> Option<String> {
+    let error_code = find(code)?;
+    let mut text = format!("{}: {}\n\n{}\n", error_code.code, error_code.title, error_code.description);
+    if !error_code.example.is_empty() {
+        text.push_str("\nFor example:\n\n");
+        for line in error_code.example.lines() {
+            text.push_str(&format!("    {}\n", line));
+        }
+    }
+    Some(text)
+}
@@ -9,7 +9,7 @@ pub struct CodePoint {
 
 impl Clone for CodePoint {
     fn clone(&self) -> Self {
-        return CodePoint {
+        CodePoint {
             line_number_from: self.line_number_from,
             line_number_to: self.line_number_to,
 
@@ -17,4 +17,40 @@ impl Clone for CodePoint {
             column_number_to: self.column_number_to,
         }
     }
-}
\ No newline at end of file
+}
+
+impl CodePoint {
+    /// The code from the start of `from` to the end of `to`.
+    pub fn spanning(from: &CodePoint, to: &CodePoint) -> CodePoint {
+        CodePoint {
+            line_number_from: from.line_number_from,
+            line_number_to: to.line_number_to,
+
+            column_number_from: from.column_number_from,
+            column_number_to: to.column_number_to,
+        }
+    }
+
+    /// The text this code point covers in `source`. Columns count characters
+    /// from 1 and the end column is inclusive.
+    pub fn source_text(&self, source: &str) -> String {
+        let mut text = String::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            if line_number < self.line_number_from || line_number > self.line_number_to {
+                continue;
+            }
+
+            let first = if line_number == self.line_number_from { self.column_number_from.max(1) } else { 1 };
+            let last = if line_number == self.line_number_to { self.column_number_to } else { usize::MAX };
+
+            if line_number != self.line_number_from {
+                text.push('\n');
+            }
+            let part: String = line.chars().skip(first - 1).take(last.saturating_sub(first - 1)).collect();
+            text.push_str(&part);
+        }
+        text
+    }
+}
@@ -0,0 +1,171 @@
+use interp::{InterpValue, InterpError};
+use error_codes;
+
+/// `noun` after the indefinite article it takes, as in "an Int" or
+/// "a String".
+pub fn with_article(noun: &str) -> String {
+    match noun.chars().next() {
+        Some('A' | 'E' | 'I' | 'O' | 'U' | 'a' | 'e' | 'i' | 'o' | 'u') => { format!("an {}", noun) }
+        _ => { format!("a {}", noun) }
+    }
+}
+
+/// `count` followed by `noun`, plural unless there is one, as in
+/// "1 argument" or "2 arguments".
+pub fn counted(count: usize, noun: &str) -> String {
+    if count == 1 { format!("1 {}", noun) } else { format!("{} {}s", count, noun) }
+}
+
+/// How a value reads when printed. Strings are printed as they are, the
+/// strings inside a collection are quoted.
+pub fn format_value(val: InterpValue) -> String {
+    match val {
+        InterpValue::InterpString(val) => {val}
+        other => {format_with(other, false)}
+    }
+}
+
+/// Like `format_value`, but strings are quoted and escaped so that the
+/// string "5" can be told apart from the number 5, and booleans, functions
+/// and structs are tagged with their kind and id.
+pub fn format_debug(val: InterpValue) -> String {
+    format_with(val, true)
+}
+
+fn format_with(val: InterpValue, debug: bool) -> String {
+    use interp::InterpValue::*;
+    let format_all = |values: &mut dyn Iterator<Item = &InterpValue>| {
+        let values: Vec<String> = values.map(|value| format_with(value.clone(), debug)).collect();
+        values.join(", ")
+    };
+    match val {
+        InterpVoid => {String::from("null")}
+        InterpBoolean(val) if debug => {format!("BOOLEAN {{{}}}", val)}
+        InterpBoolean(val) => {val.to_string()}
+        InterpNumber(num) => {num.to_string()}
+        InterpInt(num) => {num.to_string()}
+        #[cfg(feature = "bignum")]
+        InterpBigNumber(num) => {num.normalized().to_string()}
+        InterpString(val) => {format!("\"{}\"", val.escape_debug())}
+        InterpFunction{id, ..} | InterpBoundFunction{id, ..} if debug => {format!("FUNCTION {}", id)}
+        InterpFunction{..} | InterpBoundFunction{..} => {String::from("<function>")}
+        InterpStruct(i) if debug => {format!("STRUCT {}", i)}
+        InterpStruct(_) => {String::from("<struct>")}
+        InterpTuple(values) => {format!("({})", format_all(&mut values.iter()))}
+        InterpBuilder(buffer) => {format!("BUILDER {{{}}}", buffer.borrow())}
+        InterpList(list) => {format!("[{}]", format_all(&mut list.iter()))}
+        InterpMap(map) => {
+            let entries: Vec<String> = map.iter()
+                .map(|entry| format!("{}: {}", format_with(entry.0.to_value(), debug), format_with(entry.1.clone(), debug)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        InterpIterator(_) => {String::from("ITERATOR")}
+        InterpInstance{struct_id, fields, ..} if debug => {
+            format!("STRUCT {} {{{}}}", struct_id, format_all(&mut fields.borrow().iter()))
+        }
+        InterpInstance{name, fields, ..} => {format!("{}({})", name, format_all(&mut fields.borrow().iter()))}
+        InterpArray(values) => {format!("[{}]", format_all(&mut values.borrow().iter()))}
+    }
+}
+
+/// One piece of a format string: text to copy, or a placeholder for the
+/// next value.
+enum Piece<'t> {
+    Text(&'t str),
+    /// `{}`, or `{:?}` for the debug form.
+    Value { debug: bool }
+}
+
+fn invalid_format(template: &str) -> InterpError {
+    let msg = format!("Invalid format string {:?}, write {{{{ and }}}} for braces", template);
+    InterpError::new(&error_codes::INVALID_FORMAT, msg)
+}
+
+/// Splits a format string into text and placeholders. `{{` and `}}` are
+/// literal braces.
+fn pieces<'t>(template: &'t str) -> Result<Vec<Piece<'t>>, InterpError> {
+    let mut pieces = Vec::new();
+    let mut rest = template;
+    while let Some(index) = rest.find(['{', '}']) {
+        if index > 0 {
+            pieces.push(Piece::Text(&rest[..index]));
+        }
+        rest = &rest[index..];
+        if rest.starts_with("{{") {
+            pieces.push(Piece::Text("{"));
+            rest = &rest[2..];
+        } else if rest.starts_with("}}") {
+            pieces.push(Piece::Text("}"));
+            rest = &rest[2..];
+        } else if rest.starts_with("{}") {
+            pieces.push(Piece::Value { debug: false });
+            rest = &rest[2..];
+        } else if rest.starts_with("{:?}") {
+            pieces.push(Piece::Value { debug: true });
+            rest = &rest[4..];
+        } else {
+            return Err(invalid_format(template));
+        }
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest));
+    }
+    Ok(pieces)
+}
+
+/// Whether `print` should format the arguments after the first into it:
+/// the first is a string with a `{}` or `{:?}` placeholder, and there are
+/// values to fill them with. A lone string is printed as it is, so printing
+/// the result of `format` never formats it twice.
+pub fn is_template(args: &[InterpValue]) -> bool {
+    match args.first() {
+        Some(InterpValue::InterpString(template)) if args.len() > 1 => {
+            template.contains("{}") || template.contains("{:?}")
+        }
+        _ => { false }
+    }
+}
+
+/// Replaces every placeholder of `template` with the next of `values`,
+/// which have to be exactly as many.
+pub fn format(template: &str, values: Vec<InterpValue>) -> Result<String, InterpError> {
+    let pieces = pieces(template)?;
+    let placeholders = pieces.iter().filter(|piece| matches!(piece, &&Piece::Value{..})).count();
+    if placeholders != values.len() {
+        let msg = format!("The format string {:?} has {} but was given {}",
+                          template, counted(placeholders, "placeholder"), counted(values.len(), "value"));
+        return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg));
+    }
+
+    let mut values = values.into_iter();
+    let mut text = String::new();
+    for piece in pieces {
+        match piece {
+            Piece::Text(part) => { text.push_str(part); }
+            Piece::Value { debug } => {
+                let value = values.next().unwrap_or(InterpValue::InterpVoid);
+                text.push_str(&if debug { format_debug(value) } else { format_value(value) });
+            }
+        }
+    }
+    Ok(text)
+}
+
+/// `format(template, a, b, ...)`
+pub fn format_builtin(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    let mut args = args.into_iter();
+    match args.next() {
+        Some(InterpValue::InterpString(template)) => {
+            Ok(InterpValue::InterpString(format(&template, args.collect())?))
+        }
+        Some(other) => {
+            let msg = format!("format expects a String to format into but was given {}", with_article(other.type_name()));
+            Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+        }
+        None => {
+            let msg = "format expects a String to format into".to_string();
+            Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg))
+        }
+    }
+}
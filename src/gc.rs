@@ -0,0 +1,104 @@
+use interp::InterpValue;
+use iterator::IterSource;
+
+use std::collections::HashSet;
+use std::mem;
+
+/// Adds the ids of the closures `value` keeps alive to `ids`, looking
+/// through everything the value contains. `seen` holds the shared values
+/// already looked through, since arrays and instances can contain themselves.
+pub fn closures_of(value: &InterpValue, seen: &mut HashSet<*const ()>, ids: &mut Vec<usize>) {
+    match value {
+        &InterpValue::InterpFunction{closure_id, ..} => {
+            ids.push(closure_id);
+        }
+        &InterpValue::InterpBoundFunction{closure_id, ref arguments, ..} => {
+            ids.push(closure_id);
+            for argument in arguments.iter() {
+                closures_of(argument, seen, ids);
+            }
+        }
+        InterpValue::InterpTuple(values) => {
+            for value in values.iter() {
+                closures_of(value, seen, ids);
+            }
+        }
+        InterpValue::InterpList(list) => {
+            for value in list.iter() {
+                closures_of(value, seen, ids);
+            }
+        }
+        InterpValue::InterpMap(map) => {
+            for entry in map.iter() {
+                closures_of(&entry.1, seen, ids);
+            }
+        }
+        InterpValue::InterpArray(array)
+            if seen.insert(array.as_ptr() as *const ()) => {
+                for value in array.borrow().iter() {
+                    closures_of(value, seen, ids);
+                }
+            }
+        InterpValue::InterpInstance{fields, ..}
+            if seen.insert(fields.as_ptr() as *const ()) => {
+                for value in fields.borrow().iter() {
+                    closures_of(value, seen, ids);
+                }
+            }
+        InterpValue::InterpIterator(source)
+            if seen.insert(source.as_ptr() as *const ()) => {
+                match *source.borrow() {
+                    IterSource::List{ref list, ..} => {
+                        closures_of(&InterpValue::InterpList(list.clone()), seen, ids);
+                    }
+                    IterSource::Array{ref array, ..} => {
+                        closures_of(&InterpValue::InterpArray(array.clone()), seen, ids);
+                    }
+                    IterSource::Map{ref map, ..} => {
+                        closures_of(&InterpValue::InterpMap(map.clone()), seen, ids);
+                    }
+                    IterSource::Function(ref function) => {
+                        closures_of(function, seen, ids);
+                    }
+                    IterSource::Range{..} => {}
+                }
+            }
+        _ => {}
+    }
+}
+
+/// Roughly how many bytes `value` keeps on the heap, not counting the value
+/// itself. Shared values in `seen` were counted already and add nothing.
+pub fn heap_size(value: &InterpValue, seen: &mut HashSet<*const ()>) -> usize {
+    let slot = mem::size_of::<InterpValue>();
+    match value {
+        InterpValue::InterpString(string) => { string.len() }
+        InterpValue::InterpBuilder(buffer)
+            if seen.insert(buffer.as_ptr() as *const ()) => { buffer.borrow().len() }
+        InterpValue::InterpBoundFunction{arguments, ..} => {
+            arguments.iter().map(|argument| slot + heap_size(argument, seen)).sum()
+        }
+        InterpValue::InterpTuple(values) => {
+            values.iter().map(|value| slot + heap_size(value, seen)).sum()
+        }
+        InterpValue::InterpList(list) => {
+            list.iter().map(|value| slot + heap_size(value, seen)).sum()
+        }
+        InterpValue::InterpMap(map) => {
+            map.iter().map(|entry| 2 * slot + heap_size(&entry.1, seen)).sum()
+        }
+        InterpValue::InterpArray(array) => {
+            if !seen.insert(array.as_ptr() as *const ()) {
+                return 0;
+            }
+            array.borrow().iter().map(|value| slot + heap_size(value, seen)).sum()
+        }
+        InterpValue::InterpInstance{fields, ..} => {
+            if !seen.insert(fields.as_ptr() as *const ()) {
+                return 0;
+            }
+            fields.borrow().iter().map(|value| slot + heap_size(value, seen)).sum()
+        }
+        _ => { 0 }
+    }
+}
@@ -0,0 +1,78 @@
+use tokenizer::Tokenizer;
+use parser::{parse, Ast};
+
+use std::fs;
+use std::path::PathBuf;
+use std::ptr::NonNull;
+use std::time::SystemTime;
+
+/// Polls a script file for changes by its modification time, which is
+/// cheap enough to do once per frame.
+pub struct ScriptWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>
+}
+
+impl ScriptWatcher {
+    pub fn new(path: &str) -> ScriptWatcher {
+        let path = PathBuf::from(path);
+        let modified = modified_time(&path);
+        ScriptWatcher {
+            path,
+            modified
+        }
+    }
+
+    /// The new source of the script if it changed since the last poll.
+    pub fn poll(&mut self) -> Option<String> {
+        let modified = modified_time(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        fs::read_to_string(&self.path).ok()
+    }
+}
+
+fn modified_time(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Parses a changed script, rendering every error for the host to report.
+pub fn parse_script(source: &str) -> Result<Ast, String> {
+    parse(Tokenizer::new(source)).map_err(|errors| errors.iter().map(|error| error.to_string()).collect::<String>())
+}
+
+/// The versions of a script reloaded into an interpreter. Function values
+/// refer to their declaration for as long as the interpreter runs, so every
+/// version is kept until the interpreter is dropped. A version is only added
+/// when the file is saved, not every frame.
+#[derive(Default)]
+pub struct ReloadedScripts {
+    asts: Vec<NonNull<Ast>>
+}
+
+impl ReloadedScripts {
+    /// Keeps `ast` until `self` is dropped.
+    ///
+    /// # Safety
+    ///
+    /// The returned reference must not be used after `self` is dropped.
+    pub unsafe fn keep<'a>(&mut self, ast: Ast) -> &'a Ast {
+        let ast = NonNull::from(Box::leak(Box::new(ast)));
+        self.asts.push(ast);
+        // SAFETY: the AST lives until `self` is dropped, which the caller
+        // promises to outlive the reference.
+        unsafe { ast.as_ref() }
+    }
+}
+
+impl Drop for ReloadedScripts {
+    fn drop(&mut self) {
+        for ast in self.asts.drain(..) {
+            // SAFETY: the pointer came from `Box::leak` in `keep` and the
+            // references handed out are gone with the interpreter.
+            drop(unsafe { Box::from_raw(ast.as_ptr()) });
+        }
+    }
+}
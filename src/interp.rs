@@ -1,31 +1,134 @@
-use parser::{Ast, AstNodeType, AstFunctionDeclaration, AstStructDeclaration, AstBlock};
+use parser::{Ast, AstNodeType, AstFunctionDeclaration, AstFunctionCall, AstUnaryOperator, AstLogicalOperator, AstStructDeclaration, AstBlock, AstStatement, AstPattern};
+use parser::{AstAssignment, AstAlias, AstDestructure, AstOperatorCall, AstUnaryOperatorCall, AstLogicalOperatorCall, AstFieldAccess, AstFieldAssignment, AstMatch, AstOperator};
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
 use std::mem;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::thread;
 
 use std::fmt;
 use std::error::Error;
 use std::fmt::Display;
 use std::fmt::Formatter;
 
-use leg_sdl;
+use output;
+use output::Style;
+use leg_sdl::Sdl;
+use hot_reload;
+use hot_reload::{ScriptWatcher, ReloadedScripts};
+use format;
+use format::{with_article, counted};
 use operators;
+use suggest;
+use random::Rng;
+use replay::{Effect, EffectMode};
+use persistent::{PersistentVec, PersistentMap};
+use collections;
+use gc;
+use collections::MapKey;
+use iterator;
+use iterator::IterSource;
+use error_codes;
+use error_codes::ErrorCode;
+use file_info::CodePoint;
+
+#[cfg(feature = "bignum")]
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+#[cfg(feature = "bignum")]
+use std::str::FromStr;
+
+/// Functions dispatched by the interpreter itself rather than looked up in a
+/// closure, which every interpreter starts with. Hosts add their own with
+/// `register_fn`.
+pub const BUILTINS: [&str; 53] = ["while", "for", "if", "print", "println", "debug", "format", "is_null", "random", "rand_int", "rand_normal",
+                                  "shuffle", "choice", "stats", "bind",
+                                  "builder", "push", "build", "ilist", "imap", "with", "without", "get", "len",
+                                  "iter", "next", "range", "pop", "insert", "remove", "slice", "concat", "join",
+                                  "now", "sleep", "timer", "elapsed", "random_range", "seed",
+                                  "window_open", "window_close", "set_color", "clear", "present", "draw_rect", "draw_line",
+                                  "draw_circle", "poll_events", "key_down", "mouse_pos", "run_loop",
+                                  "load_image", "draw_image"];
+
+/// Builtins that affect the world outside the interpreter, which are the
+/// ones a `BuiltinPolicy` is consulted for.
+pub const SIDE_EFFECTING_BUILTINS: [&str; 17] = ["print", "println", "debug", "window_open", "window_close", "set_color", "clear",
+                                                 "present", "draw_rect", "draw_line", "draw_circle", "poll_events",
+                                                 "key_down", "mouse_pos", "run_loop", "load_image", "draw_image"];
+
+/// Parsing and walking the syntax tree recurse on the native stack, which is
+/// grown by another `STACK_SEGMENT` bytes whenever less than `STACK_RED_ZONE`
+/// is left. Evaluation keeps its own stack of tasks instead, so only
+/// `stack_size` limits how deep a script may nest.
+pub const STACK_RED_ZONE: usize = 256 * 1024;
+pub const STACK_SEGMENT: usize = 4 * 1024 * 1024;
+
+/// Fewer live closures than this are never collected.
+const MIN_COLLECTION: usize = 1024;
+
+/// The closure holding the globals, which `Interp::new` creates first.
+const ROOT_CLOSURE_ID: usize = 0;
 
 #[derive(Debug)]
 pub struct InterpError {
-    desc: String
+    code: &'static ErrorCode,
+    desc: String,
+    /// The innermost node that was being evaluated when the error happened.
+    span: Option<CodePoint>,
+    /// The function calls the error happened inside of, innermost first.
+    backtrace: Vec<TraceFrame>
+}
+
+/// A function call that was running when an error happened.
+#[derive(Debug)]
+pub struct TraceFrame {
+    pub name: String,
+    pub call_site: CodePoint
 }
 
 impl Display for InterpError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        writeln!(f, "InterpError: \n{}", self.desc)
+        writeln!(f, "error[{}]: {}", self.code.code, self.desc)?;
+        if let Some(ref span) = self.span {
+            writeln!(f, " --> line {}, column {}", span.line_number_from, span.column_number_from)?;
+        }
+        for frame in &self.backtrace {
+            writeln!(f, "  in {} called at line {}, column {}", frame.name, frame.call_site.line_number_from,
+                     frame.call_site.column_number_from)?;
+        }
+        Ok(())
     }
 }
 
 impl InterpError {
-    pub fn new(desc: String) -> InterpError {
-        return InterpError {
-            desc: desc
-        };
+    pub fn new(code: &'static ErrorCode, desc: String) -> InterpError {
+        InterpError {
+            code,
+            desc,
+            span: None,
+            backtrace: Vec::new()
+        }
+    }
+
+    pub fn code(&self) -> &'static ErrorCode {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.desc
+    }
+
+    pub fn span(&self) -> Option<&CodePoint> {
+        self.span.as_ref()
+    }
+
+    pub fn backtrace(&self) -> &[TraceFrame] {
+        &self.backtrace
     }
 }
 
@@ -34,164 +137,765 @@ impl Error for InterpError {
         "Interpreter error!!!"
     }
 
-    fn cause(&self) -> Option<&Error> {
+    fn cause(&self) -> Option<&dyn Error> {
         None
     }
 }
 
+/// Why evaluation stopped before a node produced its value. Control flow
+/// travels the same way as errors, so `?` unwinds every node in between.
+#[derive(Debug)]
+enum Unwind {
+    Error(InterpError),
+    /// `return`, caught by the function call it returns from.
+    Return(InterpValue),
+    /// `break` and `continue`, caught by the innermost loop.
+    Break,
+    Continue,
+    /// `return f(..)`, made by the function call it returns from once the
+    /// returning function's frames are gone, so recursion in tail position
+    /// runs in constant stack.
+    TailCall(TailCall)
+}
+
+#[derive(Debug)]
+struct TailCall {
+    name: String,
+    callee: InterpValue,
+    args: Vec<InterpValue>
+}
+
+impl From<InterpError> for Unwind {
+    fn from(error: InterpError) -> Unwind {
+        Unwind::Error(error)
+    }
+}
+
+impl Unwind {
+    /// Control flow that escaped everything that could have caught it.
+    fn into_error(self) -> InterpError {
+        match self {
+            Unwind::Error(error) => { error }
+            Unwind::Return(_) | Unwind::TailCall(_) => {
+                let msg = "return can only be used inside a function".to_string();
+                InterpError::new(&error_codes::RETURN_OUTSIDE_FUNCTION, msg)
+            }
+            Unwind::Break => {
+                let msg = "break can only be used inside a loop".to_string();
+                InterpError::new(&error_codes::LOOP_CONTROL_OUTSIDE_LOOP, msg)
+            }
+            Unwind::Continue => {
+                let msg = "continue can only be used inside a loop".to_string();
+                InterpError::new(&error_codes::LOOP_CONTROL_OUTSIDE_LOOP, msg)
+            }
+        }
+    }
+}
+
+type Evaluation = Result<InterpValue, Unwind>;
+
+/// Evaluation still to be done. Tasks are kept on a stack of their own
+/// rather than the native one, so a script nests as deep as `stack_size`
+/// allows however the host was built. Every node leaves one value on the
+/// value stack, where the tasks scheduled after it find their operands.
+enum Task<'a> {
+    /// Evaluates `node`, keeping only the first value of a tuple unless
+    /// `whole`.
+    Eval { node: &'a AstNodeType, whole: bool },
+    /// `node` left its value. Errors unwinding through here happened in it.
+    Produced { node: &'a AstNodeType, whole: bool },
+    /// Runs the statements of `block` from `next` on, the value of each
+    /// replacing the one before.
+    Statements { block: &'a AstBlock, next: usize },
+    Statement(&'a AstStatement),
+    /// Shows the hooks the value the statement left.
+    FinishStatement(&'a AstStatement),
+    /// Leaves the frame of a block, once its statements are done or unwound.
+    PopFrame,
+    /// Replaces the Array on top with its elements.
+    Spread,
+    /// Calls `call` with the values from `mark` on as its arguments.
+    Call { node: &'a AstNodeType, call: &'a AstFunctionCall, mark: usize },
+    /// The body of a function is running in the frame on top. Catches what
+    /// it returns, and makes the tail calls it returns.
+    Function { node: &'a AstNodeType, name: String, mark: usize },
+    TailCall { call: &'a AstFunctionCall, mark: usize },
+    Return,
+    Assign(&'a AstAssignment),
+    Alias(&'a AstAlias),
+    Destructure(&'a AstDestructure),
+    Operator(&'a AstOperatorCall),
+    Unary(&'a AstUnaryOperatorCall),
+    Logical(&'a AstLogicalOperatorCall),
+    /// Replaces the value on top with whether it holds.
+    Condition,
+    Tuple { mark: usize },
+    Array { mark: usize },
+    FieldAccess(&'a AstFieldAccess),
+    FieldAssignment(&'a AstFieldAssignment),
+    Index,
+    /// Tries the arms of a match from `next` on, against the value on top.
+    MatchArm { node: &'a AstMatch, next: usize },
+    MatchPattern { node: &'a AstMatch, arm: usize },
+    If { node: &'a AstNodeType, call: &'a AstFunctionCall, mark: usize },
+    /// The condition of a while was evaluated.
+    While { node: &'a AstNodeType, call: &'a AstFunctionCall, body: &'a AstBlock },
+    /// The body of a while is running. Catches `break` and `continue`.
+    WhileBody { node: &'a AstNodeType, call: &'a AstFunctionCall, mark: usize },
+    /// The arguments of a for loop were evaluated.
+    ForStart { node: &'a AstNodeType, variable: &'a String, body: &'a AstBlock, mark: usize },
+    /// The iterator of a for loop, held at `mark`, was advanced.
+    ForStep { node: &'a AstNodeType, variable: &'a String, body: &'a AstBlock, mark: usize },
+    /// The body of a for loop is running in a frame of its own. Catches
+    /// `break` and `continue`.
+    ForBody { node: &'a AstNodeType, variable: &'a String, body: &'a AstBlock, mark: usize },
+    /// Checks what a generator returned to `next`.
+    CheckStep,
+    /// The update function of `run_loop`, held at `mark`, drew a frame.
+    RunLoop { node: &'a AstNodeType, mark: usize, last_frame: Instant }
+}
+
+/// The field values of a struct instance, in declaration order.
+type Fields = Rc<RefCell<Vec<InterpValue>>>;
+
 #[derive(Clone, Debug)]
 pub enum InterpValue {
     InterpVoid,
     InterpNumber(f64),
+    /// A number written without a decimal point, or computed from such
+    /// numbers. Mixed with a Number it becomes a Number.
+    InterpInt(i64),
+    #[cfg(feature = "bignum")]
+    InterpBigNumber(BigDecimal),
     InterpBoolean(bool),
     InterpString(String),
     InterpStruct(usize),
-    InterpFunction{id: usize, closure_id: usize }
+    /// `closure_id` is the scope the function was declared in, which every
+    /// call of it sees and keeps alive.
+    InterpFunction{id: usize, closure_id: usize },
+    /// A function with its first arguments filled in by `bind`.
+    InterpBoundFunction{id: usize, closure_id: usize, arguments: Rc<Vec<InterpValue>> },
+    /// Several values returned at once. Anywhere but a destructuring
+    /// assignment only the first of them is used.
+    InterpTuple(Rc<Vec<InterpValue>>),
+    /// A string that grows in place, from `builder()`. Copies share the
+    /// same buffer.
+    InterpBuilder(Rc<RefCell<String>>),
+    /// An immutable list from `ilist`. Updates return a new list that shares
+    /// most of its structure with the old one.
+    InterpList(PersistentVec<InterpValue>),
+    /// An immutable map from `imap`.
+    InterpMap(PersistentMap<MapKey, InterpValue>),
+    /// From `iter` or `range`, advanced by `next`.
+    InterpIterator(Rc<RefCell<IterSource>>),
+    /// `[a, b, c]`. Copies share the same elements.
+    InterpArray(Rc<RefCell<Vec<InterpValue>>>),
+    /// An instance of the struct `struct_id`, made by calling the struct with
+    /// a value for every field. Copies share the same fields.
+    InterpInstance{struct_id: usize, name: Rc<str>, fields: Fields}
 }
 
 impl InterpValue {
     fn evals_to_true(&self) ->bool {
-        return match self {
+        match self {
             &InterpValue::InterpNumber(num) => {
                 num != 0.0
             },
+            &InterpValue::InterpInt(num) => {
+                num != 0
+            },
+            &InterpValue::InterpBoolean(val) => {
+                val
+            },
+            #[cfg(feature = "bignum")]
+            InterpValue::InterpBigNumber(num) => {
+                !num.is_zero()
+            },
             _ => {false}
         }
     }
+
+    /// The value of a number as a float, for the builtins that only work
+    /// with floats. Exact numbers are rounded to the nearest one.
+    pub fn to_f64(&self) -> Option<f64> {
+        match self {
+            &InterpValue::InterpNumber(num) => { Some(num) }
+            &InterpValue::InterpInt(num) => { Some(num as f64) }
+            #[cfg(feature = "bignum")]
+            InterpValue::InterpBigNumber(num) => { num.to_f64() }
+            _ => { None }
+        }
+    }
+
+    /// Whether the value is an Int, or an exact number without a fraction.
+    pub fn is_integer(&self) -> bool {
+        match self {
+            &InterpValue::InterpInt(_) => { true }
+            #[cfg(feature = "bignum")]
+            InterpValue::InterpBigNumber(num) => { num.is_integer() }
+            _ => { false }
+        }
+    }
+
+    /// The name of the value's type, as written in type annotations.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            &InterpValue::InterpVoid => { "Void" }
+            &InterpValue::InterpNumber(_) => { "Number" }
+            &InterpValue::InterpInt(_) => { "Int" }
+            #[cfg(feature = "bignum")]
+            &InterpValue::InterpBigNumber(_) => { "Number" }
+            &InterpValue::InterpBoolean(_) => { "Boolean" }
+            &InterpValue::InterpString(_) => { "String" }
+            &InterpValue::InterpStruct(_) => { "Struct" }
+            &InterpValue::InterpFunction{..} | &InterpValue::InterpBoundFunction{..} => { "Function" }
+            &InterpValue::InterpTuple(_) => { "Tuple" }
+            &InterpValue::InterpBuilder(_) => { "Builder" }
+            &InterpValue::InterpList(_) => { "IList" }
+            &InterpValue::InterpMap(_) => { "IMap" }
+            &InterpValue::InterpIterator(_) => { "Iterator" }
+            &InterpValue::InterpArray(_) => { "Array" }
+            &InterpValue::InterpInstance{..} => { "Instance" }
+        }
+    }
+}
+
+/// Every name `type_name` gives, which struct fields may be declared with
+/// besides the names of structs.
+pub const TYPE_NAMES: [&str; 14] = ["Void", "Number", "Int", "Boolean", "String", "Struct", "Function", "Tuple", "Builder",
+                                    "IList", "IMap", "Iterator", "Array", "Instance"];
+
+/// Observes evaluation without influencing it, for tools like tracing and coverage.
+pub trait InterpHook {
+    fn before_statement(&mut self, _statement: &AstStatement) {}
+
+    fn after_statement(&mut self, _statement: &AstStatement, _result: &InterpValue) {}
+
+    fn enter_function(&mut self, _name: &str) {}
+
+    fn exit_function(&mut self, _name: &str) {}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PolicyDecision {
+    Allow,
+    Deny,
+    /// Skip the builtin's effect and continue as if it returned null.
+    DryRun
+}
+
+/// Decides whether a side-effecting builtin may run, given its name and
+/// the arguments it was called with.
+pub type BuiltinPolicy = Box<dyn FnMut(&str, &[InterpValue]) -> PolicyDecision>;
+
+/// A builtin a host registers with `register_fn`, called with the evaluated
+/// arguments.
+pub type NativeFunction = Box<dyn FnMut(&[InterpValue]) -> Result<InterpValue, InterpError>>;
+
+type Intrinsic<'a> = fn(&mut Interp<'a>, &'a AstNodeType, &'a AstFunctionCall, Vec<InterpValue>) -> Evaluation;
+
+/// How a call to a builtin is made.
+enum Builtin<'a> {
+    /// Schedules the evaluation of its arguments itself, like `while`, which
+    /// evaluates its condition again before every iteration.
+    Special(fn(&mut Interp<'a>, &'a AstNodeType, &'a AstFunctionCall) -> Result<(), Unwind>),
+    /// Gets the evaluated arguments, and the call for its body.
+    Intrinsic(Intrinsic<'a>),
+    /// Gets the evaluated arguments and calls back into the script, like
+    /// `next` does for generators. It schedules the calls rather than making
+    /// them, and they leave its value.
+    Calling(fn(&mut Interp<'a>, &'a AstNodeType, Vec<InterpValue>) -> Result<(), Unwind>),
+    Native(NativeFunction)
+}
+
+/// The builtins every interpreter starts with, one for each of `BUILTINS`.
+fn default_builtins<'a>() -> HashMap<String, Builtin<'a>> {
+    let nondeterministic: Intrinsic<'a> = |interp, _, call, args| {
+        Ok(InterpValue::InterpNumber(interp.nondeterministic_builtin(&call.name, &args)?))
+    };
+    let graphics: Intrinsic<'a> = |interp, _, call, args| Ok(interp.sdl.call(&call.name, args)?);
+    let builtins: Vec<(&str, Builtin<'a>)> = vec![
+        ("while", Builtin::Special(|interp, node, call| interp.evaluate_while(node, call))),
+        ("for", Builtin::Special(|interp, node, call| interp.evaluate_for(node, call))),
+        ("if", Builtin::Special(|interp, node, call| interp.evaluate_if(node, call))),
+        ("print", Builtin::Intrinsic(|interp, _, _, args| Ok(interp.print(args, Style::Print)?))),
+        ("println", Builtin::Intrinsic(|interp, _, _, args| Ok(interp.print(args, Style::Println)?))),
+        ("debug", Builtin::Intrinsic(|interp, _, _, args| Ok(interp.print(args, Style::Debug)?))),
+        ("format", Builtin::Intrinsic(|_, _, _, args| Ok(format::format_builtin(args)?))),
+        ("is_null", Builtin::Intrinsic(|_, _, _, args| Ok(is_null(args)?))),
+        ("random", Builtin::Intrinsic(nondeterministic)),
+        ("rand_int", Builtin::Intrinsic(nondeterministic)),
+        ("rand_normal", Builtin::Intrinsic(nondeterministic)),
+        ("shuffle", Builtin::Intrinsic(|interp, _, _, args| Ok(interp.shuffle(args)?))),
+        ("choice", Builtin::Intrinsic(|interp, _, _, args| Ok(interp.choice(args)?))),
+        ("stats", Builtin::Intrinsic(|interp, _, _, args| {
+            no_arguments("stats", &args)?;
+            Ok(InterpValue::InterpString(interp.stats().to_string()))
+        })),
+        ("bind", Builtin::Intrinsic(|interp, _, _, args| Ok(interp.bind(args)?))),
+        ("builder", Builtin::Intrinsic(|_, _, _, args| {
+            no_arguments("builder", &args)?;
+            Ok(InterpValue::InterpBuilder(Rc::new(RefCell::new(String::new()))))
+        })),
+        ("push", Builtin::Intrinsic(|interp, _, _, args| Ok(interp.push(args)?))),
+        ("build", Builtin::Intrinsic(|_, _, _, args| Ok(build(args)?))),
+        ("ilist", Builtin::Intrinsic(|interp, _, _, args| Ok(interp.checked_list(collections::ilist(args))?))),
+        ("imap", Builtin::Intrinsic(|_, _, _, args| Ok(collections::imap(args)?))),
+        ("with", Builtin::Intrinsic(|interp, _, _, args| Ok(interp.checked_list(collections::with(args)?)?))),
+        ("without", Builtin::Intrinsic(|_, _, _, args| Ok(collections::without(args)?))),
+        ("get", Builtin::Intrinsic(|_, _, _, args| Ok(collections::get(args)?))),
+        ("len", Builtin::Intrinsic(|_, _, _, args| Ok(collections::len(args)?))),
+        ("iter", Builtin::Intrinsic(|_, _, _, args| Ok(iterator::iter(args)?))),
+        ("next", Builtin::Calling(|interp, node, args| interp.next(node, args))),
+        ("range", Builtin::Intrinsic(|_, _, _, args| Ok(iterator::range(args)?))),
+        ("pop", Builtin::Intrinsic(|_, _, _, args| Ok(collections::pop(args)?))),
+        ("insert", Builtin::Intrinsic(|interp, _, _, args| {
+            let array = args.first().cloned().unwrap_or(InterpValue::InterpVoid);
+            let value = collections::insert(args)?;
+            interp.check_grown(&array, 1)?;
+            Ok(value)
+        })),
+        ("remove", Builtin::Intrinsic(|_, _, _, args| Ok(collections::remove(args)?))),
+        ("slice", Builtin::Intrinsic(|interp, _, _, args| Ok(interp.checked_list(collections::slice(args)?)?))),
+        ("concat", Builtin::Intrinsic(|interp, _, _, args| Ok(interp.checked_list(collections::concat(args)?)?))),
+        ("join", Builtin::Intrinsic(|interp, _, _, args| {
+            let joined = collections::join(args)?;
+            interp.allocate_result(&joined)?;
+            Ok(joined)
+        })),
+        ("now", Builtin::Intrinsic(nondeterministic)),
+        ("sleep", Builtin::Intrinsic(|_, _, _, args| Ok(sleep(args)?))),
+        ("timer", Builtin::Intrinsic(nondeterministic)),
+        ("elapsed", Builtin::Intrinsic(nondeterministic)),
+        ("random_range", Builtin::Intrinsic(nondeterministic)),
+        ("seed", Builtin::Intrinsic(|interp, _, _, args| Ok(interp.seed(args)?))),
+        ("window_open", Builtin::Intrinsic(graphics)),
+        ("window_close", Builtin::Intrinsic(graphics)),
+        ("set_color", Builtin::Intrinsic(graphics)),
+        ("clear", Builtin::Intrinsic(graphics)),
+        ("present", Builtin::Intrinsic(graphics)),
+        ("draw_rect", Builtin::Intrinsic(graphics)),
+        ("draw_line", Builtin::Intrinsic(graphics)),
+        ("draw_circle", Builtin::Intrinsic(graphics)),
+        ("poll_events", Builtin::Intrinsic(graphics)),
+        ("key_down", Builtin::Intrinsic(graphics)),
+        ("mouse_pos", Builtin::Intrinsic(graphics)),
+        ("run_loop", Builtin::Calling(|interp, node, args| interp.run_loop(node, args))),
+        ("load_image", Builtin::Intrinsic(graphics)),
+        ("draw_image", Builtin::Intrinsic(graphics))
+    ];
+    builtins.into_iter().map(|(name, builtin)| (String::from(name), builtin)).collect()
+}
+
+/// Where a resumable execution stopped.
+#[derive(Debug)]
+pub enum RunState {
+    /// The yield interval ran out, call `Interp::resume` to continue.
+    Interrupted,
+    Finished(InterpValue)
+}
+
+/// Counters describing the work an interpreter has done, for spotting
+/// performance regressions and leaks.
+#[derive(Debug, Clone, Default)]
+pub struct InterpStats {
+    /// Closures created since the interpreter started.
+    pub allocations: usize,
+    /// Closures that have not been freed.
+    pub live_closures: usize,
+    pub peak_frame_depth: usize,
+    pub evaluated_nodes: usize,
+    /// How many values of each type evaluation produced, by `type_name`.
+    pub value_counts: BTreeMap<&'static str, usize>
+}
+
+impl Display for InterpStats {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "allocations: {}, live closures: {}, peak frame depth: {}, evaluated nodes: {}",
+               self.allocations, self.live_closures, self.peak_frame_depth, self.evaluated_nodes)?;
+        for (type_name, count) in &self.value_counts {
+            write!(f, ", {}: {}", type_name, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// One step of evaluation, as yielded by `Interp::steps`.
+#[derive(Debug)]
+pub enum InterpEvent<'a> {
+    EnteredStatement(&'a AstStatement),
+    /// A call or block pushed a frame, leaving the stack `depth` deep.
+    PushedFrame { depth: usize },
+    PoppedFrame { depth: usize },
+    ProducedValue(InterpValue)
+}
+
+/// Drives a script one event at a time, for tools such as debuggers.
+/// Evaluation pauses as soon as there is an event to take, so the script
+/// does not run ahead of the tool consuming them.
+pub struct Steps<'i, 'a: 'i> {
+    interp: &'i mut Interp<'a>,
+    execution: Execution<'a>,
+    error: Option<InterpError>,
+    finished: bool
+}
+
+impl<'i, 'a> Iterator for Steps<'i, 'a> {
+    type Item = Result<InterpEvent<'a>, InterpError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.interp.events.as_mut().and_then(|events| events.pop_front()) {
+                return Some(Ok(event));
+            }
+            if self.finished {
+                return self.error.take().map(Err);
+            }
+
+            match self.interp.resume_for(&mut self.execution, None) {
+                Ok(RunState::Interrupted) => {}
+                Ok(RunState::Finished(_)) => { self.finished = true; }
+                Err(error) => {
+                    // The events leading up to the error come first
+                    self.error = Some(error);
+                    self.finished = true;
+                }
+            }
+        }
+    }
+}
+
+impl<'i, 'a> Drop for Steps<'i, 'a> {
+    fn drop(&mut self) {
+        self.interp.events = None;
+    }
+}
+
+/// How many nodes are evaluated between looking at the clock for the
+/// `timeout`, which is slow compared to evaluating a node.
+const DEADLINE_INTERVAL: usize = 1024;
+
+/// How many frames calls and loop bodies may nest when no depth is configured.
+pub const DEFAULT_STACK_SIZE: usize = 10;
+
+/// Caps on how much a script may grow, for sandboxed deployments. `None`
+/// leaves a resource unlimited.
+#[derive(Debug, Clone, Default)]
+pub struct InterpOptions {
+    /// In chars.
+    pub max_string_length: Option<usize>,
+    pub max_array_length: Option<usize>,
+    pub max_structs: Option<usize>,
+    /// In frames. The stack is never unlimited, `None` means
+    /// `DEFAULT_STACK_SIZE`.
+    pub stack_size: Option<usize>,
+    /// How many nodes each call to `run` or `resume` may evaluate.
+    pub max_steps: Option<usize>,
+    /// How long each call to `run` or `resume` may take.
+    pub timeout: Option<Duration>,
+    /// In bytes, roughly what the values and scopes of the script take up.
+    pub max_memory: Option<usize>
+}
+
+/// A script started with `Interp::start`, which runs a slice at a time.
+/// What is left of it waits on the interpreter's stacks between slices.
+pub struct Execution<'a> {
+    ast: &'a Ast,
+    started: bool,
+    finished: bool
+}
+
+/// Lets the host keep a handle on a hook's state after handing it to the interpreter.
+impl<T: InterpHook> InterpHook for Rc<RefCell<T>> {
+    fn before_statement(&mut self, statement: &AstStatement) {
+        self.borrow_mut().before_statement(statement);
+    }
+
+    fn after_statement(&mut self, statement: &AstStatement, result: &InterpValue) {
+        self.borrow_mut().after_statement(statement, result);
+    }
+
+    fn enter_function(&mut self, name: &str) {
+        self.borrow_mut().enter_function(name);
+    }
+
+    fn exit_function(&mut self, name: &str) {
+        self.borrow_mut().exit_function(name);
+    }
 }
 
+/// Runtime metadata of a declared struct, one type name per field.
 struct InterpStruct {
+    pub name: Rc<str>,
     pub fields: Vec<String>,
     pub types: Vec<String>
 }
 
+impl InterpStruct {
+    fn from_declaration(dec: &AstStructDeclaration) -> Result<InterpStruct, InterpError> {
+        for (index, field) in dec.fields.iter().enumerate() {
+            if dec.fields[..index].contains(field) {
+                let msg = format!("The field {} is declared more than once", field);
+                return Err(InterpError::new(&error_codes::INVALID_STRUCT, msg));
+            }
+        }
+        if dec.fields.len() != dec.types.len() {
+            let msg = "Every struct field needs a type".to_string();
+            return Err(InterpError::new(&error_codes::INVALID_STRUCT, msg));
+        }
+
+        Ok(InterpStruct {
+            name: Rc::from(&dec.name[..]),
+            fields: dec.fields.clone(),
+            types: dec.types.clone()
+        })
+    }
+
+    fn field_index(&self, field: &str) -> Result<usize, InterpError> {
+        match self.fields.iter().position(|name| name == field) {
+            Some(index) => { Ok(index) }
+            None => {
+                let msg = format!("Unknown field {}", field);
+                let msg = suggest::with_suggestion(msg, field, self.fields.iter().map(|name| &name[..]));
+                Err(InterpError::new(&error_codes::UNKNOWN_FIELD, msg))
+            }
+        }
+    }
+}
+
 struct StackFrame<'a> {
     index: usize,
-    creator: &'a AstNodeType,
+    creator: Option<&'a AstNodeType>,
+    /// Whether the frame is a function call, rather than a block or loop body.
+    is_call: bool,
     closure_id: usize,
     parent_frame: Option<Box<StackFrame<'a>>>
 }
 
 impl <'a>StackFrame<'a> {
-    fn new(creator: &'a AstNodeType, closure: usize) -> StackFrame<'a> {
-        return StackFrame {
+    fn new(creator: Option<&'a AstNodeType>, closure: usize, is_call: bool) -> StackFrame<'a> {
+        StackFrame {
             index: 0,
-            creator: creator,
+            creator,
+            is_call,
             closure_id: closure,
             parent_frame: None
-        };
+        }
     }
 
     fn set_parent_frame(&mut self, parent_frame: StackFrame<'a>) {
         let parent_frame_index = parent_frame.index;
         let new_frame = Some(Box::new(parent_frame));
-        mem::replace(&mut self.parent_frame, new_frame);
+        self.parent_frame = new_frame;
 
         self.index = parent_frame_index+1;
     }
 
     fn remove_parent_frame(&mut self) -> Option<StackFrame<'a>> {
-        let parent_frame = mem::replace(&mut self.parent_frame, None);
+        let parent_frame = self.parent_frame.take();
 
-        if let Some(frame) = parent_frame {
-            let frame = Some(*frame);
-            return frame;
-        } else {
-            return None;
-        }
+        parent_frame.map(|frame| *frame)
     }
 }
 
-struct Closure<'a> {
-    creator: &'a AstNodeType,
+struct Closure {
     variables: HashMap<String, InterpValue>,
     parent_id: Option<usize>
 }
 
-impl <'a>Closure<'a> {
-    fn new(creator: &'a AstNodeType, parent_closure: Option<usize>) -> Closure<'a> {
-        return Closure {
-            creator: creator,
+impl Closure {
+    fn new(parent_closure: Option<usize>) -> Closure {
+        Closure {
             variables: HashMap::new(),
             parent_id: parent_closure
-        };
+        }
     }
 
     fn set_variable(&mut self, name: String, value: InterpValue) {
-
+        self.variables.insert(name, value);
     }
 }
 
 
 
-struct Interp<'a> {
-    stack_size: usize,
-    structs: Vec<&'a AstStructDeclaration>,
+pub struct Interp<'a> {
+    structs: Vec<InterpStruct>,
     functions: Vec<&'a AstFunctionDeclaration>,
-    closures: Vec<Option<Closure<'a>>>,
-    current_frame: StackFrame<'a>
+    function_ids: HashMap<*const AstFunctionDeclaration, usize>,
+    closures: Vec<Option<Closure>>,
+    /// Ids of freed closures, reused before `closures` grows.
+    free_closures: Vec<usize>,
+    /// How many live closures trigger the next collection.
+    next_collection: usize,
+    /// What is left to evaluate, innermost last.
+    tasks: Vec<Task<'a>>,
+    /// The values evaluated nodes left for the tasks after them, like the
+    /// arguments of a call evaluated so far. Collections keep them alive.
+    values: Vec<InterpValue>,
+    current_frame: StackFrame<'a>,
+    output: Box<dyn Write>,
+    hooks: Vec<Box<dyn InterpHook>>,
+    builtins: HashMap<String, Builtin<'a>>,
+    current_statement: Option<&'a AstStatement>,
+    rng: Rng,
+    policy: Option<BuiltinPolicy>,
+    bignum: bool,
+    strict_overflow: bool,
+    /// Dividing by zero and results that are not a number are errors.
+    strict_division: bool,
+    /// Conditions must be booleans rather than any truthy value.
+    strict_conditions: bool,
+    yield_interval: Option<usize>,
+    /// The `evaluated_nodes` count at which `resume` hands control back.
+    yield_at: Option<usize>,
+    effects: EffectMode,
+    stats: InterpStats,
+    options: InterpOptions,
+    /// The `evaluated_nodes` count at which `max_steps` runs out.
+    step_limit: Option<usize>,
+    deadline: Option<Instant>,
+    /// What `timer` and `elapsed` measure from.
+    started: Instant,
+    /// Whether `now`, `timer` and `elapsed` read a clock that never moves.
+    clock_frozen: bool,
+    /// Bytes allocated since memory use was last measured, plus what was
+    /// measured then.
+    allocated: usize,
+    events: Option<VecDeque<InterpEvent<'a>>>,
+    /// The windows the script has open.
+    sdl: Sdl,
+    /// The script file `run_loop` reloads functions from when it changes.
+    watcher: Option<ScriptWatcher>,
+    /// Every version of the script reloaded so far. Declared last so that
+    /// everything referring to them is dropped first.
+    reloaded: ReloadedScripts
 }
 
 impl <'a>Interp<'a> {
-    fn get_closure_by_id(&self, id: usize) -> Result<&Closure<'a>, InterpError> {
-        return self.closures[id].as_ref().ok_or({
+    fn get_closure_by_id(&self, id: usize) -> Result<&Closure, InterpError> {
+        self.closures.get(id).and_then(|closure| closure.as_ref()).ok_or({
             let msg = format!("The closure with id {} no longer exists", id);
-            InterpError::new(msg)
-        });
+            InterpError::new(&error_codes::INTERNAL, msg)
+        })
     }
 
-    fn get_mut_closure_by_id(&mut self, id: usize) -> Result<&mut Closure<'a>, InterpError> {
-        return self.closures[id].as_mut().ok_or({
+    fn get_mut_closure_by_id(&mut self, id: usize) -> Result<&mut Closure, InterpError> {
+        self.closures.get_mut(id).and_then(|closure| closure.as_mut()).ok_or({
             let msg = format!("The closure with id {} no longer exists", id);
-            InterpError::new(msg)
-        });
+            InterpError::new(&error_codes::INTERNAL, msg)
+        })
     }
 
-    fn get_current_closure(&self) -> Result<&Closure<'a>, InterpError>{
+    fn get_current_closure(&self) -> Result<&Closure, InterpError>{
         let id = self.current_frame.closure_id;
-        return self.get_closure_by_id(id);
+        self.get_closure_by_id(id)
     }
 
-    fn get_current_mut_closure(&mut self) -> Result<&mut Closure<'a>, InterpError> {
+    fn get_current_mut_closure(&mut self) -> Result<&mut Closure, InterpError> {
         let id = self.current_frame.closure_id;
-        return self.get_mut_closure_by_id(id);
+        self.get_mut_closure_by_id(id)
     }
 
-    fn get_variable_of_closure(&self, name: &String, closure: &'a Closure) -> Result<&InterpValue, InterpError> {
-        if let Some(interpValue) = closure.variables.get(name) {
-            return Ok(interpValue);
+    fn get_variable_of_closure<'c>(&'c self, name: &String, closure: &'c Closure) -> Result<&'c InterpValue, InterpError> {
+        if let Some(interp_value) = closure.variables.get(name) {
+            Ok(interp_value)
         } else if let Some(parent_id) = closure.parent_id {
             let parent_closure = self.get_closure_by_id(parent_id)?;
-            return self.get_variable_of_closure(name, parent_closure);
+            self.get_variable_of_closure(name, parent_closure)
         } else {
-            let msg = format!("Unable to find variable {}", name);
-            return Err(InterpError::new(msg));
+            Err(self.unknown_name_error("variable", name))
+        }
+    }
+
+    /// Reports a failed lookup, suggesting a name in scope that is close to
+    /// the one asked for. Functions may also be misspelled builtins.
+    fn unknown_name_error(&self, kind: &str, name: &str) -> InterpError {
+        let mut candidates = self.visible_names();
+        if kind == "function" {
+            candidates.extend(self.builtins.keys().cloned());
         }
+
+        let msg = format!("Unable to find {} {}", kind, name);
+        let msg = suggest::with_suggestion(msg, name, candidates.iter().map(|candidate| &candidate[..]));
+        InterpError::new(&error_codes::UNKNOWN_NAME, msg)
     }
 
     fn get_variable(&self, name: &String) -> Result<&InterpValue, InterpError> {
         let closure = self.get_current_closure()?;
-        return self.get_variable_of_closure(name, closure);
+        self.get_variable_of_closure(name, closure)
     }
 
+    /// Binds `name` in the innermost closure, shadowing any outer binding.
     fn set_variable(&mut self, name: String, value: InterpValue) -> Result<InterpValue, InterpError> {
         let closure = self.get_current_mut_closure()?;
-        closure.variables.insert(name, value);
+        closure.set_variable(name, value);
+
+        Ok(InterpValue::InterpVoid)
+    }
+
+    /// Writes to the closest closure that already binds `name`, so that a
+    /// nested block or function updates the original variable. A name that
+    /// is not bound anywhere yet is bound in the innermost closure.
+    fn assign_variable(&mut self, name: String, value: InterpValue) -> Result<InterpValue, InterpError> {
+        let mut closure_id = Some(self.current_frame.closure_id);
+        while let Some(id) = closure_id {
+            let closure = self.get_closure_by_id(id)?;
+            if closure.variables.contains_key(&name) {
+                break;
+            }
+            closure_id = closure.parent_id;
+        }
 
-        return Ok(InterpValue::InterpVoid);
+        let id = closure_id.unwrap_or(self.current_frame.closure_id);
+        let closure = self.get_mut_closure_by_id(id)?;
+        closure.set_variable(name, value);
+
+        Ok(InterpValue::InterpVoid)
+    }
+
+    /// Points an error that does not know where it happened yet at `node`,
+    /// and at the function calls that were running when it did.
+    fn locate(&self, mut error: InterpError, node: &AstNodeType) -> InterpError {
+        if error.span.is_some() {
+            return error;
+        }
+        error.span = Some(node.span().clone());
+
+        let mut frame = Some(&self.current_frame);
+        while let Some(current) = frame {
+            if let (true, Some(creator)) = (current.is_call, current.creator) {
+                let name = match creator {
+                    AstNodeType::FunctionCall(call) => { call.name.clone() }
+                    _ => { String::from("<function>") }
+                };
+                error.backtrace.push(TraceFrame { name, call_site: creator.span().clone() });
+            }
+            frame = current.parent_frame.as_deref();
+        }
+        error
     }
 
-    fn push_frame(&mut self, creator: &'a AstNodeType, closure_id: usize) -> Result<InterpValue, InterpError> {
-        if self.current_frame.index > self.stack_size {
-            let msg = format!("Stack overflow!");
-            return Err(InterpError::new(msg));
+    fn push_frame(&mut self, creator: &'a AstNodeType, closure_id: usize, is_call: bool) -> Result<InterpValue, InterpError> {
+        let stack_size = self.options.stack_size.unwrap_or(DEFAULT_STACK_SIZE);
+        if self.current_frame.index > stack_size {
+            let msg = format!("Stack overflow! Calls nested deeper than {} frames", stack_size);
+            return Err(InterpError::new(&error_codes::STACK_OVERFLOW, msg));
         }
 
-        let mut new_frame = StackFrame::new(creator, closure_id);
+        let new_frame = StackFrame::new(Some(creator), closure_id, is_call);
         let frame = mem::replace(&mut self.current_frame, new_frame);
         self.current_frame.set_parent_frame(frame);
+        self.stats.peak_frame_depth = self.stats.peak_frame_depth.max(self.current_frame.index);
+        let depth = self.current_frame.index;
+        self.emit(InterpEvent::PushedFrame { depth });
+
+        Ok(InterpValue::InterpVoid)
+    }
 
-        return Ok(InterpValue::InterpVoid);
+    fn emit(&mut self, event: InterpEvent<'a>) {
+        if let Some(ref mut events) = self.events {
+            events.push_back(event);
+        }
     }
 
     fn pop_frame(&mut self) -> Result<StackFrame<'a>, InterpError> {
@@ -199,217 +903,1905 @@ impl <'a>Interp<'a> {
 
         if let Some(frame) = parent_frame {
             let old_frame = mem::replace(&mut self.current_frame, frame);
-            return Ok(old_frame);
+            let depth = self.current_frame.index;
+            self.emit(InterpEvent::PoppedFrame { depth });
+            Ok(old_frame)
         } else {
-            let msg = format!("Unable to pop from stack");
-            return Err(InterpError::new(msg));
+            let msg = "Unable to pop from stack".to_string();
+            Err(InterpError::new(&error_codes::INTERNAL, msg))
         }
     }
 
-    fn add_closure(&mut self, creator: &'a AstNodeType, parent_closure_id: usize) -> usize {
-        let closure = Closure::new(creator, Some(parent_closure_id));
-        let id = self.closures.len();
-        self.closures.push(Some(closure));
-        return id;
-    }
-
-    fn evaluate_block(&mut self, creator: &'a AstNodeType, block: &'a AstBlock) -> Result<InterpValue, InterpError> {
-        let name = "A block";
-        let parent_closure_id = self.current_frame.closure_id;
-        let closure_id = self.add_closure(creator, parent_closure_id);
-
-        self.push_frame(creator, closure_id)?;
-        let res = {
-            let mut last_result: InterpValue = InterpValue::InterpVoid;
-
-            for statement in &block.statements {
-                last_result = self.evaluate_next(&statement)?;
+    /// `bind(f, a, b)` is `f` with its first arguments fixed to `a` and `b`.
+    /// Binding a bound function adds to the arguments it already has.
+    fn bind(&self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        let mut args = args.into_iter();
+        let (id, closure_id, mut arguments) = match args.next() {
+            Some(InterpValue::InterpFunction{id, closure_id}) => { (id, closure_id, Vec::new()) }
+            Some(InterpValue::InterpBoundFunction{id, closure_id, arguments}) => { (id, closure_id, (*arguments).clone()) }
+            Some(other) => {
+                let msg = format!("bind expects a function but was given {}", with_article(other.type_name()));
+                return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+            }
+            None => {
+                let msg = "bind expects a function to bind arguments to".to_string();
+                return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg));
             }
-
-            last_result
         };
-        self.pop_frame()?;
+        arguments.extend(args);
 
-        return Ok(res);
+        if let Some(func) = self.functions.get(id) {
+            if arguments.len() > func.arguments.len() {
+                let msg = format!("Can not bind {} to a function taking {}", counted(arguments.len(), "argument"), func.arguments.len());
+                return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg));
+            }
+        }
+        Ok(InterpValue::InterpBoundFunction{id, closure_id, arguments: Rc::new(arguments)})
     }
 
-    fn evaluate_next(&mut self, node: &'a AstNodeType) -> Result<InterpValue, InterpError> {
-        match node {
-            &AstNodeType::Block(ref boxed) => {
-                let block = &**boxed;
-                return self.evaluate_block(node, block);
+    /// Schedules the arguments of a call to be evaluated in order, leaving a
+    /// value each, or the elements of the Array `...` is applied to.
+    fn schedule_arguments(&mut self, arguments: &'a [AstNodeType]) {
+        for arg in arguments.iter().rev() {
+            if let AstNodeType::Spread(spread) = arg {
+                self.tasks.push(Task::Spread);
+                self.tasks.push(Task::Eval { node: &spread.value, whole: false });
+            } else {
+                self.tasks.push(Task::Eval { node: arg, whole: false });
             }
-            &AstNodeType::FunctionCall(ref boxed) => {
-                let function = &**boxed;
-
-                let mut args: Vec<InterpValue> = Vec::with_capacity(function.arguments.len());
-                for arg in &function.arguments {
-                    let val = self.evaluate_next(&arg)?;
-                    args.push(val);
-                }
+        }
+    }
 
-                let name = &function.name;
-                if name == "while" {
-                    
-                } else if name == "if" {
-                    if args.len() != 1 {
-                        let msg = format!("if statements can only have one parameter");
-                        return Err(InterpError::new(msg));
-                    }
+    /// Schedules `nodes` to be evaluated in order, leaving a value each.
+    fn schedule_each(&mut self, nodes: &'a [AstNodeType]) {
+        for node in nodes.iter().rev() {
+            self.tasks.push(Task::Eval { node, whole: false });
+        }
+    }
 
-                    if let Some(ref body) = function.body {
-                        let is_true = args[0].evals_to_true();
+    /// Runs a block in a frame of its own, leaving the value of its last
+    /// statement.
+    fn schedule_block(&mut self, creator: &'a AstNodeType, block: &'a AstBlock) -> Result<(), Unwind> {
+        let parent_closure_id = self.current_frame.closure_id;
+        let closure_id = self.add_closure(parent_closure_id);
 
-                        if is_true {
-                            return self.evaluate_block(node, body);
-                        } else {
-                            return Ok(InterpValue::InterpVoid);
-                        }
-                    } else {
-                        let msg = format!("If statement must have a body");
-                        return Err(InterpError::new(msg));
-                    }
-                } else if name == "print" {
-                    leg_sdl::print(args);
-                    return Ok(InterpValue::InterpVoid);
-                } else {
-                    let mut maybe_index = {
-                        let interp_value = self.get_variable(name)?;
-                        match *interp_value {
-                            InterpValue::InterpFunction{id, closure_id} => {
-                                Some((id, closure_id))
-                            }
-                            _ => {
-                                None
-                            }
-                        }
-                    };
-
-                    if let Some((function_id, closure_id)) = maybe_index {
-                        let func :&AstFunctionDeclaration = self.functions.get(function_id).unwrap();
-
-                        let mut argument_names :Vec<&str> = Vec::new();
-                        for arg in &func.arguments {
-                            match arg {
-                                &AstNodeType::Variable(ref boxed) => {
-                                    let variable = &**boxed;
-                                    let name = &variable.name[..];
-                                    argument_names.push(name);
-                                }
-                                _ => {
-                                    let msg = format!("Invalid argument expression");
-                                    return Err(InterpError::new(msg));
-                                }
-                            }
-                        }
+        self.push_frame(creator, closure_id, false)?;
+        self.tasks.push(Task::PopFrame);
+        self.tasks.push(Task::Statements { block, next: 0 });
+        Ok(())
+    }
 
-                        assert_eq!(argument_names.len(), args.len());
+    /// `if` evaluates its condition, and once it has, runs the body of the
+    /// first if in an `if / else if / else` chain whose condition holds.
+    fn evaluate_if(&mut self, node: &'a AstNodeType, call: &'a AstFunctionCall) -> Result<(), Unwind> {
+        let mark = self.values.len();
+        self.tasks.push(Task::If { node, call, mark });
+        self.schedule_arguments(&call.arguments);
+        Ok(())
+    }
 
-                        self.push_frame(node, closure_id)?;
-                        for name_value in argument_names.iter().zip(&args) {
-                            let name = String::from(*name_value.0);
-                            let value: InterpValue = name_value.1.clone();
-                            self.set_variable(name, value);
-                        }
+    /// Runs the body of `call` if its condition holds, or the alternative
+    /// after it when it does not, the final else when there is no other.
+    fn choose_branch(&mut self, node: &'a AstNodeType, call: &'a AstFunctionCall, args: Vec<InterpValue>) -> Result<(), Unwind> {
+        if args.len() != 1 {
+            let msg = "if statements can only have one parameter".to_string();
+            return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg).into());
+        }
 
-                        let res = self.evaluate_block(node,&func.body);
-                        self.pop_frame();
+        let body = match call.body {
+            Some(ref body) => { body }
+            None => {
+                let msg = "If statement must have a body".to_string();
+                return Err(InterpError::new(&error_codes::MISSING_BODY, msg).into());
+            }
+        };
+        if self.condition(&args[0])? {
+            return self.schedule_block(node, body);
+        }
 
-                        return res;
+        match call.next {
+            Some(ref alternative) if alternative.name == "else" => {
+                match alternative.body {
+                    Some(ref body) => { self.schedule_block(node, body) }
+                    None => {
+                        let msg = "Else must have a body".to_string();
+                        Err(InterpError::new(&error_codes::MISSING_BODY, msg).into())
                     }
                 }
-
-                let msg = format!("Unable to find function {}", name);
-                return Err(InterpError::new(msg));
             }
-            &AstNodeType::StringValue(ref boxed) => {
-                let string = &**boxed;
-                let value = string.value.clone();
-
-                return Ok(InterpValue::InterpString(value));
+            Some(ref alternative) => { self.evaluate_if(node, alternative) }
+            None => {
+                self.values.push(InterpValue::InterpVoid);
+                Ok(())
             }
-            &AstNodeType::NumberValue(ref boxed) => {
-                let number = &**boxed;
+        }
+    }
+
+    /// Runs the body of a `while` for as long as its condition, evaluated again
+    /// before every iteration, holds. `break` and `continue` in the body stop
+    /// here.
+    fn evaluate_while(&mut self, node: &'a AstNodeType, call: &'a AstFunctionCall) -> Result<(), Unwind> {
+        if call.arguments.len() != 1 {
+            let msg = "while loops can only have one parameter".to_string();
+            return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg).into());
+        }
 
-                return Ok(InterpValue::InterpNumber(number.value));
+        let body = match call.body {
+            Some(ref body) => { body }
+            None => {
+                let msg = "While loop must have a body".to_string();
+                return Err(InterpError::new(&error_codes::MISSING_BODY, msg).into());
             }
-            &AstNodeType::Variable(ref boxed) => {
-                let variable = &**boxed;
-                let name = &variable.name;
+        };
+        self.tasks.push(Task::While { node, call, body });
+        self.tasks.push(Task::Eval { node: &call.arguments[0], whole: false });
+        Ok(())
+    }
 
-                let val = self.get_variable(name)?;
-                return Ok(val.clone());
+    /// `for(i, start, end) { .. }` counts i from start up to end, and
+    /// `for(x, values) { .. }` runs the body for every value `iter` yields.
+    /// Every iteration gets its own closure, so functions declared in the body
+    /// keep the value they saw.
+    fn evaluate_for(&mut self, node: &'a AstNodeType, call: &'a AstFunctionCall) -> Result<(), Unwind> {
+        let variable = match call.arguments.first() {
+            Some(AstNodeType::Variable(boxed)) => { &boxed.name }
+            _ => {
+                let msg = "for loops must start with the name of the loop variable".to_string();
+                return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg).into());
             }
-            &AstNodeType::Assignment(ref boxed) => {
-                let assignment = &**boxed;
-                let name = assignment.to.name.clone();
-                let value = self.evaluate_next(&assignment.from)?;
+        };
+        let body = match call.body {
+            Some(ref body) => { body }
+            None => {
+                let msg = "For loop must have a body".to_string();
+                return Err(InterpError::new(&error_codes::MISSING_BODY, msg).into());
+            }
+        };
+
+        let mark = self.values.len();
+        self.tasks.push(Task::ForStart { node, variable, body, mark });
+        self.schedule_each(&call.arguments[1..]);
+        Ok(())
+    }
 
-                self.set_variable(name, value);
-                return Ok(InterpValue::InterpVoid);
+    /// Makes the iterator of a for loop from its arguments, and holds it at
+    /// `mark` while the loop runs.
+    fn start_for(&mut self, node: &'a AstNodeType, variable: &'a String, body: &'a AstBlock, mark: usize) -> Result<(), Unwind> {
+        let args = self.values.split_off(mark);
+        let iterator = match args.len() {
+            1 => { iterator::iter(args)? }
+            2 => { iterator::range(args)? }
+            count => {
+                let msg = format!("for loops expect 2 or 3 arguments but were called with {}", count + 1);
+                return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg).into());
             }
-            &AstNodeType::OperatorCall(ref boxed) => {
-                let operation = &**boxed;
-                let operator = operation.operator;
-                let lhs = self.evaluate_next(&operation.lhs)?;
-                let rhs = self.evaluate_next(&operation.rhs)?;
+        };
+        self.values.push(iterator);
+        self.advance_for(node, variable, body, mark)
+    }
 
-                return operators::apply_operation(lhs, rhs, operator);
+    fn advance_for(&mut self, node: &'a AstNodeType, variable: &'a String, body: &'a AstBlock, mark: usize) -> Result<(), Unwind> {
+        self.tasks.push(Task::ForStep { node, variable, body, mark });
+        match self.values[mark] {
+            InterpValue::InterpIterator(ref source) => {
+                let source = source.clone();
+                self.schedule_next(node, source)
             }
-            &AstNodeType::Alias(ref boxed) => {
-                let alias = &**boxed;
-                let name = alias.to.name.clone();
-                let value = self.evaluate_next(&alias.from)?;
+            _ => {
+                let msg = "The iterator of a for loop is gone".to_string();
+                Err(InterpError::new(&error_codes::INTERNAL, msg).into())
+            }
+        }
+    }
 
-                self.set_variable(name, value);
-                return Ok(InterpValue::InterpVoid);
+    /// Runs the body of a for loop for the value the iterator stepped to,
+    /// or ends the loop once it is done.
+    fn step_for(&mut self, node: &'a AstNodeType, variable: &'a String, body: &'a AstBlock, mark: usize) -> Result<(), Unwind> {
+        let value = match self.pop_value()? {
+            InterpValue::InterpTuple(ref step) if !step[0].evals_to_true() => { step[1].clone() }
+            _ => {
+                self.values.truncate(mark);
+                self.values.push(InterpValue::InterpVoid);
+                return Ok(());
             }
-            &AstNodeType::StructDeclaration(ref boxed) => {
-                let dec = &**boxed;
+        };
 
-                let index = self.structs.len();
-                self.structs.push(&dec);
+        let parent_closure_id = self.current_frame.closure_id;
+        let closure_id = self.add_closure(parent_closure_id);
+        self.push_frame(node, closure_id, false)?;
+        self.tasks.push(Task::ForBody { node, variable, body, mark });
+        self.set_variable(variable.clone(), value)?;
+        self.tasks.push(Task::Statements { block: body, next: 0 });
+        Ok(())
+    }
 
-                return Ok(InterpValue::InterpStruct(index));
+    /// Calls a function value with `args`, after any arguments bound to it.
+    /// The body is scheduled to run in a frame of its own, while a struct is
+    /// instantiated right away.
+    fn call(&mut self, node: &'a AstNodeType, name: &str, callee: InterpValue, mut args: Vec<InterpValue>) -> Result<(), Unwind> {
+        let (function_id, closure_id, bound) = match callee {
+            InterpValue::InterpFunction{id, closure_id} => { (id, closure_id, None) }
+            InterpValue::InterpBoundFunction{id, closure_id, arguments} => { (id, closure_id, Some(arguments)) }
+            InterpValue::InterpStruct(struct_id) => {
+                let instance = self.instantiate(struct_id, args)?;
+                self.values.push(instance);
+                return Ok(());
             }
-            &AstNodeType::FunctionDeclaration(ref boxed) => {
-                let dec = &**boxed;
-
-                let index = self.functions.len();
-                self.functions.push(dec);
+            _ => {
+                let msg = format!("{} is not a function", name);
+                return Err(InterpError::new(&error_codes::NOT_A_FUNCTION, msg).into());
+            }
+        };
 
-                let parent_closure_id = self.current_frame.closure_id;
-                let closure_id = self.add_closure(node, parent_closure_id);
+        let bound_count = bound.as_ref().map_or(0, |bound| bound.len());
+        if let Some(bound) = bound {
+            args.splice(0..0, bound.iter().cloned());
+        }
 
-                return Ok(InterpValue::InterpFunction{id: index, closure_id: closure_id});
+        let func: &'a AstFunctionDeclaration = match self.functions.get(function_id) {
+            Some(func) => { func }
+            None => {
+                let msg = format!("The function {} no longer exists", name);
+                return Err(InterpError::new(&error_codes::INTERNAL, msg).into());
             }
-            &AstNodeType::NullValue(ref boxed) => {
-                return Ok(InterpValue::InterpVoid);
+        };
+
+        let mut argument_names :Vec<&str> = Vec::new();
+        for arg in &func.arguments {
+            match arg {
+                AstNodeType::Variable(boxed) => {
+                    let variable = &**boxed;
+                    let name = &variable.name[..];
+                    argument_names.push(name);
+                }
+                _ => {
+                    let msg = "Invalid argument expression".to_string();
+                    return Err(InterpError::new(&error_codes::INTERNAL, msg).into());
+                }
             }
-            _ => {
-                let msg = format!("Unable to intepret AstNode: {:?}", node);
-                return Err(InterpError::new(msg));
+        }
+
+        if argument_names.len() != args.len() {
+            let msg = format!("{} expects {} but was called with {}",
+                              name, counted(argument_names.len() - bound_count, "argument"), args.len() - bound_count);
+            return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg).into());
+        }
+
+        // Every call gets a scope of its own for the arguments and locals,
+        // inside the scope the function was declared in.
+        let call_closure_id = self.add_closure(closure_id);
+        self.push_frame(node, call_closure_id, true)?;
+        for hook in &mut self.hooks {
+            hook.enter_function(name);
+        }
+        let mark = self.values.len();
+        self.tasks.push(Task::Function { node, name: String::from(name), mark });
+
+        for name_value in argument_names.iter().zip(&args) {
+            let name = String::from(*name_value.0);
+            let value: InterpValue = name_value.1.clone();
+            self.set_variable(name, value)?;
+        }
+
+        self.tasks.push(Task::Statements { block: &func.body, next: 0 });
+        Ok(())
+    }
+
+    fn leave_function(&mut self, name: &str) -> Result<(), InterpError> {
+        for hook in &mut self.hooks {
+            hook.exit_function(name);
+        }
+        self.pop_frame()?;
+        Ok(())
+    }
+
+    /// Takes the callee and arguments of `return f(..)` to the enclosing
+    /// function call, to make once the returning function's frames are gone.
+    fn tail_call(&mut self, call: &'a AstFunctionCall, mark: usize) -> Result<(), Unwind> {
+        let args = self.values.split_off(mark);
+        let callee = self.get_variable(&call.name)
+            .map_err(|_| self.unknown_name_error("function", &call.name))?
+            .clone();
+        let call = TailCall {
+            name: call.name.clone(),
+            callee,
+            args
+        };
+        Err(Unwind::TailCall(call))
+    }
+
+    /// Calling a struct makes an instance of it, with the arguments as the
+    /// values of its fields in the order they were declared.
+    fn instantiate(&self, struct_id: usize, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        let structure = self.get_struct(struct_id)?;
+        if args.len() != structure.fields.len() {
+            let msg = format!("The struct expects {} but was given {}", counted(structure.fields.len(), "field"), args.len());
+            return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg));
+        }
+        for (field, value) in structure.fields.iter().zip(&args) {
+            self.check_field(structure, field, value)?;
+        }
+        Ok(InterpValue::InterpInstance{struct_id, name: structure.name.clone(), fields: Rc::new(RefCell::new(args))})
+    }
+
+    /// Checks that `value` may be stored in `field` of `structure`, and
+    /// returns the index of the field. A field declared with a builtin type
+    /// takes values of that type, one declared with the name of a struct
+    /// takes instances of that struct.
+    fn check_field(&self, structure: &InterpStruct, field: &str, value: &InterpValue) -> Result<usize, InterpError> {
+        let index = structure.field_index(field)?;
+
+        let expected = &structure.types[index];
+        let fits = if TYPE_NAMES.contains(&&expected[..]) {
+            let actual = value.type_name();
+            *expected == actual || (expected == "Number" && actual == "Int")
+        } else {
+            match (self.get_variable(expected), value) {
+                (Ok(&InterpValue::InterpStruct(expected_id)), &InterpValue::InterpInstance{struct_id, ..}) => {
+                    struct_id == expected_id
+                }
+                (Ok(&InterpValue::InterpStruct(_)), _) => { false }
+                _ => {
+                    let msg = format!("The field {} has the unknown type {}", field, expected);
+                    let msg = suggest::with_suggestion(msg, expected, TYPE_NAMES.iter().cloned());
+                    return Err(InterpError::new(&error_codes::UNKNOWN_TYPE, msg));
+                }
             }
+        };
+        if !fits {
+            let msg = format!("The field {} has type {} but was given {}", field, expected, with_article(&self.type_of(value)));
+            return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
         }
+        Ok(index)
     }
-}
 
-pub fn interp(ast: Ast) -> Result<InterpValue, InterpError> {
-    let root_expr = &ast.root;
+    /// The type name of `value`, with instances named after their struct.
+    fn type_of(&self, value: &InterpValue) -> String {
+        match value {
+            InterpValue::InterpInstance{name, ..} => { name.to_string() }
+            _ => { String::from(value.type_name()) }
+        }
+    }
 
-    let mut closures: Vec<Option<Closure>> = Vec::new();
-    let base_closure = Closure::new(root_expr, None);
-    let base_closure_id = closures.len();
-    closures.push(Some(base_closure));
+    fn get_struct(&self, struct_id: usize) -> Result<&InterpStruct, InterpError> {
+        match self.structs.get(struct_id) {
+            Some(structure) => { Ok(structure) }
+            None => {
+                let msg = format!("The struct {} no longer exists", struct_id);
+                Err(InterpError::new(&error_codes::INTERNAL, msg))
+            }
+        }
+    }
 
-    let base_stack_frame = StackFrame::new(root_expr, base_closure_id);
+    /// The struct and fields of an instance, for reading or writing a field.
+    fn instance_fields(&self, value: InterpValue, field: &str) -> Result<(&InterpStruct, Fields), InterpError> {
+        match value {
+            InterpValue::InterpInstance{struct_id, fields, ..} => { Ok((self.get_struct(struct_id)?, fields)) }
+            other => {
+                let msg = format!("Can not access the field {} of {}", field, with_article(other.type_name()));
+                Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+            }
+        }
+    }
 
-    let mut interp = Interp {
-        stack_size: 10,
-        functions: Vec::new(),
-        structs: Vec::new(),
-        closures: closures,
-        current_frame: base_stack_frame
-    };
-    return interp.evaluate_next(root_expr);
-}
\ No newline at end of file
+    /// `next(iterator)` advances the iterator and returns `done, value`.
+    fn next(&mut self, node: &'a AstNodeType, args: Vec<InterpValue>) -> Result<(), Unwind> {
+        let source = match (args.len(), args.first()) {
+            (1, Some(InterpValue::InterpIterator(source))) => { source.clone() }
+            (1, Some(other)) => {
+                let msg = format!("next expects an Iterator but was given {}", with_article(other.type_name()));
+                return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg).into());
+            }
+            (count, _) => {
+                let msg = format!("next expects 1 argument but was called with {}", count);
+                return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg).into());
+            }
+        };
+        self.schedule_next(node, source)
+    }
+
+    /// Advances an iterator, leaving the `done, value` tuple `next` returns.
+    /// A generator is called for its next value.
+    fn schedule_next(&mut self, node: &'a AstNodeType, source: Rc<RefCell<IterSource>>) -> Result<(), Unwind> {
+        let generator = match *source.borrow() {
+            IterSource::Function(ref function) => { Some(function.clone()) }
+            _ => { None }
+        };
+        if let Some(generator) = generator {
+            self.tasks.push(Task::CheckStep);
+            return self.call(node, "next", generator, Vec::new());
+        }
+        let value = source.borrow_mut().advance();
+        self.values.push(iterator::step(value));
+        Ok(())
+    }
+
+    /// Appends every argument after the first to the Array in the first, or
+    /// their printed form to the Builder in the first, and returns it so
+    /// calls can be piped.
+    fn push(&mut self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        if let Some(&InterpValue::InterpArray(_)) = args.first() {
+            let added = args.len() - 1;
+            let array = collections::push(args)?;
+            self.check_grown(&array, added)?;
+            return Ok(array);
+        }
+
+        let mut args = args.into_iter();
+        let builder = match args.next() {
+            Some(InterpValue::InterpBuilder(buffer)) => { buffer }
+            Some(other) => {
+                let msg = format!("push expects an Array or a Builder but was given {}", with_article(other.type_name()));
+                return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+            }
+            None => {
+                let msg = "push expects an Array or a Builder to push to".to_string();
+                return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg));
+            }
+        };
+
+        let pushed = {
+            let mut buffer = builder.borrow_mut();
+            let length = buffer.len();
+            for value in args {
+                buffer.push_str(&format::format_value(value));
+            }
+            self.check_string_length(&buffer)?;
+            buffer.len() - length
+        };
+        self.allocate(pushed, &[])?;
+        Ok(InterpValue::InterpBuilder(builder))
+    }
+
+    fn call_builtin(&mut self, node: &'a AstNodeType, call: &'a AstFunctionCall, args: Vec<InterpValue>) -> Result<(), Unwind> {
+        let intrinsic = match self.builtins.get_mut(&call.name) {
+            Some(&mut Builtin::Intrinsic(intrinsic)) => { intrinsic }
+            Some(&mut Builtin::Native(ref mut function)) => {
+                let value = function(&args)?;
+                self.values.push(value);
+                return Ok(());
+            }
+            Some(&mut Builtin::Calling(calling)) => { return calling(self, node, args); }
+            Some(&mut Builtin::Special(special)) => { return special(self, node, call); }
+            None => { return Err(self.unknown_name_error("function", &call.name).into()); }
+        };
+        let value = intrinsic(self, node, call, args)?;
+        self.values.push(value);
+        Ok(())
+    }
+
+    /// Writes the arguments to the output in the given style. A `print` or
+    /// `println` whose first argument has placeholders writes the rest
+    /// formatted into it instead.
+    fn print(&mut self, args: Vec<InterpValue>, style: Style) -> Result<InterpValue, InterpError> {
+        let args = if style.formats() && format::is_template(&args) {
+            vec![format::format_builtin(args)?]
+        } else {
+            args
+        };
+        if let Err(error) = output::write(&mut *self.output, style, args) {
+            let msg = format!("Failed to print: {}", error);
+            return Err(InterpError::new(&error_codes::OUTPUT_FAILED, msg));
+        }
+        Ok(InterpValue::InterpVoid)
+    }
+
+    /// Lists made by builtins, like `ilist` and `concat`, are checked against
+    /// `max_array_length` and `max_memory`.
+    fn checked_list(&mut self, value: InterpValue) -> Result<InterpValue, InterpError> {
+        let length = match value {
+            InterpValue::InterpList(ref list) => { list.len() }
+            InterpValue::InterpArray(ref values) => { values.borrow().len() }
+            _ => { return Ok(value); }
+        };
+        self.check_array_length(length)?;
+        self.allocate(length * mem::size_of::<InterpValue>(), &[&value])?;
+        Ok(value)
+    }
+
+    /// Checks an Array that grew in place by `added` elements.
+    fn check_grown(&mut self, array: &InterpValue, added: usize) -> Result<(), InterpError> {
+        if let InterpValue::InterpArray(values) = array {
+            let length = values.borrow().len();
+            self.check_array_length(length)?;
+            self.allocate(added * mem::size_of::<InterpValue>(), &[array])?;
+        }
+        Ok(())
+    }
+
+    /// `run_loop(update)` calls `update(dt)` once a frame, with the seconds
+    /// since the previous frame, until every window is closed, the last one
+    /// by the user, or `update` returns `false`. `update` draws the frame
+    /// and presents it, which waits for the display to be ready. Before
+    /// every frame the functions of a watched script that changed are
+    /// swapped in, globals keeping their values.
+    fn run_loop(&mut self, node: &'a AstNodeType, args: Vec<InterpValue>) -> Result<(), Unwind> {
+        let update = match (args.len(), args.first()) {
+            (1, Some(update @ &InterpValue::InterpFunction{..})) | (1, Some(update @ &InterpValue::InterpBoundFunction{..})) => {
+                update.clone()
+            }
+            (1, Some(other)) => {
+                let msg = format!("run_loop expects a Function but was given {}", with_article(other.type_name()));
+                return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg).into());
+            }
+            (count, _) => {
+                let msg = format!("run_loop expects 1 argument but was called with {}", count);
+                return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg).into());
+            }
+        };
+
+        let mark = self.values.len();
+        self.values.push(update);
+        self.next_frame(node, mark, Instant::now())
+    }
+
+    /// Calls the update function `run_loop` holds at `mark` for the next
+    /// frame, or ends the loop once there are no windows left.
+    fn next_frame(&mut self, node: &'a AstNodeType, mark: usize, last_frame: Instant) -> Result<(), Unwind> {
+        if !self.sdl.frame()? {
+            self.values.truncate(mark);
+            self.values.push(InterpValue::InterpVoid);
+            return Ok(());
+        }
+        if let Err(error) = self.reload_changed() {
+            eprintln!("Failed to reload the script, running the old version: {}", error);
+        }
+        let dt = last_frame.elapsed().as_secs_f64();
+        self.tasks.push(Task::RunLoop { node, mark, last_frame: Instant::now() });
+        let update = self.values[mark].clone();
+        self.call(node, "run_loop", update, vec![InterpValue::InterpNumber(dt)])
+    }
+
+    /// `seed(n)` restarts the random builtins from the integer `n`, so the
+    /// numbers they return after it are the same on every run.
+    fn seed(&mut self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        let seed = number_arguments("seed", &args, 1)?[0];
+        if seed.fract() != 0.0 || !seed.is_finite() {
+            let msg = format!("seed expects an integer, found {}", seed);
+            return Err(InterpError::new(&error_codes::INTEGER_REQUIRED, msg));
+        }
+        self.seed_rng(seed as i64 as u64);
+        Ok(InterpValue::InterpVoid)
+    }
+
+    /// `shuffle(list)` puts the elements of an Array in a random order in
+    /// place and returns it. An IList is left as it is and a shuffled copy
+    /// returned.
+    fn shuffle(&mut self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        let mut values = collections::list_argument("shuffle", &args)?;
+        // Fisher-Yates, every order is equally likely
+        for i in (1..values.len()).rev() {
+            let j = self.random_index("shuffle", i + 1)?;
+            values.swap(i, j);
+        }
+        Ok(collections::replace_elements(&args[0], values))
+    }
+
+    /// `choice(list)` is one element of an Array or IList, picked at random.
+    fn choice(&mut self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        let values = collections::list_argument("choice", &args)?;
+        if values.is_empty() {
+            let msg = format!("choice was given an empty {}", args[0].type_name());
+            return Err(InterpError::new(&error_codes::INDEX_OUT_OF_BOUNDS, msg));
+        }
+        let index = self.random_index("choice", values.len())?;
+        Ok(values[index].clone())
+    }
+
+    /// A random index below `length`, recorded and replayed like the results
+    /// of the other random builtins.
+    fn random_index(&mut self, name: &str, length: usize) -> Result<usize, InterpError> {
+        let index = self.recorded(name, |interp| Ok(interp.rng.range_int(0, length as i64 - 1) as f64))?;
+        if index.fract() != 0.0 || index < 0.0 || index >= length as f64 {
+            let msg = format!("The replay log has index {} for a call to {} with {} elements", index, name, length);
+            return Err(InterpError::new(&error_codes::REPLAY_MISMATCH, msg));
+        }
+        Ok(index as usize)
+    }
+
+    /// Calls a builtin from `NONDETERMINISTIC_BUILTINS`, recording its result
+    /// or taking it from the replay log instead.
+    fn nondeterministic_builtin(&mut self, name: &str, args: &[InterpValue]) -> Result<f64, InterpError> {
+        self.recorded(name, |interp| interp.compute_nondeterministic(name, args))
+    }
+
+    /// Runs `compute` for the builtin `name` and records the result, or when
+    /// replaying takes the next result from the log instead.
+    fn recorded<F>(&mut self, name: &str, compute: F) -> Result<f64, InterpError>
+        where F: FnOnce(&mut Interp<'a>) -> Result<f64, InterpError> {
+        if let EffectMode::Replay(ref mut effects) = self.effects {
+            return match effects.pop_front() {
+                Some(ref effect) if effect.builtin == name => { Ok(effect.value) }
+                Some(effect) => {
+                    let msg = format!("The replay log has a call to {} where {} is called", effect.builtin, name);
+                    Err(InterpError::new(&error_codes::REPLAY_MISMATCH, msg))
+                }
+                None => {
+                    let msg = format!("The replay log ended before the call to {}", name);
+                    Err(InterpError::new(&error_codes::REPLAY_MISMATCH, msg))
+                }
+            };
+        }
+
+        let value = compute(self)?;
+        if let EffectMode::Record(ref mut effects) = self.effects {
+            effects.push(Effect { builtin: String::from(name), value });
+        }
+        Ok(value)
+    }
+
+    fn compute_nondeterministic(&mut self, name: &str, args: &[InterpValue]) -> Result<f64, InterpError> {
+        let value = if name == "random" {
+            number_arguments(name, args, 0)?;
+            self.rng.next_f64()
+        } else if name == "now" {
+            number_arguments(name, args, 0)?;
+            if self.clock_frozen {
+                0.0
+            } else {
+                SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs_f64()).unwrap_or(0.0)
+            }
+        } else if name == "timer" {
+            number_arguments(name, args, 0)?;
+            self.seconds_since_start()
+        } else if name == "elapsed" {
+            let timer = number_arguments(name, args, 1)?;
+            self.seconds_since_start() - timer[0]
+        } else if name == "random_range" {
+            let bounds = number_arguments(name, args, 2)?;
+            if bounds.iter().any(|bound| !bound.is_finite()) || bounds[0] > bounds[1] {
+                let msg = format!("random_range needs finite bounds with low <= high, got {} and {}",
+                                  bounds[0], bounds[1]);
+                return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+            }
+            self.rng.range(bounds[0], bounds[1])
+        } else if name == "rand_int" {
+            let bounds = number_arguments(name, args, 2)?;
+            if bounds.iter().any(|bound| bound.fract() != 0.0) || bounds[0] > bounds[1] {
+                let msg = format!("rand_int needs integer bounds with low <= high, got {} and {}",
+                                  bounds[0], bounds[1]);
+                return Err(InterpError::new(&error_codes::INTEGER_REQUIRED, msg));
+            }
+            self.rng.range_int(bounds[0] as i64, bounds[1] as i64) as f64
+        } else {
+            let params = number_arguments(name, args, 2)?;
+            self.rng.normal(params[0], params[1])
+        };
+        Ok(value)
+    }
+
+    fn seconds_since_start(&self) -> f64 {
+        if self.clock_frozen {
+            return 0.0;
+        }
+        self.started.elapsed().as_secs_f64()
+    }
+
+    /// Counts `bytes` towards `max_memory`. Values are not counted when they
+    /// are freed, so once the count passes the limit garbage is collected and
+    /// the memory that is still reachable is measured, which is what the
+    /// limit applies to. `roots` are values being made that nothing else
+    /// holds yet.
+    fn allocate(&mut self, bytes: usize, roots: &[&InterpValue]) -> Result<(), InterpError> {
+        let max = match self.options.max_memory {
+            Some(max) => { max }
+            None => { return Ok(()); }
+        };
+        self.allocated += bytes;
+        if self.allocated > max {
+            self.collect(roots);
+            self.allocated = self.memory_usage();
+            if self.allocated > max {
+                let msg = format!("The script uses more than {} bytes of memory", max);
+                return Err(InterpError::new(&error_codes::RESOURCE_LIMIT, msg));
+            }
+        }
+        Ok(())
+    }
+
+    /// Strings made by operators, like concatenation, count towards
+    /// `max_memory`.
+    fn allocate_result(&mut self, value: &InterpValue) -> Result<(), InterpError> {
+        if let InterpValue::InterpString(string) = value {
+            return self.allocate(string.len(), &[]);
+        }
+        Ok(())
+    }
+
+    /// Roughly how many bytes the live scopes, and the values they and
+    /// evaluation hold on to, take up.
+    pub fn memory_usage(&self) -> usize {
+        let mut seen = HashSet::new();
+        let mut bytes = 0;
+        for closure in self.closures.iter().flatten() {
+            bytes += mem::size_of::<Closure>();
+            for (name, value) in &closure.variables {
+                bytes += name.len() + mem::size_of::<InterpValue>() + gc::heap_size(value, &mut seen);
+            }
+        }
+        for value in &self.values {
+            bytes += gc::heap_size(value, &mut seen);
+        }
+        bytes
+    }
+
+    /// Starts counting `max_steps` and `timeout` from now.
+    fn start_limits(&mut self) {
+        self.step_limit = self.options.max_steps.map(|steps| self.stats.evaluated_nodes + steps);
+        self.deadline = self.options.timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    /// Stops a runaway script once it has evaluated `max_steps` nodes or run
+    /// past its `timeout`.
+    fn check_limits(&self) -> Result<(), InterpError> {
+        let evaluated = self.stats.evaluated_nodes;
+        if let (Some(limit), Some(steps)) = (self.step_limit, self.options.max_steps) {
+            if evaluated > limit {
+                let msg = format!("The script took more than {} steps", steps);
+                return Err(InterpError::new(&error_codes::RESOURCE_LIMIT, msg));
+            }
+        }
+        if let (Some(deadline), Some(timeout)) = (self.deadline, self.options.timeout) {
+            if evaluated.is_multiple_of(DEADLINE_INTERVAL) && Instant::now() >= deadline {
+                let msg = format!("The script ran for longer than {:?}", timeout);
+                return Err(InterpError::new(&error_codes::RESOURCE_LIMIT, msg));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every operation that creates or grows a string checks it against
+    /// `max_string_length`.
+    fn check_string_length(&self, string: &str) -> Result<(), InterpError> {
+        if let Some(max) = self.options.max_string_length {
+            let length = string.chars().count();
+            if length > max {
+                let msg = format!("String of length {} exceeds the limit of {}", length, max);
+                return Err(InterpError::new(&error_codes::RESOURCE_LIMIT, msg));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_array_length(&self, length: usize) -> Result<(), InterpError> {
+        if let Some(max) = self.options.max_array_length {
+            if length > max {
+                let msg = format!("Array of length {} exceeds the limit of {}", length, max);
+                return Err(InterpError::new(&error_codes::RESOURCE_LIMIT, msg));
+            }
+        }
+        Ok(())
+    }
+
+    fn add_closure(&mut self, parent_closure_id: usize) -> usize {
+        let closure = Closure::new(Some(parent_closure_id));
+        self.stats.allocations += 1;
+        self.allocated += mem::size_of::<Closure>();
+        match self.free_closures.pop() {
+            Some(id) => {
+                self.closures[id] = Some(closure);
+                id
+            }
+            None => {
+                self.closures.push(Some(closure));
+                self.closures.len() - 1
+            }
+        }
+    }
+
+    fn live_closures(&self) -> usize {
+        self.closures.len() - self.free_closures.len()
+    }
+
+    /// Frees the closures that nothing can reach anymore, once enough
+    /// closures are live to make it worth the walk. What is reachable starts
+    /// from the frames, the value stack and `roots`, values that are in use
+    /// but not yet stored anywhere.
+    fn collect_garbage(&mut self, roots: &[&InterpValue]) {
+        if self.live_closures() < self.next_collection {
+            return;
+        }
+        self.collect(roots);
+    }
+
+    /// Frees every closure that neither the frames, the value stack nor
+    /// `roots` can reach.
+    fn collect(&mut self, roots: &[&InterpValue]) {
+
+        let mut pending: Vec<usize> = Vec::new();
+        let mut seen = HashSet::new();
+        let mut frame = Some(&self.current_frame);
+        while let Some(current) = frame {
+            pending.push(current.closure_id);
+            frame = current.parent_frame.as_deref();
+        }
+        for value in self.values.iter().chain(roots.iter().cloned()) {
+            gc::closures_of(value, &mut seen, &mut pending);
+        }
+
+        let mut marked = vec![false; self.closures.len()];
+        while let Some(id) = pending.pop() {
+            if marked[id] {
+                continue;
+            }
+            marked[id] = true;
+            if let Some(Some(ref closure)) = self.closures.get(id) {
+                pending.extend(closure.parent_id);
+                for value in closure.variables.values() {
+                    gc::closures_of(value, &mut seen, &mut pending);
+                }
+            }
+        }
+
+        for (id, closure) in self.closures.iter_mut().enumerate() {
+            if closure.is_some() && !marked[id] {
+                *closure = None;
+                self.free_closures.push(id);
+            }
+        }
+        self.next_collection = MIN_COLLECTION.max(self.live_closures() * 2);
+    }
+
+    fn pop_value(&mut self) -> Result<InterpValue, InterpError> {
+        match self.values.pop() {
+            Some(value) => { Ok(value) }
+            None => {
+                let msg = "Unable to pop from the value stack".to_string();
+                Err(InterpError::new(&error_codes::INTERNAL, msg))
+            }
+        }
+    }
+
+    fn peek_value(&self) -> Result<&InterpValue, InterpError> {
+        match self.values.last() {
+            Some(value) => { Ok(value) }
+            None => {
+                let msg = "The value stack is empty".to_string();
+                Err(InterpError::new(&error_codes::INTERNAL, msg))
+            }
+        }
+    }
+
+    /// Runs `task` and everything it schedules on top of whatever is being
+    /// evaluated already, and returns the value it leaves.
+    fn evaluate_to_end(&mut self, task: Task<'a>) -> Evaluation {
+        let base = self.tasks.len();
+        let mark = self.values.len();
+        self.tasks.push(task);
+        match self.evaluate_tasks(base, false) {
+            Ok(RunState::Finished(value)) => { Ok(value) }
+            Ok(RunState::Interrupted) => {
+                let msg = "Evaluation was interrupted where it can not be".to_string();
+                Err(InterpError::new(&error_codes::INTERNAL, msg).into())
+            }
+            Err(unwind) => {
+                self.values.truncate(mark);
+                Err(unwind)
+            }
+        }
+    }
+
+    /// Performs tasks until the stack is back down to `base`, and returns
+    /// the value they left. When `pausable`, evaluation is interrupted as
+    /// soon as `yield_at` is reached or an event waits to be taken, leaving
+    /// the rest on the stacks.
+    fn evaluate_tasks(&mut self, base: usize, pausable: bool) -> Result<RunState, Unwind> {
+        while self.tasks.len() > base {
+            let task = match self.tasks.pop() {
+                Some(task) => { task }
+                None => { break; }
+            };
+            if let Err(unwind) = self.perform(task) {
+                self.unwind(base, unwind)?;
+            }
+            if pausable && self.tasks.len() > base && self.should_yield() {
+                return Ok(RunState::Interrupted);
+            }
+        }
+        Ok(RunState::Finished(self.pop_value()?))
+    }
+
+    fn should_yield(&self) -> bool {
+        self.yield_at.is_some_and(|at| self.stats.evaluated_nodes >= at)
+            || self.events.as_ref().is_some_and(|events| !events.is_empty())
+    }
+
+    /// Pops tasks until one of them catches `unwind`, and evaluation goes on
+    /// from there. What nothing above `base` catches is returned.
+    fn unwind(&mut self, base: usize, mut unwind: Unwind) -> Result<(), Unwind> {
+        while self.tasks.len() > base {
+            let task = match self.tasks.pop() {
+                Some(task) => { task }
+                None => { break; }
+            };
+            unwind = match self.catch(task, unwind) {
+                Ok(()) => { return Ok(()); }
+                Err(unwind) => { unwind }
+            };
+        }
+        Err(unwind)
+    }
+
+    /// Lets `task` handle control flow unwinding through it, returning what
+    /// keeps unwinding. Tasks that entered a frame leave it here.
+    fn catch(&mut self, task: Task<'a>, unwind: Unwind) -> Result<(), Unwind> {
+        match (task, unwind) {
+            (Task::Produced { node, .. }, Unwind::Error(error)) => {
+                Err(Unwind::Error(self.locate(error, node)))
+            }
+            (Task::PopFrame, unwind) => {
+                self.pop_frame()?;
+                Err(unwind)
+            }
+            (Task::Function { node, name, mark }, unwind) => {
+                self.leave_function(&name)?;
+                self.values.truncate(mark);
+                match unwind {
+                    Unwind::Return(value) => {
+                        self.values.push(value);
+                        self.collect_garbage(&[]);
+                        Ok(())
+                    }
+                    Unwind::TailCall(call) => {
+                        let mut roots: Vec<&InterpValue> = call.args.iter().collect();
+                        roots.push(&call.callee);
+                        self.collect_garbage(&roots);
+                        self.call(node, &call.name, call.callee, call.args)
+                    }
+                    unwind => { Err(Unwind::Error(unwind.into_error())) }
+                }
+            }
+            (Task::WhileBody { mark, .. }, Unwind::Break) => {
+                self.values.truncate(mark);
+                self.values.push(InterpValue::InterpVoid);
+                Ok(())
+            }
+            (Task::WhileBody { node, call, mark, .. }, Unwind::Continue) => {
+                self.values.truncate(mark);
+                self.collect_garbage(&[]);
+                self.evaluate_while(node, call)
+            }
+            (Task::ForBody { node, variable, body, mark }, unwind) => {
+                self.pop_frame()?;
+                match unwind {
+                    Unwind::Break => {
+                        self.values.truncate(mark);
+                        self.values.push(InterpValue::InterpVoid);
+                        Ok(())
+                    }
+                    Unwind::Continue => {
+                        self.values.truncate(mark + 1);
+                        self.collect_garbage(&[]);
+                        self.advance_for(node, variable, body, mark)
+                    }
+                    unwind => { Err(unwind) }
+                }
+            }
+            (_, unwind) => { Err(unwind) }
+        }
+    }
+
+    fn perform(&mut self, task: Task<'a>) -> Result<(), Unwind> {
+        match task {
+            Task::Eval { node, whole } => {
+                self.stats.evaluated_nodes += 1;
+                self.tasks.push(Task::Produced { node, whole });
+                self.check_limits()?;
+                self.evaluate_node(node)?;
+            }
+            Task::Produced { whole, .. } => {
+                let type_name = self.peek_value()?.type_name();
+                *self.stats.value_counts.entry(type_name).or_insert(0) += 1;
+                if self.events.is_some() {
+                    let value = self.peek_value()?.clone();
+                    self.emit(InterpEvent::ProducedValue(value));
+                }
+                if let (false, Some(value)) = (whole, self.values.last_mut()) {
+                    if let InterpValue::InterpTuple(values) = value {
+                        *value = values[0].clone();
+                    }
+                }
+            }
+            Task::Statements { block, next } => {
+                if next > 0 && next < block.statements.len() {
+                    self.pop_value()?;
+                }
+                match block.statements.get(next) {
+                    Some(statement) => {
+                        self.tasks.push(Task::Statements { block, next: next + 1 });
+                        self.tasks.push(Task::Statement(statement));
+                    }
+                    None if next == 0 => { self.values.push(InterpValue::InterpVoid); }
+                    None => {}
+                }
+            }
+            Task::Statement(statement) => {
+                self.current_statement = Some(statement);
+                self.emit(InterpEvent::EnteredStatement(statement));
+                for hook in &mut self.hooks {
+                    hook.before_statement(statement);
+                }
+                self.tasks.push(Task::FinishStatement(statement));
+                self.tasks.push(Task::Eval { node: &statement.node, whole: true });
+            }
+            Task::FinishStatement(statement) => {
+                if let Some(value) = self.values.last() {
+                    for hook in &mut self.hooks {
+                        hook.after_statement(statement, value);
+                    }
+                }
+            }
+            Task::PopFrame => {
+                self.pop_frame()?;
+            }
+            Task::Spread => {
+                match self.pop_value()? {
+                    InterpValue::InterpArray(ref values) => {
+                        self.values.extend(values.borrow().iter().cloned());
+                    }
+                    value => {
+                        let msg = format!("Only arrays can be spread with ..., got {}", with_article(value.type_name()));
+                        return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg).into());
+                    }
+                }
+            }
+            Task::Call { node, call, mark } => {
+                let args = self.values.split_off(mark);
+                return self.call_named(node, call, args);
+            }
+            Task::Function { name, .. } => {
+                self.leave_function(&name)?;
+                self.collect_garbage(&[]);
+            }
+            Task::TailCall { call, mark } => {
+                return self.tail_call(call, mark);
+            }
+            Task::Return => {
+                let value = self.pop_value()?;
+                return Err(Unwind::Return(value));
+            }
+            Task::Assign(assignment) => {
+                let name = assignment.to.name.clone();
+                let value = self.pop_value()?;
+                if assignment.declares {
+                    self.set_variable(name, value)?;
+                } else {
+                    self.assign_variable(name, value)?;
+                }
+                self.values.push(InterpValue::InterpVoid);
+            }
+            Task::Alias(alias) => {
+                let name = alias.to.name.clone();
+                let value = self.pop_value()?;
+                self.set_variable(name, value)?;
+                self.values.push(InterpValue::InterpVoid);
+            }
+            Task::Destructure(destructure) => {
+                let values = match self.pop_value()? {
+                    InterpValue::InterpTuple(values) => { (*values).clone() }
+                    value => { vec![value] }
+                };
+
+                if values.len() != destructure.to.len() {
+                    let msg = format!("Can not assign {} to {}", counted(values.len(), "value"), counted(destructure.to.len(), "name"));
+                    return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg).into());
+                }
+                for (variable, value) in destructure.to.iter().zip(values) {
+                    if destructure.declares {
+                        self.set_variable(variable.name.clone(), value)?;
+                    } else {
+                        self.assign_variable(variable.name.clone(), value)?;
+                    }
+                }
+                self.values.push(InterpValue::InterpVoid);
+            }
+            Task::Operator(operation) => {
+                let rhs = self.pop_value()?;
+                let lhs = self.pop_value()?;
+                let res = self.apply_operation(lhs, rhs, operation.operator)?;
+                self.values.push(res);
+            }
+            Task::Unary(operation) => {
+                let operand = self.pop_value()?;
+                let res = if let AstUnaryOperator::Not = operation.operator {
+                    InterpValue::InterpBoolean(!self.condition(&operand)?)
+                } else if self.strict_overflow {
+                    operators::apply_checked_unary_operation(operand, operation.operator)?
+                } else {
+                    operators::apply_unary_operation(operand, operation.operator)?
+                };
+                self.values.push(res);
+            }
+            Task::Logical(operation) => {
+                let lhs = self.pop_value()?;
+                let lhs = self.condition(&lhs)?;
+
+                // `false && x` and `true || x` are decided without evaluating x
+                let decided = match operation.operator {
+                    AstLogicalOperator::And => { !lhs }
+                    AstLogicalOperator::Or => { lhs }
+                };
+                if decided {
+                    self.values.push(InterpValue::InterpBoolean(lhs));
+                } else {
+                    self.tasks.push(Task::Condition);
+                    self.tasks.push(Task::Eval { node: &operation.rhs, whole: false });
+                }
+            }
+            Task::Condition => {
+                let value = self.pop_value()?;
+                let holds = self.condition(&value)?;
+                self.values.push(InterpValue::InterpBoolean(holds));
+            }
+            Task::Tuple { mark } => {
+                let values = self.values.split_off(mark);
+                self.values.push(InterpValue::InterpTuple(Rc::new(values)));
+            }
+            Task::Array { mark } => {
+                let values = self.values.split_off(mark);
+                self.values.push(InterpValue::InterpArray(Rc::new(RefCell::new(values))));
+            }
+            Task::FieldAccess(access) => {
+                let value = self.pop_value()?;
+                let (structure, fields) = self.instance_fields(value, &access.field)?;
+                let index = structure.field_index(&access.field)?;
+                let value = fields.borrow()[index].clone();
+                self.values.push(value);
+            }
+            Task::FieldAssignment(assignment) => {
+                let value = self.pop_value()?;
+                let target = self.pop_value()?;
+                let (structure, fields) = self.instance_fields(target, &assignment.to.field)?;
+                let index = self.check_field(structure, &assignment.to.field, &value)?;
+                fields.borrow_mut()[index] = value;
+                self.values.push(InterpValue::InterpVoid);
+            }
+            Task::Index => {
+                let key = self.pop_value()?;
+                let value = self.pop_value()?;
+                self.values.push(collections::lookup(&value, &key)?);
+            }
+            Task::MatchArm { node, next } => {
+                let arm = match node.arms.get(next) {
+                    Some(arm) => { arm }
+                    None => {
+                        self.pop_value()?;
+                        self.values.push(InterpValue::InterpVoid);
+                        return Ok(());
+                    }
+                };
+                match arm.pattern {
+                    AstPattern::Wildcard => {
+                        self.pop_value()?;
+                        self.tasks.push(Task::Eval { node: &arm.body, whole: false });
+                    }
+                    AstPattern::Literal(ref literal) => {
+                        self.tasks.push(Task::MatchPattern { node, arm: next });
+                        self.tasks.push(Task::Eval { node: literal, whole: false });
+                    }
+                }
+            }
+            Task::MatchPattern { node, arm } => {
+                let pattern = self.pop_value()?;
+                if operators::values_equal(self.peek_value()?, &pattern) {
+                    self.pop_value()?;
+                    self.tasks.push(Task::Eval { node: &node.arms[arm].body, whole: false });
+                } else {
+                    self.tasks.push(Task::MatchArm { node, next: arm + 1 });
+                }
+            }
+            Task::If { node, call, mark } => {
+                let args = self.values.split_off(mark);
+                return self.choose_branch(node, call, args);
+            }
+            Task::While { node, call, body } => {
+                let condition = self.pop_value()?;
+                if !self.condition(&condition)? {
+                    self.values.push(InterpValue::InterpVoid);
+                    return Ok(());
+                }
+                let mark = self.values.len();
+                self.tasks.push(Task::WhileBody { node, call, mark });
+                return self.schedule_block(node, body);
+            }
+            Task::WhileBody { node, call, mark, .. } => {
+                self.values.truncate(mark);
+                self.collect_garbage(&[]);
+                return self.evaluate_while(node, call);
+            }
+            Task::ForStart { node, variable, body, mark } => {
+                return self.start_for(node, variable, body, mark);
+            }
+            Task::ForStep { node, variable, body, mark } => {
+                return self.step_for(node, variable, body, mark);
+            }
+            Task::ForBody { node, variable, body, mark } => {
+                self.pop_value()?;
+                self.pop_frame()?;
+                self.collect_garbage(&[]);
+                return self.advance_for(node, variable, body, mark);
+            }
+            Task::CheckStep => {
+                let value = self.pop_value()?;
+                self.values.push(iterator::check_step(value)?);
+            }
+            Task::RunLoop { node, mark, last_frame } => {
+                if let InterpValue::InterpBoolean(false) = self.pop_value()? {
+                    self.values.truncate(mark);
+                    self.values.push(InterpValue::InterpVoid);
+                    return Ok(());
+                }
+                return self.next_frame(node, mark, last_frame);
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls the function or builtin `call` names with the evaluated `args`.
+    fn call_named(&mut self, node: &'a AstNodeType, call: &'a AstFunctionCall, args: Vec<InterpValue>) -> Result<(), Unwind> {
+        let name = &call.name;
+        if !self.builtins.contains_key(name) {
+            let callee = self.get_variable(name)
+                .map_err(|_| self.unknown_name_error("function", name))?
+                .clone();
+            return self.call(node, name, callee, args);
+        }
+
+        if SIDE_EFFECTING_BUILTINS.contains(&&name[..]) {
+            let decision = match self.policy {
+                Some(ref mut policy) => { policy(name, &args) }
+                None => { PolicyDecision::Allow }
+            };
+            match decision {
+                PolicyDecision::Allow => {}
+                PolicyDecision::Deny => {
+                    let msg = format!("{} is not allowed here", name);
+                    return Err(InterpError::new(&error_codes::NOT_ALLOWED, msg).into());
+                }
+                PolicyDecision::DryRun => {
+                    self.values.push(InterpValue::InterpVoid);
+                    return Ok(());
+                }
+            }
+        }
+        self.call_builtin(node, call, args)
+    }
+
+    fn apply_operation(&mut self, lhs: InterpValue, rhs: InterpValue, operator: AstOperator) -> Result<InterpValue, InterpError> {
+        if !self.strict_overflow && !self.strict_division {
+            let res = operators::apply_operation(lhs, rhs, operator)?;
+            self.allocate_result(&res)?;
+            return Ok(res);
+        }
+
+        if self.strict_division {
+            operators::check_division(&lhs, &rhs, operator)?;
+        }
+        let res = if self.strict_overflow {
+            operators::apply_checked_operation(lhs, rhs, operator)?
+        } else {
+            operators::apply_operation(lhs, rhs, operator)?
+        };
+        if self.strict_division {
+            operators::check_number(&res, operator)?;
+        }
+        self.allocate_result(&res)?;
+        Ok(res)
+    }
+
+    /// Starts evaluating `node`, which leaves its value once the tasks
+    /// scheduled for it are done. Leaves get their value right away.
+    fn evaluate_node(&mut self, node: &'a AstNodeType) -> Result<(), Unwind> {
+        match node {
+            AstNodeType::Block(boxed) => {
+                let block = &**boxed;
+                self.schedule_block(node, block)?;
+            }
+            AstNodeType::FunctionCall(boxed) => {
+                let function = &**boxed;
+                if let Some(&Builtin::Special(special)) = self.builtins.get(&function.name) {
+                    return special(self, node, function);
+                }
+                let mark = self.values.len();
+                self.tasks.push(Task::Call { node, call: function, mark });
+                self.schedule_arguments(&function.arguments);
+            }
+            AstNodeType::StringValue(boxed) => {
+                let string = &**boxed;
+                let value = string.value.clone();
+                self.check_string_length(&value)?;
+                self.allocate(value.len(), &[])?;
+
+                self.values.push(InterpValue::InterpString(value));
+            }
+            AstNodeType::NumberValue(boxed) => {
+                let number = &**boxed;
+
+                let value = if self.bignum {
+                    self.big_number(&number.text)?
+                } else if let Some(integer) = number.integer {
+                    InterpValue::InterpInt(integer)
+                } else {
+                    InterpValue::InterpNumber(number.value)
+                };
+                self.values.push(value);
+            }
+            AstNodeType::Variable(boxed) => {
+                let variable = &**boxed;
+                let name = &variable.name;
+
+                let val = self.get_variable(name)?.clone();
+                self.values.push(val);
+            }
+            AstNodeType::Assignment(boxed) => {
+                let assignment = &**boxed;
+                self.tasks.push(Task::Assign(assignment));
+                self.tasks.push(Task::Eval { node: &assignment.from, whole: false });
+            }
+            AstNodeType::OperatorCall(boxed) => {
+                let operation = &**boxed;
+                self.tasks.push(Task::Operator(operation));
+                self.tasks.push(Task::Eval { node: &operation.rhs, whole: false });
+                self.tasks.push(Task::Eval { node: &operation.lhs, whole: false });
+            }
+            AstNodeType::UnaryOperatorCall(boxed) => {
+                let operation = &**boxed;
+                self.tasks.push(Task::Unary(operation));
+                self.tasks.push(Task::Eval { node: &operation.operand, whole: false });
+            }
+            AstNodeType::LogicalOperatorCall(boxed) => {
+                let operation = &**boxed;
+                self.tasks.push(Task::Logical(operation));
+                self.tasks.push(Task::Eval { node: &operation.lhs, whole: false });
+            }
+            AstNodeType::Alias(boxed) => {
+                let alias = &**boxed;
+                self.tasks.push(Task::Alias(alias));
+                self.tasks.push(Task::Eval { node: &alias.from, whole: false });
+            }
+            AstNodeType::StructDeclaration(boxed) => {
+                let dec = &**boxed;
+
+                let index = self.structs.len();
+                if self.options.max_structs.is_some_and(|max| index >= max) {
+                    let msg = format!("More than {} structs declared", index);
+                    return Err(InterpError::new(&error_codes::RESOURCE_LIMIT, msg).into());
+                }
+                self.structs.push(InterpStruct::from_declaration(dec)?);
+
+                self.values.push(InterpValue::InterpStruct(index));
+            }
+            AstNodeType::FunctionDeclaration(boxed) => {
+                let dec = &**boxed;
+
+                // Evaluating the same declaration again in the same scope, say in
+                // a loop body, yields the same function instead of a new entry.
+                let index = match self.function_ids.get(&(dec as *const AstFunctionDeclaration)) {
+                    Some(&index) => { index }
+                    None => {
+                        let index = self.functions.len();
+                        self.functions.push(dec);
+                        self.function_ids.insert(dec as *const AstFunctionDeclaration, index);
+                        index
+                    }
+                };
+                let closure_id = self.current_frame.closure_id;
+                self.values.push(InterpValue::InterpFunction{id: index, closure_id});
+            }
+            &AstNodeType::NullValue(_) => {
+                self.values.push(InterpValue::InterpVoid);
+            }
+            AstNodeType::BooleanValue(boxed) => {
+                let boolean = &**boxed;
+                self.values.push(InterpValue::InterpBoolean(boolean.value));
+            }
+            AstNodeType::Return(boxed) => {
+                let ret = &**boxed;
+                if let Some(AstNodeType::FunctionCall(ref call)) = ret.value {
+                    if call.body.is_none() && !self.builtins.contains_key(&call.name) {
+                        let mark = self.values.len();
+                        self.tasks.push(Task::TailCall { call, mark });
+                        self.schedule_arguments(&call.arguments);
+                        return Ok(());
+                    }
+                }
+                match ret.value {
+                    Some(ref value) => {
+                        self.tasks.push(Task::Return);
+                        self.tasks.push(Task::Eval { node: value, whole: true });
+                    }
+                    None => { return Err(Unwind::Return(InterpValue::InterpVoid)); }
+                }
+            }
+            &AstNodeType::Break(_) => {
+                return Err(Unwind::Break);
+            }
+            &AstNodeType::Continue(_) => {
+                return Err(Unwind::Continue);
+            }
+            AstNodeType::Tuple(boxed) => {
+                let tuple = &**boxed;
+                let mark = self.values.len();
+                self.tasks.push(Task::Tuple { mark });
+                self.schedule_each(&tuple.values);
+            }
+            AstNodeType::Destructure(boxed) => {
+                let destructure = &**boxed;
+                self.tasks.push(Task::Destructure(destructure));
+                self.tasks.push(Task::Eval { node: &destructure.from, whole: true });
+            }
+            &AstNodeType::Spread(_) => {
+                let msg = "... can only be used in the arguments of a call".to_string();
+                return Err(InterpError::new(&error_codes::INTERNAL, msg).into());
+            }
+            AstNodeType::ArrayLiteral(boxed) => {
+                let array = &**boxed;
+                self.check_array_length(array.values.len())?;
+                self.allocate(array.values.len() * mem::size_of::<InterpValue>(), &[])?;
+                let mark = self.values.len();
+                self.tasks.push(Task::Array { mark });
+                self.schedule_each(&array.values);
+            }
+            AstNodeType::FieldAccess(boxed) => {
+                let access = &**boxed;
+                self.tasks.push(Task::FieldAccess(access));
+                self.tasks.push(Task::Eval { node: &access.value, whole: false });
+            }
+            AstNodeType::FieldAssignment(boxed) => {
+                let assignment = &**boxed;
+                self.tasks.push(Task::FieldAssignment(assignment));
+                self.tasks.push(Task::Eval { node: &assignment.from, whole: false });
+                self.tasks.push(Task::Eval { node: &assignment.to.value, whole: false });
+            }
+            AstNodeType::Index(boxed) => {
+                let index = &**boxed;
+                self.tasks.push(Task::Index);
+                self.tasks.push(Task::Eval { node: &index.index, whole: false });
+                self.tasks.push(Task::Eval { node: &index.value, whole: false });
+            }
+            AstNodeType::Match(boxed) => {
+                let node = &**boxed;
+                self.tasks.push(Task::MatchArm { node, next: 0 });
+                self.tasks.push(Task::Eval { node: &node.value, whole: false });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl <'a>Default for Interp<'a> {
+    fn default() -> Interp<'a> {
+        Interp::new()
+    }
+}
+
+impl <'a>Interp<'a> {
+    pub fn new() -> Interp<'a> {
+        let mut closures: Vec<Option<Closure>> = Vec::new();
+        let base_closure = Closure::new(None);
+        closures.push(Some(base_closure));
+
+        let base_stack_frame = StackFrame::new(None, ROOT_CLOSURE_ID, false);
+
+        Interp {
+            functions: Vec::new(),
+            function_ids: HashMap::new(),
+            structs: Vec::new(),
+            closures,
+            free_closures: Vec::new(),
+            next_collection: MIN_COLLECTION,
+            tasks: Vec::new(),
+            values: Vec::new(),
+            current_frame: base_stack_frame,
+            output: Box::new(io::stdout()),
+            hooks: Vec::new(),
+            builtins: default_builtins(),
+            current_statement: None,
+            rng: Rng::from_time(),
+            policy: None,
+            bignum: false,
+            strict_overflow: false,
+            strict_division: false,
+            strict_conditions: false,
+            yield_interval: None,
+            yield_at: None,
+            effects: EffectMode::Live,
+            stats: InterpStats { allocations: 1, ..InterpStats::default() },
+            options: InterpOptions::default(),
+            step_limit: None,
+            deadline: None,
+            started: Instant::now(),
+            clock_frozen: false,
+            allocated: 0,
+            events: None,
+            sdl: Sdl::default(),
+            watcher: None,
+            reloaded: ReloadedScripts::default()
+        }
+    }
+
+    /// Switches number literals to arbitrary precision, which is only
+    /// available when built with the `bignum` feature.
+    pub fn set_bignum(&mut self, enabled: bool) -> Result<(), InterpError> {
+        if enabled && !cfg!(feature = "bignum") {
+            let msg = "This build has no bignum support, rebuild with --features bignum".to_string();
+            return Err(InterpError::new(&error_codes::BIGNUM_UNAVAILABLE, msg));
+        }
+        self.bignum = enabled;
+        Ok(())
+    }
+
+    pub fn set_options(&mut self, options: InterpOptions) {
+        self.options = options;
+    }
+
+    /// Makes arithmetic that overflows an error instead of an infinity or an
+    /// Int that wraps around.
+    pub fn set_strict_overflow(&mut self, enabled: bool) {
+        self.strict_overflow = enabled;
+    }
+
+    /// Makes dividing by zero, and results that are not a number, errors
+    /// instead of infinities and NaN.
+    pub fn set_strict_division(&mut self, enabled: bool) {
+        self.strict_division = enabled;
+    }
+
+    /// Turns on every check of the strict dialect: overflow and division
+    /// errors, and conditions that must be booleans.
+    pub fn set_strict(&mut self, enabled: bool) {
+        self.strict_overflow = enabled;
+        self.strict_division = enabled;
+        self.strict_conditions = enabled;
+    }
+
+    fn condition(&self, value: &InterpValue) -> Result<bool, InterpError> {
+        if self.strict_conditions && !matches!(value, &InterpValue::InterpBoolean(_)) {
+            let msg = format!("Conditions must be a Boolean in strict mode, found {}", with_article(value.type_name()));
+            return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+        }
+        Ok(value.evals_to_true())
+    }
+
+    #[cfg(feature = "bignum")]
+    fn big_number(&self, text: &str) -> Result<InterpValue, InterpError> {
+        match BigDecimal::from_str(text) {
+            Ok(num) => { Ok(InterpValue::InterpBigNumber(num)) }
+            Err(error) => {
+                let msg = format!("Failed to parse number {}: {}", text, error);
+                Err(InterpError::new(&error_codes::INTERNAL, msg))
+            }
+        }
+    }
+
+    #[cfg(not(feature = "bignum"))]
+    fn big_number(&self, text: &str) -> Result<InterpValue, InterpError> {
+        let msg = format!("Can not evaluate {} without bignum support", text);
+        Err(InterpError::new(&error_codes::BIGNUM_UNAVAILABLE, msg))
+    }
+
+    pub fn add_hook(&mut self, hook: Box<dyn InterpHook>) {
+        self.hooks.push(hook);
+    }
+
+    /// Restarts the random builtins from `seed`, making their results
+    /// reproducible.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Stops the clock the time builtins read, so that scripts using them give
+    /// the same results on every run. `now` is then always the Unix epoch,
+    /// and no time passes for `timer` and `elapsed`.
+    pub fn freeze_clock(&mut self) {
+        self.clock_frozen = true;
+    }
+
+    /// Starts keeping the result of every nondeterministic builtin call, to
+    /// be read with `recorded_effects`.
+    pub fn record_effects(&mut self) {
+        self.effects = EffectMode::Record(Vec::new());
+    }
+
+    /// Makes the nondeterministic builtins return `effects` in order instead
+    /// of computing results, reproducing a recorded run.
+    pub fn replay_effects(&mut self, effects: Vec<Effect>) {
+        self.effects = EffectMode::Replay(effects.into_iter().collect());
+    }
+
+    pub fn stats(&self) -> InterpStats {
+        let mut stats = self.stats.clone();
+        stats.live_closures = self.live_closures();
+        stats
+    }
+
+    pub fn recorded_effects(&self) -> &[Effect] {
+        match self.effects {
+            EffectMode::Record(ref effects) => { effects }
+            _ => { &[] }
+        }
+    }
+
+    /// Watches the script at `path`, so that the functions it declares are
+    /// swapped into the running program when the file changes, every frame
+    /// of `run_loop` or whenever `reload_changed` is called.
+    pub fn watch_script(&mut self, path: &str) {
+        self.watcher = Some(ScriptWatcher::new(path));
+    }
+
+    /// Reloads the functions of the watched script if it changed since the
+    /// last call, returning the names of the replaced functions. A script
+    /// that fails to reload is left running the old code.
+    pub fn reload_changed(&mut self) -> Result<Vec<String>, String> {
+        let source = match self.watcher.as_mut().and_then(|watcher| watcher.poll()) {
+            Some(source) => { source }
+            None => { return Ok(Vec::new()); }
+        };
+        let ast = hot_reload::parse_script(&source)?;
+        // SAFETY: the interpreter owns the AST, and the only references to
+        // it are held by the interpreter itself, in fields dropped before it.
+        let ast = unsafe { self.reloaded.keep(ast) };
+        self.reload(ast).map_err(|error| error.to_string())
+    }
+
+    /// Consults `policy` before every call to one of the
+    /// `SIDE_EFFECTING_BUILTINS`. Without a policy everything is allowed.
+    pub fn set_policy(&mut self, policy: BuiltinPolicy) {
+        self.policy = Some(policy);
+    }
+
+    /// Declares `name` in the root scope, for hosts to hand scripts settings
+    /// and handles. Scripts can shadow or assign to it like any global.
+    pub fn set_global(&mut self, name: &str, value: InterpValue) -> Result<(), InterpError> {
+        let closure = self.get_mut_closure_by_id(ROOT_CLOSURE_ID)?;
+        closure.set_variable(String::from(name), value);
+        Ok(())
+    }
+
+    /// The value `name` has in the root scope, if a script or the host
+    /// declared it there.
+    pub fn get_global(&self, name: &str) -> Option<InterpValue> {
+        self.get_closure_by_id(ROOT_CLOSURE_ID).ok()
+            .and_then(|closure| closure.variables.get(name))
+            .cloned()
+    }
+
+    /// Makes `name(..)` call `function` with the evaluated arguments, in place
+    /// of any builtin or script function of that name.
+    pub fn register_fn<F>(&mut self, name: &str, function: F)
+        where F: FnMut(&[InterpValue]) -> Result<InterpValue, InterpError> + 'static {
+        self.builtins.insert(String::from(name), Builtin::Native(Box::new(function)));
+    }
+
+    /// Replaces where `print`, `println` and `debug` write to, which is
+    /// stdout by default.
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    /// Runs the top level statements of `ast` directly in the current frame,
+    /// so that anything they declare stays visible to later calls. After an
+    /// error the frames the failed statement pushed are unwound, so the
+    /// interpreter can keep running with everything declared before it, as
+    /// the REPL does.
+    pub fn run(&mut self, ast: &'a Ast) -> Result<InterpValue, InterpError> {
+        self.start_limits();
+        let depth = self.current_frame.index;
+        let task = match &ast.root {
+            AstNodeType::Block(block) => { Task::Statements { block, next: 0 } }
+            root => { Task::Eval { node: root, whole: false } }
+        };
+        let res = self.evaluate_to_end(task);
+
+        if res.is_err() {
+            self.unwind_to(depth);
+        }
+        res.map_err(Unwind::into_error)
+    }
+
+    /// Has `resume` hand control back to the host once it evaluated `steps`
+    /// nodes, or never when `None`.
+    pub fn set_yield_interval(&mut self, steps: Option<usize>) {
+        self.yield_interval = steps;
+    }
+
+    /// Prepares `ast` to be run with `resume`, for hosts that can not block
+    /// until the script is done, such as GUI event loops. An execution that
+    /// was interrupted before is abandoned.
+    pub fn start(&mut self, ast: &'a Ast) -> Execution<'a> {
+        self.tasks.clear();
+        self.values.clear();
+        self.unwind_to(0);
+        Execution {
+            ast,
+            started: false,
+            finished: false
+        }
+    }
+
+    /// Runs `execution` until it finishes or the yield interval runs out.
+    /// Control can be handed back in the middle of a statement or a call,
+    /// and the next call continues from there.
+    pub fn resume(&mut self, execution: &mut Execution<'a>) -> Result<RunState, InterpError> {
+        let interval = self.yield_interval;
+        self.resume_for(execution, interval)
+    }
+
+    /// Runs `ast` as a sequence of `InterpEvent`s. Evaluation only advances
+    /// as the events are consumed.
+    pub fn steps<'i>(&'i mut self, ast: &'a Ast) -> Steps<'i, 'a> {
+        self.events = Some(VecDeque::new());
+        let execution = self.start(ast);
+        Steps {
+            interp: self,
+            execution,
+            error: None,
+            finished: false
+        }
+    }
+
+    fn resume_for(&mut self, execution: &mut Execution<'a>, yield_interval: Option<usize>) -> Result<RunState, InterpError> {
+        if execution.finished {
+            return Ok(RunState::Finished(InterpValue::InterpVoid));
+        }
+        self.start_limits();
+        if !execution.started {
+            execution.started = true;
+            let task = match &execution.ast.root {
+                AstNodeType::Block(block) => { Task::Statements { block, next: 0 } }
+                root => { Task::Eval { node: root, whole: false } }
+            };
+            self.tasks.push(task);
+        }
+
+        self.yield_at = yield_interval.map(|steps| self.stats.evaluated_nodes + steps);
+        let res = self.evaluate_tasks(0, true);
+        self.yield_at = None;
+        match res {
+            Ok(RunState::Interrupted) => { Ok(RunState::Interrupted) }
+            Ok(finished) => {
+                execution.finished = true;
+                Ok(finished)
+            }
+            Err(unwind) => {
+                execution.finished = true;
+                self.values.clear();
+                self.unwind_to(0);
+                Err(unwind.into_error())
+            }
+        }
+    }
+
+    fn unwind_to(&mut self, depth: usize) {
+        while self.current_frame.index > depth {
+            if self.pop_frame().is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Swaps the functions `ast` declares at its top level into the running
+    /// program. Globals keep their values and functions that did not exist
+    /// yet are declared in the root scope, wherever the program currently
+    /// is. Returns the names of the replaced functions.
+    pub fn reload(&mut self, ast: &'a Ast) -> Result<Vec<String>, InterpError> {
+        let block = match &ast.root {
+            AstNodeType::Block(block) => { block }
+            _ => { return Ok(Vec::new()); }
+        };
+
+        let mut reloaded = Vec::new();
+        for statement in &block.statements {
+            if let AstNodeType::Alias(alias) = &statement.node {
+                if let AstNodeType::FunctionDeclaration(dec) = &alias.from {
+                    match self.get_global(&alias.to.name) {
+                        Some(InterpValue::InterpFunction{id, ..}) => {
+                            self.functions[id] = &**dec;
+                            self.function_ids.insert(&**dec as *const AstFunctionDeclaration, id);
+                            reloaded.push(alias.to.name.clone());
+                        }
+                        _ => {
+                            let id = self.functions.len();
+                            self.functions.push(&**dec);
+                            self.function_ids.insert(&**dec as *const AstFunctionDeclaration, id);
+                            self.set_global(&alias.to.name, InterpValue::InterpFunction{id, closure_id: ROOT_CLOSURE_ID})?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(reloaded)
+    }
+
+    /// All variable names reachable from the current frame, innermost first.
+    pub fn visible_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut closure_id = Some(self.current_frame.closure_id);
+
+        while let Some(id) = closure_id {
+            match self.get_closure_by_id(id) {
+                Ok(closure) => {
+                    for name in closure.variables.keys() {
+                        if !names.contains(name) {
+                            names.push(name.clone());
+                        }
+                    }
+                    closure_id = closure.parent_id;
+                }
+                Err(_) => {
+                    closure_id = None;
+                }
+            }
+        }
+        names
+    }
+}
+
+/// Checks that a builtin got `count` arguments, all numbers.
+fn number_arguments(name: &str, args: &[InterpValue], count: usize) -> Result<Vec<f64>, InterpError> {
+    if args.len() != count {
+        let msg = format!("{} expects {} but was called with {}", name, counted(count, "argument"), args.len());
+        return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg));
+    }
+
+    let mut numbers = Vec::with_capacity(count);
+    for arg in args {
+        match arg.to_f64() {
+            Some(num) => { numbers.push(num); }
+            None => {
+                let msg = format!("{} expects numbers but was given {}", name, with_article(arg.type_name()));
+                return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+            }
+        }
+    }
+    Ok(numbers)
+}
+
+/// `sleep(ms)` pauses the script for that many milliseconds.
+fn sleep(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    let ms = number_arguments("sleep", &args, 1)?[0];
+    match Duration::try_from_secs_f64(ms / 1000.0) {
+        Ok(duration) => { thread::sleep(duration); }
+        Err(_) => {
+            let msg = format!("sleep expects a positive number of milliseconds, found {}", ms);
+            return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+        }
+    }
+    Ok(InterpValue::InterpVoid)
+}
+
+fn no_arguments(name: &str, args: &[InterpValue]) -> Result<(), InterpError> {
+    if !args.is_empty() {
+        let msg = format!("{} expects 0 arguments but was called with {}", name, args.len());
+        return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg));
+    }
+    Ok(())
+}
+
+fn is_null(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    if args.len() != 1 {
+        let msg = format!("is_null expects 1 argument but was called with {}", args.len());
+        return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg));
+    }
+    Ok(InterpValue::InterpBoolean(matches!(args[0], InterpValue::InterpVoid)))
+}
+
+/// `build(builder)` is the string the builder holds.
+fn build(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    if args.len() != 1 {
+        let msg = format!("build expects 1 argument but was called with {}", args.len());
+        return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg));
+    }
+    match args[0] {
+        InterpValue::InterpBuilder(ref buffer) => { Ok(InterpValue::InterpString(buffer.borrow().clone())) }
+        ref other => {
+            let msg = format!("build expects a Builder but was given {}", with_article(other.type_name()));
+            Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+        }
+    }
+}
+
+pub fn interp(ast: Ast) -> Result<InterpValue, InterpError> {
+    let mut interp = Interp::new();
+    interp.run(&ast)
+}
@@ -0,0 +1,131 @@
+use interp::{InterpValue, InterpError};
+use persistent::{PersistentVec, PersistentMap};
+use collections::MapKey;
+use format::with_article;
+use error_codes;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+/// Where an iterator takes its values from. `next` advances it in place,
+/// so every copy of an iterator value sees the same position.
+#[derive(Debug)]
+pub enum IterSource {
+    List{list: PersistentVec<InterpValue>, index: usize},
+    /// Sees elements the array gains while it is iterated over.
+    Array{array: Rc<RefCell<Vec<InterpValue>>>, index: usize},
+    /// Iterates over the keys of the map.
+    Map{map: PersistentMap<MapKey, InterpValue>, index: usize},
+    /// Yields Ints when it starts from one, and Numbers otherwise.
+    Range{next: f64, end: f64, integers: bool},
+    /// A function called without arguments that returns `done, value`.
+    Function(InterpValue)
+}
+
+impl IterSource {
+    /// The next value of a source that does not call back into the
+    /// interpreter, or None when it is done. Function sources are advanced
+    /// by the interpreter itself.
+    pub fn advance(&mut self) -> Option<InterpValue> {
+        match *self {
+            IterSource::List{ref list, ref mut index} => {
+                let value = list.get(*index).cloned();
+                *index += 1;
+                value
+            }
+            IterSource::Array{ref array, ref mut index} => {
+                let value = array.borrow().get(*index).cloned();
+                *index += 1;
+                value
+            }
+            IterSource::Map{ref map, ref mut index} => {
+                let key = map.entry(*index).map(|entry| entry.0.to_value());
+                *index += 1;
+                key
+            }
+            IterSource::Range{ref mut next, end, integers} => {
+                if *next >= end {
+                    return None;
+                }
+                let value = *next;
+                *next += 1.0;
+                if integers {
+                    return Some(InterpValue::InterpInt(value as i64));
+                }
+                Some(InterpValue::InterpNumber(value))
+            }
+            IterSource::Function(_) => { None }
+        }
+    }
+}
+
+/// `iter(value)` returns an iterator over an array or list, the keys of a map, or the
+/// values a generator function returns. Iterators are returned as they are.
+pub fn iter(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    if args.len() != 1 {
+        let msg = format!("iter expects 1 argument but was called with {}", args.len());
+        return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg));
+    }
+    let source = match args.into_iter().next() {
+        Some(InterpValue::InterpList(list)) => { IterSource::List{list, index: 0} }
+        Some(InterpValue::InterpArray(array)) => { IterSource::Array{array, index: 0} }
+        Some(InterpValue::InterpMap(map)) => { IterSource::Map{map, index: 0} }
+        Some(function @ InterpValue::InterpFunction{..}) | Some(function @ InterpValue::InterpBoundFunction{..}) => {
+            IterSource::Function(function)
+        }
+        Some(iterator @ InterpValue::InterpIterator(_)) => { return Ok(iterator); }
+        Some(other) => {
+            let msg = format!("Can not iterate over {}", with_article(other.type_name()));
+            return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+        }
+        None => { return Ok(InterpValue::InterpVoid); }
+    };
+    Ok(InterpValue::InterpIterator(Rc::new(RefCell::new(source))))
+}
+
+/// `range(end)` or `range(start, end)` iterates over the numbers from start,
+/// or 0, up to but not including end.
+pub fn range(args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+    let mut bounds = Vec::with_capacity(args.len());
+    for arg in &args {
+        match arg.to_f64() {
+            Some(num) => { bounds.push(num); }
+            None => {
+                let msg = format!("range expects numbers but was given {}", with_article(arg.type_name()));
+                return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+            }
+        }
+    }
+    let source = match bounds.len() {
+        1 => { IterSource::Range{next: 0.0, end: bounds[0], integers: true} }
+        2 => {
+            let integers = args[0].is_integer();
+            IterSource::Range{next: bounds[0], end: bounds[1], integers}
+        }
+        count => {
+            let msg = format!("range expects 1 or 2 arguments but was called with {}", count);
+            return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg));
+        }
+    };
+    Ok(InterpValue::InterpIterator(Rc::new(RefCell::new(source))))
+}
+
+/// The `done, value` pair `next` returns.
+pub fn step(value: Option<InterpValue>) -> InterpValue {
+    let step = match value {
+        Some(value) => { vec![InterpValue::InterpBoolean(false), value] }
+        None => { vec![InterpValue::InterpBoolean(true), InterpValue::InterpVoid] }
+    };
+    InterpValue::InterpTuple(Rc::new(step))
+}
+
+/// Checks that a generator function returned a `done, value` pair.
+pub fn check_step(value: InterpValue) -> Result<InterpValue, InterpError> {
+    if let InterpValue::InterpTuple(ref values) = value {
+        if values.len() == 2 && matches!(values[0], InterpValue::InterpBoolean(_)) {
+            return Ok(value.clone());
+        }
+    }
+    let msg = format!("Iterator functions must return done, value but returned {}", with_article(value.type_name()));
+    Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+}
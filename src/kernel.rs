@@ -0,0 +1,439 @@
+use interp::{InterpValue, BUILTINS};
+use engine::Engine;
+use format;
+use output::SharedBuffer;
+use repl;
+use diagnostic::render_interp_error;
+
+use zmq;
+use serde_json::Value;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::process;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+const PROTOCOL_VERSION: &str = "5.3";
+
+struct ConnectionInfo {
+    transport: String,
+    ip: String,
+    key: String,
+    shell_port: u64,
+    iopub_port: u64,
+    stdin_port: u64,
+    control_port: u64,
+    hb_port: u64
+}
+
+impl ConnectionInfo {
+    fn read(path: &str) -> Result<ConnectionInfo, String> {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|error| format!("Failed to read connection file {}: {}", path, error))?;
+
+        let json: Value = serde_json::from_str(&contents)
+            .map_err(|error| format!("Invalid connection file {}: {}", path, error))?;
+
+        let text = |name: &str| -> Result<String, String> {
+            json[name].as_str().map(String::from).ok_or(format!("Connection file is missing {}", name))
+        };
+        let port = |name: &str| -> Result<u64, String> {
+            json[name].as_u64().ok_or(format!("Connection file is missing {}", name))
+        };
+
+        let scheme = json["signature_scheme"].as_str().unwrap_or("hmac-sha256");
+        if scheme != "hmac-sha256" {
+            return Err(format!("Unsupported signature scheme: {}", scheme));
+        }
+
+        Ok(ConnectionInfo {
+            transport: text("transport")?,
+            ip: text("ip")?,
+            key: text("key")?,
+            shell_port: port("shell_port")?,
+            iopub_port: port("iopub_port")?,
+            stdin_port: port("stdin_port")?,
+            control_port: port("control_port")?,
+            hb_port: port("hb_port")?
+        })
+    }
+
+    fn address(&self, port: u64) -> String {
+        match &self.transport[..] {
+            "ipc" => { format!("ipc://{}-{}", self.ip, port) }
+            _ => { format!("{}://{}:{}", self.transport, self.ip, port) }
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Channel {
+    Shell,
+    Control,
+    IoPub
+}
+
+struct Message {
+    identities: Vec<Vec<u8>>,
+    header: Value,
+    content: Value
+}
+
+impl Message {
+    fn msg_type(&self) -> &str {
+        self.header["msg_type"].as_str().unwrap_or("")
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Formats a unix timestamp as the ISO 8601 date the message header expects.
+fn iso_date(time: SystemTime) -> String {
+    let duration = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = duration.as_secs() as i64;
+    let (days, seconds_of_day) = (seconds / 86400, seconds % 86400);
+
+    // Civil date from days since 1970-01-01, after Howard Hinnant's algorithm
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z", year, month, day,
+                   seconds_of_day / 3600, seconds_of_day % 3600 / 60, seconds_of_day % 60,
+                   duration.subsec_micros())
+}
+
+fn display_data(value: &InterpValue) -> Value {
+    let text = format::format_debug(value.clone());
+    let json = match value {
+        &InterpValue::InterpNumber(num) => { Some(json!(num)) }
+        &InterpValue::InterpInt(num) => { Some(json!(num)) }
+        &InterpValue::InterpBoolean(val) => { Some(json!(val)) }
+        InterpValue::InterpString(val) => { Some(json!(val)) }
+        _ => { None }
+    };
+
+    let mut data = json!({ "text/plain": text });
+    if let Some(json) = json {
+        data["application/json"] = json;
+    }
+    data
+}
+
+struct Kernel {
+    key: Vec<u8>,
+    session: String,
+    message_count: usize,
+    execution_count: usize,
+
+    shell: zmq::Socket,
+    control: zmq::Socket,
+    iopub: zmq::Socket,
+    _stdin: zmq::Socket,
+
+    engine: Engine,
+    output: SharedBuffer
+}
+
+impl Kernel {
+    fn sign(&self, parts: &[&[u8]]) -> String {
+        if self.key.is_empty() {
+            return String::new();
+        }
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        for part in parts {
+            mac.update(part);
+        }
+        to_hex(&mac.finalize().into_bytes())
+    }
+
+    fn receive(&self, socket: &zmq::Socket) -> Result<Message, String> {
+        let frames = socket.recv_multipart(0).map_err(|error| error.to_string())?;
+
+        let delimiter = frames.iter().position(|frame| &frame[..] == DELIMITER)
+            .ok_or(String::from("Message without delimiter"))?;
+        if frames.len() < delimiter + 6 {
+            return Err(String::from("Message is missing parts"));
+        }
+
+        let parts: Vec<&[u8]> = frames[delimiter + 2..delimiter + 6].iter().map(|part| &part[..]).collect();
+        if self.sign(&parts).as_bytes() != &frames[delimiter + 1][..] {
+            return Err(String::from("Message with an invalid signature"));
+        }
+
+        let parse = |part: &[u8]| -> Result<Value, String> {
+            serde_json::from_slice(part).map_err(|error| error.to_string())
+        };
+        Ok(Message {
+            identities: frames[..delimiter].to_vec(),
+            header: parse(parts[0])?,
+            content: parse(parts[3])?
+        })
+    }
+
+    fn send(&mut self, channel: Channel, parent: &Message, msg_type: &str, content: Value) -> Result<(), String> {
+        self.message_count += 1;
+        let header = json!({
+            "msg_id": format!("{}-{}", self.session, self.message_count),
+            "session": self.session,
+            "username": "leg",
+            "date": iso_date(SystemTime::now()),
+            "msg_type": msg_type,
+            "version": PROTOCOL_VERSION
+        });
+
+        let header = header.to_string();
+        let parent_header = parent.header.to_string();
+        let metadata = String::from("{}");
+        let content = content.to_string();
+        let signature = self.sign(&[header.as_bytes(), parent_header.as_bytes(), metadata.as_bytes(), content.as_bytes()]);
+
+        let mut frames: Vec<Vec<u8>> = Vec::new();
+        if channel == Channel::IoPub {
+            frames.push(msg_type.as_bytes().to_vec());
+        } else {
+            frames.extend(parent.identities.iter().cloned());
+        }
+        frames.push(DELIMITER.to_vec());
+        frames.push(signature.into_bytes());
+        frames.push(header.into_bytes());
+        frames.push(parent_header.into_bytes());
+        frames.push(metadata.into_bytes());
+        frames.push(content.into_bytes());
+
+        let socket = match channel {
+            Channel::Shell => { &self.shell }
+            Channel::Control => { &self.control }
+            Channel::IoPub => { &self.iopub }
+        };
+        socket.send_multipart(frames, 0).map_err(|error| error.to_string())
+    }
+
+    fn publish_status(&mut self, parent: &Message, state: &str) -> Result<(), String> {
+        self.send(Channel::IoPub, parent, "status", json!({ "execution_state": state }))
+    }
+
+    fn kernel_info(&self) -> Value {
+        json!({
+            "status": "ok",
+            "protocol_version": PROTOCOL_VERSION,
+            "implementation": "leg",
+            "implementation_version": env!("CARGO_PKG_VERSION"),
+            "language_info": {
+                "name": "leg",
+                "version": env!("CARGO_PKG_VERSION"),
+                "mimetype": "text/x-leg",
+                "file_extension": ".leg"
+            },
+            "banner": "leg",
+            "help_links": []
+        })
+    }
+
+    fn execute(&mut self, request: &Message) -> Result<(), String> {
+        let code = String::from(request.content["code"].as_str().unwrap_or(""));
+        let silent = request.content["silent"].as_bool().unwrap_or(false);
+        if !silent {
+            self.execution_count += 1;
+        }
+        let execution_count = self.execution_count;
+
+        self.send(Channel::IoPub, request, "execute_input", json!({ "code": code, "execution_count": execution_count }))?;
+
+        let result = if code.trim().is_empty() {
+            Ok(InterpValue::InterpVoid)
+        } else {
+            match repl::parse_entry(&code) {
+                Ok(ast) => {
+                    self.engine.eval_ast(ast).map_err(|error| {
+                        (String::from("RuntimeError"), render_interp_error(&error, &code, repl::REPL_FILE_NAME))
+                    })
+                }
+                Err(error) => {
+                    Err((String::from("SyntaxError"), error))
+                }
+            }
+        };
+
+        let printed = self.output.take();
+        if !printed.is_empty() && !silent {
+            self.send(Channel::IoPub, request, "stream", json!({ "name": "stdout", "text": printed }))?;
+        }
+
+        match result {
+            Ok(value) => {
+                let has_value = !matches!(value, InterpValue::InterpVoid);
+                if has_value && !silent {
+                    let content = json!({ "execution_count": execution_count, "data": display_data(&value), "metadata": {} });
+                    self.send(Channel::IoPub, request, "execute_result", content)?;
+                }
+
+                let reply = json!({ "status": "ok", "execution_count": execution_count, "user_expressions": {}, "payload": [] });
+                self.send(Channel::Shell, request, "execute_reply", reply)
+            }
+            Err((name, value)) => {
+                let value = String::from(value.trim());
+                let error = json!({ "ename": name, "evalue": value, "traceback": [value] });
+                self.send(Channel::IoPub, request, "error", error.clone())?;
+
+                let mut reply = error;
+                reply["status"] = json!("error");
+                reply["execution_count"] = json!(execution_count);
+                self.send(Channel::Shell, request, "execute_reply", reply)
+            }
+        }
+    }
+
+    fn complete(&self, request: &Message) -> Value {
+        let code: Vec<char> = request.content["code"].as_str().unwrap_or("").chars().collect();
+        let cursor = (request.content["cursor_pos"].as_u64().unwrap_or(0) as usize).min(code.len());
+
+        let mut start = cursor;
+        while start > 0 && (code[start - 1].is_alphanumeric() || code[start - 1] == '_') {
+            start -= 1;
+        }
+        let prefix: String = code[start..cursor].iter().collect();
+
+        let mut names = self.engine.visible_names();
+        names.extend(BUILTINS.iter().map(|name| String::from(*name)));
+        let mut matches: Vec<String> = names.into_iter().filter(|name| name.starts_with(&prefix[..])).collect();
+        matches.sort();
+        matches.dedup();
+
+        json!({ "status": "ok", "matches": matches, "cursor_start": start, "cursor_end": cursor, "metadata": {} })
+    }
+
+    /// Handles one request and returns false once the kernel should shut down.
+    fn handle(&mut self, channel: Channel, request: Message) -> Result<bool, String> {
+        self.publish_status(&request, "busy")?;
+
+        let mut keep_running = true;
+        match request.msg_type() {
+            "kernel_info_request" => {
+                let info = self.kernel_info();
+                self.send(channel, &request, "kernel_info_reply", info)?;
+            }
+            "execute_request" => {
+                self.execute(&request)?;
+            }
+            "is_complete_request" => {
+                let code = request.content["code"].as_str().unwrap_or("");
+                let status = if repl::is_incomplete(code) { "incomplete" } else { "complete" };
+                self.send(channel, &request, "is_complete_reply", json!({ "status": status, "indent": "    " }))?;
+            }
+            "complete_request" => {
+                let reply = self.complete(&request);
+                self.send(channel, &request, "complete_reply", reply)?;
+            }
+            "interrupt_request" => {
+                self.send(channel, &request, "interrupt_reply", json!({ "status": "ok" }))?;
+            }
+            "shutdown_request" => {
+                let restart = request.content["restart"].as_bool().unwrap_or(false);
+                self.send(channel, &request, "shutdown_reply", json!({ "status": "ok", "restart": restart }))?;
+                keep_running = false;
+            }
+            other => {
+                eprintln!("Ignoring unsupported message type {}", other);
+            }
+        }
+
+        self.publish_status(&request, "idle")?;
+        Ok(keep_running)
+    }
+
+    fn run(&mut self) -> Result<(), String> {
+        loop {
+            let (control_ready, shell_ready) = {
+                let mut items = [self.control.as_poll_item(zmq::POLLIN), self.shell.as_poll_item(zmq::POLLIN)];
+                zmq::poll(&mut items, -1).map_err(|error| error.to_string())?;
+                (items[0].is_readable(), items[1].is_readable())
+            };
+
+            // Control messages such as shutdown take priority over queued cells
+            let (channel, received) = if control_ready {
+                (Channel::Control, self.receive(&self.control))
+            } else if shell_ready {
+                (Channel::Shell, self.receive(&self.shell))
+            } else {
+                continue;
+            };
+
+            match received {
+                Ok(request) => {
+                    if !self.handle(channel, request)? {
+                        return Ok(());
+                    }
+                }
+                Err(error) => {
+                    eprintln!("Dropping message: {}", error);
+                }
+            }
+        }
+    }
+}
+
+fn bind(context: &zmq::Context, socket_type: zmq::SocketType, address: &str) -> Result<zmq::Socket, String> {
+    let socket = context.socket(socket_type).map_err(|error| error.to_string())?;
+    socket.bind(address).map_err(|error| format!("Failed to bind {}: {}", address, error))?;
+    Ok(socket)
+}
+
+fn start(connection_file: &str) -> Result<(), String> {
+    let info = ConnectionInfo::read(connection_file)?;
+    let context = zmq::Context::new();
+
+    let heartbeat = bind(&context, zmq::REP, &info.address(info.hb_port))?;
+    thread::spawn(move || {
+        while let Ok(ping) = heartbeat.recv_bytes(0) {
+            if heartbeat.send(ping, 0).is_err() {
+                break;
+            }
+        }
+    });
+
+    let output = SharedBuffer::new();
+    let mut engine = Engine::new();
+    engine.set_output(output.clone());
+
+    let started = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut kernel = Kernel {
+        key: info.key.clone().into_bytes(),
+        session: format!("{:x}-{:x}", process::id(), started.as_nanos()),
+        message_count: 0,
+        execution_count: 0,
+
+        shell: bind(&context, zmq::ROUTER, &info.address(info.shell_port))?,
+        control: bind(&context, zmq::ROUTER, &info.address(info.control_port))?,
+        iopub: bind(&context, zmq::PUB, &info.address(info.iopub_port))?,
+        _stdin: bind(&context, zmq::ROUTER, &info.address(info.stdin_port))?,
+
+        engine,
+        output
+    };
+    kernel.run()
+}
+
+/// Runs a Jupyter kernel for the connection file the notebook server passes
+/// in, evaluating every cell against one interpreter for the whole session.
+pub fn run_kernel(connection_file: &str) {
+    if let Err(error) = start(connection_file) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
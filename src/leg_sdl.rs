@@ -1,16 +1,544 @@
-use interp::InterpValue;
-
-pub fn print(arguments: Vec<InterpValue>) {
-    use interp::InterpValue::*;
-    for val in arguments {
-        let string = match val {
-            InterpVoid => {String::from("VOID")}
-            InterpBoolean(val) => {format!("BOOLEAN {{{}}}", val)}
-            InterpNumber(num) => {num.to_string()}
-            InterpString(val) => {val}
-            InterpFunction{id, closure_id} => {format!("FUNCTION {}", id)}
-            InterpStruct(i) =>{format!("STRUCT {}", i)}
-        };
-        println!("{}", string);
-    }
-}
\ No newline at end of file
+//! Windows and graphics for scripts, backed by SDL2. The builtins exist in
+//! every build, so scripts using them still parse and resolve, but only a
+//! build with `--features sdl` can run them.
+
+use interp::{InterpValue, InterpError};
+use error_codes;
+#[cfg(feature = "sdl")]
+use collections;
+#[cfg(feature = "sdl")]
+use format::{with_article, counted};
+
+#[cfg(feature = "sdl")]
+use sdl2;
+#[cfg(feature = "sdl")]
+use sdl2::render::{Canvas, BlendMode, Texture};
+#[cfg(feature = "sdl")]
+use sdl2::image::LoadTexture;
+#[cfg(feature = "sdl")]
+use sdl2::video::Window;
+#[cfg(feature = "sdl")]
+use sdl2::pixels::Color;
+#[cfg(feature = "sdl")]
+use sdl2::rect::{Rect, Point};
+#[cfg(feature = "sdl")]
+use sdl2::event::{Event, WindowEvent};
+#[cfg(feature = "sdl")]
+use sdl2::EventPump;
+#[cfg(feature = "sdl")]
+use sdl2::keyboard::{Keycode, Scancode};
+#[cfg(feature = "sdl")]
+use sdl2::mouse::MouseButton;
+
+#[cfg(feature = "sdl")]
+use std::mem;
+#[cfg(feature = "sdl")]
+use std::rc::Rc;
+#[cfg(feature = "sdl")]
+use std::cell::RefCell;
+
+/// The builtins that open windows, draw into them and read their input.
+pub const GRAPHICS_BUILTINS: [&str; 14] = ["window_open", "window_close", "set_color", "clear", "present", "draw_rect",
+                                           "draw_line", "draw_circle", "poll_events", "key_down", "mouse_pos", "run_loop",
+                                           "load_image", "draw_image"];
+
+fn graphics_error(msg: String) -> InterpError {
+    InterpError::new(&error_codes::GRAPHICS_FAILED, msg)
+}
+
+#[cfg(not(feature = "sdl"))]
+fn unavailable(name: &str) -> InterpError {
+    let msg = format!("{} needs a build with SDL support, rebuild with --features sdl", name);
+    graphics_error(msg)
+}
+
+/// SDL itself, which only one of may exist at a time.
+#[cfg(feature = "sdl")]
+struct Context {
+    _sdl: sdl2::Sdl,
+    video: sdl2::VideoSubsystem,
+    events: EventPump
+}
+
+/// The SDL state of an interpreter. SDL is initialised by the first
+/// graphics builtin a script calls. Windows are referred to by the Int
+/// handle `window_open` returns, which is never reused.
+#[derive(Default)]
+pub struct Sdl {
+    #[cfg(feature = "sdl")]
+    context: Option<Context>,
+    #[cfg(feature = "sdl")]
+    windows: Vec<Option<Canvas<Window>>>,
+    /// The SDL id of each window, by handle, which stays known after the
+    /// window is closed.
+    #[cfg(feature = "sdl")]
+    window_ids: Vec<u32>,
+    /// The images loaded by handle, with the handle of the window they were
+    /// loaded for. They are freed with the window.
+    #[cfg(feature = "sdl")]
+    images: Vec<Option<(usize, Texture)>>,
+    /// What is drawn with, white until `set_color` is called.
+    #[cfg(feature = "sdl")]
+    color: Option<Color>,
+    /// Events `run_loop` has seen but `poll_events` has not returned yet.
+    #[cfg(feature = "sdl")]
+    pending: Vec<Event>
+}
+
+#[cfg(not(feature = "sdl"))]
+impl Sdl {
+    pub fn call(&mut self, name: &str, _args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        Err(unavailable(name))
+    }
+
+    pub fn frame(&mut self) -> Result<bool, InterpError> {
+        Err(unavailable("run_loop"))
+    }
+}
+
+#[cfg(feature = "sdl")]
+impl Sdl {
+    /// Runs the graphics builtin `name`.
+    pub fn call(&mut self, name: &str, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        match name {
+            "window_open" => { self.window_open(args) }
+            "window_close" => { self.window_close(args) }
+            "set_color" => { self.set_color(args) }
+            "clear" => {
+                arity(name, &args, 0, 0)?;
+                self.canvas(name)?.clear();
+                Ok(InterpValue::InterpVoid)
+            }
+            "present" => {
+                arity(name, &args, 0, 0)?;
+                self.canvas(name)?.present();
+                Ok(InterpValue::InterpVoid)
+            }
+            "draw_rect" => { self.draw_rect(args) }
+            "draw_line" => { self.draw_line(args) }
+            "draw_circle" => { self.draw_circle(args) }
+            "poll_events" => { self.poll_events(args) }
+            "load_image" => { self.load_image(args) }
+            "draw_image" => { self.draw_image(args) }
+            "key_down" => { self.key_down(args) }
+            "mouse_pos" => {
+                arity(name, &args, 0, 0)?;
+                let events = &mut self.context()?.events;
+                events.pump_events();
+                let mouse = events.mouse_state();
+                let position = vec![InterpValue::InterpInt(mouse.x() as i64), InterpValue::InterpInt(mouse.y() as i64)];
+                Ok(InterpValue::InterpTuple(Rc::new(position)))
+            }
+            _ => {
+                let msg = format!("{} is not a graphics builtin", name);
+                Err(InterpError::new(&error_codes::INTERNAL, msg))
+            }
+        }
+    }
+
+    /// Starts a frame of `run_loop`: closes the windows asked to close,
+    /// keeping their events for `poll_events`, and returns whether any
+    /// window is still open.
+    pub fn frame(&mut self) -> Result<bool, InterpError> {
+        let polled: Vec<Event> = self.context()?.events.poll_iter().collect();
+        let mut quit = false;
+        for event in &polled {
+            match *event {
+                Event::Quit { .. } => { quit = true; }
+                Event::Window { window_id, win_event: WindowEvent::Close, .. } => {
+                    if let Some(handle) = self.window_ids.iter().position(|&id| id == window_id) {
+                        self.close(handle);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.pending.extend(polled);
+        Ok(!quit && self.windows.iter().any(|window| window.is_some()))
+    }
+
+    fn context(&mut self) -> Result<&mut Context, InterpError> {
+        let context = match self.context.take() {
+            Some(context) => { context }
+            None => {
+                let context = sdl2::init().and_then(|sdl| {
+                    let video = sdl.video()?;
+                    let events = sdl.event_pump()?;
+                    Ok(Context { _sdl: sdl, video, events })
+                });
+                context.map_err(|error| graphics_error(format!("Failed to initialise SDL: {}", error)))?
+            }
+        };
+        Ok(self.context.insert(context))
+    }
+
+    /// `window_open(title, width, height)` opens a window and returns its
+    /// handle. Drawing goes to the window opened last that is still open.
+    fn window_open(&mut self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        if args.len() != 3 {
+            let msg = format!("window_open expects 3 arguments but was called with {}", args.len());
+            return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg));
+        }
+        let title = match args[0] {
+            InterpValue::InterpString(ref title) => { title.clone() }
+            ref other => {
+                let msg = format!("window_open expects a String title but was given {}", with_article(other.type_name()));
+                return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+            }
+        };
+        let width = size(&args[1])?;
+        let height = size(&args[2])?;
+
+        let window = self.context()?.video.window(&title, width, height).position_centered().build()
+            .map_err(|error| graphics_error(format!("Failed to open a window: {}", error)))?;
+        let mut canvas = window.into_canvas().present_vsync().build()
+            .map_err(|error| graphics_error(format!("Failed to open a window: {}", error)))?;
+        canvas.set_blend_mode(BlendMode::Blend);
+        self.window_ids.push(canvas.window().id());
+        self.windows.push(Some(canvas));
+        Ok(InterpValue::InterpInt(self.windows.len() as i64 - 1))
+    }
+
+    /// `window_close(window)` closes a window, and `window_close()` the
+    /// one drawing goes to. Closing a closed window does nothing.
+    fn window_close(&mut self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        let handle = match args.len() {
+            0 => { self.windows.iter().rposition(|window| window.is_some()) }
+            1 => { Some(self.handle(&args[0])?) }
+            count => {
+                let msg = format!("window_close expects 0 or 1 arguments but was called with {}", count);
+                return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg));
+            }
+        };
+        if let Some(handle) = handle {
+            self.close(handle);
+        }
+        Ok(InterpValue::InterpVoid)
+    }
+
+    /// Closes a window, freeing the images loaded for it.
+    fn close(&mut self, handle: usize) {
+        for image in self.images.iter_mut() {
+            if image.as_ref().map(|&(window, _)| window == handle).unwrap_or(false) {
+                *image = None;
+            }
+        }
+        self.windows[handle] = None;
+    }
+
+    /// The window drawing goes to, with the current color to draw with.
+    fn canvas(&mut self, name: &str) -> Result<&mut Canvas<Window>, InterpError> {
+        let color = self.color.unwrap_or(Color::WHITE);
+        match self.windows.iter_mut().rev().find_map(|window| window.as_mut()) {
+            Some(canvas) => {
+                canvas.set_draw_color(color);
+                Ok(canvas)
+            }
+            None => { Err(graphics_error(format!("{} needs an open window", name))) }
+        }
+    }
+
+    /// `set_color(r, g, b)` or `set_color(r, g, b, a)`, with each part from
+    /// 0 to 255, sets the color of everything drawn after it.
+    fn set_color(&mut self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        arity("set_color", &args, 3, 4)?;
+        let mut parts = [255; 4];
+        for (part, value) in parts.iter_mut().zip(numbers("set_color", &args)?) {
+            if !(0.0..=255.0).contains(&value) {
+                let msg = format!("Colors have parts from 0 to 255, found {}", value);
+                return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+            }
+            *part = value.round() as u8;
+        }
+        self.color = Some(Color::RGBA(parts[0], parts[1], parts[2], parts[3]));
+        Ok(InterpValue::InterpVoid)
+    }
+
+    /// `draw_rect(x, y, width, height)` draws the outline of a rectangle,
+    /// or fills it when given `true` after the height.
+    fn draw_rect(&mut self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        let (args, filled) = filled("draw_rect", args, 4)?;
+        let numbers = numbers("draw_rect", &args)?;
+        if numbers[2] < 0.0 || numbers[3] < 0.0 {
+            let msg = "draw_rect expects a positive width and height".to_string();
+            return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+        }
+        let rect = Rect::new(numbers[0] as i32, numbers[1] as i32, numbers[2] as u32, numbers[3] as u32);
+        let canvas = self.canvas("draw_rect")?;
+        let res = if filled { canvas.fill_rect(rect) } else { canvas.draw_rect(rect) };
+        drawn(res)
+    }
+
+    /// `draw_line(x1, y1, x2, y2)`
+    fn draw_line(&mut self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        arity("draw_line", &args, 4, 4)?;
+        let numbers = numbers("draw_line", &args)?;
+        let from = Point::new(numbers[0] as i32, numbers[1] as i32);
+        let to = Point::new(numbers[2] as i32, numbers[3] as i32);
+        let res = self.canvas("draw_line")?.draw_line(from, to);
+        drawn(res)
+    }
+
+    /// `draw_circle(x, y, radius)` draws the outline of a circle around
+    /// `x, y`, or fills it when given `true` after the radius.
+    fn draw_circle(&mut self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        let (args, filled) = filled("draw_circle", args, 3)?;
+        let numbers = numbers("draw_circle", &args)?;
+        let (x, y, radius) = (numbers[0] as i32, numbers[1] as i32, numbers[2] as i32);
+
+        // Walks one eighth of the circle and mirrors it, as in the midpoint
+        // circle algorithm.
+        let mut points = Vec::new();
+        let mut lines = Vec::new();
+        let (mut dx, mut dy, mut error) = (radius, 0, 1 - radius);
+        while dx >= dy {
+            for &(px, py) in &[(dx, dy), (dy, dx)] {
+                points.extend_from_slice(&[Point::new(x + px, y + py), Point::new(x - px, y + py),
+                                           Point::new(x + px, y - py), Point::new(x - px, y - py)]);
+                lines.push((Point::new(x - px, y + py), Point::new(x + px, y + py)));
+                lines.push((Point::new(x - px, y - py), Point::new(x + px, y - py)));
+            }
+            dy += 1;
+            if error < 0 {
+                error += 2 * dy + 1;
+            } else {
+                dx -= 1;
+                error += 2 * (dy - dx) + 1;
+            }
+        }
+
+        let canvas = self.canvas("draw_circle")?;
+        if filled {
+            for (from, to) in lines {
+                drawn(canvas.draw_line(from, to))?;
+            }
+            return Ok(InterpValue::InterpVoid);
+        }
+        drawn(canvas.draw_points(&points[..]))
+    }
+
+    /// `poll_events()` is an Array of the input since it was last called,
+    /// each an imap with a `"type"`:
+    ///
+    /// - `"quit"` when the last window is closed.
+    /// - `"window_close"` with the `"window"` handle, when it is asked to close.
+    /// - `"key_down"` and `"key_up"` with the name of the `"key"`, like `"W"`
+    ///   or `"Space"`.
+    /// - `"mouse_move"` with the `"x"` and `"y"` of the mouse.
+    /// - `"mouse_down"` and `"mouse_up"` with `"x"`, `"y"` and the
+    ///   `"button"`: `"left"`, `"middle"` or `"right"`.
+    fn poll_events(&mut self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        arity("poll_events", &args, 0, 0)?;
+        let polled: Vec<Event> = self.context()?.events.poll_iter().collect();
+        let mut pending = mem::take(&mut self.pending);
+        pending.extend(polled);
+
+        let mut events = Vec::new();
+        for event in pending {
+            let (kind, fields) = match event {
+                Event::Quit { .. } => { ("quit", vec![]) }
+                Event::Window { window_id, win_event: WindowEvent::Close, .. } => {
+                    match self.window_ids.iter().position(|&id| id == window_id) {
+                        Some(handle) => { ("window_close", vec![("window", InterpValue::InterpInt(handle as i64))]) }
+                        None => { continue; }
+                    }
+                }
+                Event::KeyDown { keycode: Some(keycode), .. } => {
+                    ("key_down", vec![("key", InterpValue::InterpString(keycode.name()))])
+                }
+                Event::KeyUp { keycode: Some(keycode), .. } => {
+                    ("key_up", vec![("key", InterpValue::InterpString(keycode.name()))])
+                }
+                Event::MouseMotion { x, y, .. } => {
+                    ("mouse_move", vec![("x", InterpValue::InterpInt(x as i64)), ("y", InterpValue::InterpInt(y as i64))])
+                }
+                Event::MouseButtonDown { mouse_btn, x, y, .. } | Event::MouseButtonUp { mouse_btn, x, y, .. } => {
+                    let kind = if matches!(event, Event::MouseButtonDown { .. }) { "mouse_down" } else { "mouse_up" };
+                    let button = match mouse_btn {
+                        MouseButton::Left => { "left" }
+                        MouseButton::Middle => { "middle" }
+                        MouseButton::Right => { "right" }
+                        _ => { continue; }
+                    };
+                    (kind, vec![("button", InterpValue::InterpString(String::from(button))),
+                                ("x", InterpValue::InterpInt(x as i64)), ("y", InterpValue::InterpInt(y as i64))])
+                }
+                _ => { continue; }
+            };
+
+            let mut pairs = vec![InterpValue::InterpString(String::from("type")), InterpValue::InterpString(String::from(kind))];
+            for (key, value) in fields {
+                pairs.push(InterpValue::InterpString(String::from(key)));
+                pairs.push(value);
+            }
+            events.push(collections::imap(pairs)?);
+        }
+        Ok(InterpValue::InterpArray(Rc::new(RefCell::new(events))))
+    }
+
+    /// `key_down(name)` is whether the key is held down right now, named as
+    /// in the `"key"` of key events.
+    fn key_down(&mut self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        arity("key_down", &args, 1, 1)?;
+        let scancode = match args[0] {
+            InterpValue::InterpString(ref name) => {
+                match Keycode::from_name(name).and_then(Scancode::from_keycode) {
+                    Some(scancode) => { scancode }
+                    None => {
+                        let msg = format!("There is no key named {:?}", name);
+                        return Err(graphics_error(msg));
+                    }
+                }
+            }
+            ref other => {
+                let msg = format!("key_down expects the name of a key but was given {}", with_article(other.type_name()));
+                return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+            }
+        };
+        let events = &mut self.context()?.events;
+        events.pump_events();
+        Ok(InterpValue::InterpBoolean(events.keyboard_state().is_scancode_pressed(scancode)))
+    }
+
+    /// `load_image(path)` loads a PNG, JPEG or BMP file for the window
+    /// drawing goes to, and returns its handle. It stays loaded until the
+    /// window is closed.
+    fn load_image(&mut self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        arity("load_image", &args, 1, 1)?;
+        let path = match args[0] {
+            InterpValue::InterpString(ref path) => { path.clone() }
+            ref other => {
+                let msg = format!("load_image expects a String path but was given {}", with_article(other.type_name()));
+                return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+            }
+        };
+        let window = match self.windows.iter().rposition(|window| window.is_some()) {
+            Some(window) => { window }
+            None => { return Err(graphics_error("load_image needs an open window".to_string())); }
+        };
+        let texture = self.canvas("load_image")?.texture_creator().load_texture(&path)
+            .map_err(|error| graphics_error(format!("Failed to load the image {}: {}", path, error)))?;
+        self.images.push(Some((window, texture)));
+        Ok(InterpValue::InterpInt(self.images.len() as i64 - 1))
+    }
+
+    /// `draw_image(image, x, y)` draws an image with its top left corner at
+    /// `x, y`, into the window it was loaded for. It takes an optional scale
+    /// after `y`, and a rotation in degrees around its center after that.
+    fn draw_image(&mut self, args: Vec<InterpValue>) -> Result<InterpValue, InterpError> {
+        arity("draw_image", &args, 3, 5)?;
+        let image = match args[0] {
+            InterpValue::InterpInt(image) if image >= 0 && (image as usize) < self.images.len() => { image as usize }
+            InterpValue::InterpInt(image) => {
+                return Err(graphics_error(format!("No image has the handle {}", image)));
+            }
+            ref other => {
+                let msg = format!("Images are referred to by an Int handle, found {}", with_article(other.type_name()));
+                return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+            }
+        };
+        let numbers = numbers("draw_image", &args[1..])?;
+        let scale = numbers.get(2).cloned().unwrap_or(1.0);
+        let rotation = numbers.get(3).cloned().unwrap_or(0.0);
+        if scale < 0.0 {
+            let msg = format!("draw_image expects a positive scale, found {}", scale);
+            return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
+        }
+
+        let (window, texture) = match self.images[image] {
+            Some((window, ref texture)) => { (window, texture) }
+            None => {
+                let msg = format!("The image {} was freed when its window closed", image);
+                return Err(graphics_error(msg));
+            }
+        };
+        let size = texture.query();
+        let rect = Rect::new(numbers[0] as i32, numbers[1] as i32, (size.width as f64 * scale) as u32,
+                             (size.height as f64 * scale) as u32);
+        match self.windows[window] {
+            Some(ref mut canvas) => { drawn(canvas.copy_ex(texture, None, rect, rotation, None, false, false)) }
+            None => { Err(graphics_error(format!("The window of image {} is closed", image))) }
+        }
+    }
+
+    fn handle(&self, value: &InterpValue) -> Result<usize, InterpError> {
+        match value {
+            &InterpValue::InterpInt(handle) if handle >= 0 && (handle as usize) < self.windows.len() => {
+                Ok(handle as usize)
+            }
+            &InterpValue::InterpInt(handle) => {
+                Err(graphics_error(format!("No window has the handle {}", handle)))
+            }
+            other => {
+                let msg = format!("Windows are referred to by an Int handle, found {}", with_article(other.type_name()));
+                Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sdl")]
+fn drawn(res: Result<(), String>) -> Result<InterpValue, InterpError> {
+    match res {
+        Ok(()) => { Ok(InterpValue::InterpVoid) }
+        Err(error) => { Err(graphics_error(format!("Failed to draw: {}", error))) }
+    }
+}
+
+#[cfg(feature = "sdl")]
+fn arity(name: &str, args: &[InterpValue], min: usize, max: usize) -> Result<(), InterpError> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max { counted(min, "argument") } else { format!("{} to {} arguments", min, max) };
+        let msg = format!("{} expects {} but was called with {}", name, expected, args.len());
+        return Err(InterpError::new(&error_codes::ARITY_MISMATCH, msg));
+    }
+    Ok(())
+}
+
+/// Splits the optional Boolean after the `count` numbers of a shape, which
+/// fills it when `true`.
+#[cfg(feature = "sdl")]
+fn filled(name: &str, mut args: Vec<InterpValue>, count: usize) -> Result<(Vec<InterpValue>, bool), InterpError> {
+    arity(name, &args, count, count + 1)?;
+    if args.len() == count {
+        return Ok((args, false));
+    }
+    match args.pop() {
+        Some(InterpValue::InterpBoolean(filled)) => { Ok((args, filled)) }
+        other => {
+            let found = other.map_or_else(|| String::from("nothing"), |value| with_article(value.type_name()));
+            let msg = format!("{} expects a Boolean after the {} numbers but was given {}", name, count, found);
+            Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+        }
+    }
+}
+
+/// Positions, sizes and colors, which may be any kind of number.
+#[cfg(feature = "sdl")]
+fn numbers(name: &str, args: &[InterpValue]) -> Result<Vec<f64>, InterpError> {
+    args.iter().map(|arg| {
+        match arg.to_f64() {
+            Some(num) if num.is_finite() => { Ok(num) }
+            _ => {
+                let msg = format!("{} expects numbers but was given {}", name, with_article(arg.type_name()));
+                Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+            }
+        }
+    }).collect()
+}
+
+/// A width or height in pixels.
+#[cfg(feature = "sdl")]
+fn size(value: &InterpValue) -> Result<u32, InterpError> {
+    match value.to_f64() {
+        Some(num) if value.is_integer() && num > 0.0 && num <= u32::MAX as f64 => { Ok(num as u32) }
+        Some(num) if value.is_integer() => {
+            let msg = format!("Window sizes must be positive, found {}", num);
+            Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+        }
+        _ => {
+            let msg = format!("Window sizes must be Ints, found {}", with_article(value.type_name()));
+            Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! The leg language as a library: tokenize a script, parse the tokens into
+//! an `Ast` and run it, from any Rust project. The `lang1` binary is a thin
+//! command line wrapper around the same modules.
+//!
+//! ```ignore
+//! let ast = lang1::parse(lang1::Tokenizer::new("1 + 2;"))?;
+//! let value = lang1::interp(ast)?;
+//! ```
+
+extern crate rustyline;
+extern crate stacker;
+#[cfg(feature = "jupyter")]
+extern crate zmq;
+#[cfg(feature = "jupyter")]
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature = "jupyter")]
+extern crate hmac;
+#[cfg(feature = "jupyter")]
+extern crate sha2;
+#[cfg(feature = "bignum")]
+extern crate bigdecimal;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "sdl")]
+extern crate sdl2;
+
+pub mod tokenizer;
+pub mod file_info;
+pub mod parser;
+pub mod interp;
+pub mod error;
+pub mod error_codes;
+pub mod diagnostic;
+pub mod visitor;
+pub mod resolver;
+pub mod lint;
+pub mod typecheck;
+pub mod analyze;
+pub mod semantic_tokens;
+pub mod pragma;
+pub mod run;
+pub mod engine;
+pub mod convert;
+pub mod trace;
+pub mod coverage;
+pub mod profiler;
+pub mod replay;
+pub mod repl;
+pub mod output;
+#[cfg(feature = "jupyter")]
+pub mod kernel;
+#[cfg(feature = "serde")]
+mod serialize;
+
+mod operators;
+mod leg_sdl;
+mod format;
+mod suggest;
+mod hot_reload;
+mod random;
+mod persistent;
+mod collections;
+mod iterator;
+mod gc;
+
+pub use tokenizer::{tokenize, Tokenizer, Token, TokenType, TokenizationError};
+pub use parser::{parse, Ast, AstNodeType, AstStatement, AstBlock, ParsingError};
+pub use interp::{interp, Interp, InterpOptions, InterpValue, InterpError, TraceFrame};
+pub use file_info::CodePoint;
+pub use engine::Engine;
+pub use output::SharedBuffer;
+pub use convert::ConversionError;
+pub use error::LegError;
+pub use error_codes::ErrorCode;
+pub use diagnostic::{Diagnostic, Severity};
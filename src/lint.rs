@@ -0,0 +1,187 @@
+use parser::{Ast, AstNodeType, AstBlock, AstStatement, AstFunctionCall, AstFunctionDeclaration, AstVariable, AstAssignment,
+             AstAlias, AstDestructure};
+use visitor::{Visitor, walk_block, walk_function_call};
+use diagnostic::{Diagnostic, Severity};
+use file_info::CodePoint;
+use error_codes;
+use error_codes::ErrorCode;
+
+/// A name declared in a scope, and whether anything reads it.
+struct Binding {
+    name: String,
+    file_info: CodePoint,
+    used: bool
+}
+
+/// Finds code that runs, but probably not the way it was meant to: names
+/// that are never read, statements that can never run and `let`s hiding a
+/// name of an enclosing scope. Scopes see every name declared in them, as in
+/// the resolver.
+struct Linter {
+    scopes: Vec<Vec<Binding>>,
+    statement: Option<CodePoint>,
+    diagnostics: Vec<Diagnostic>
+}
+
+impl Linter {
+    fn lookup(&mut self, name: &str) -> Option<&mut Binding> {
+        self.scopes.iter_mut().rev().filter_map(|scope| scope.iter_mut().find(|binding| binding.name == name)).next()
+    }
+
+    fn warn(&mut self, code: &'static ErrorCode, message: String, file_info: CodePoint) {
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            code,
+            message,
+            file_info
+        });
+    }
+
+    /// Adds `name` to the scope being built, unless it already declares it.
+    /// Names that do not always declare, like those of a plain assignment,
+    /// refer to the visible name when there is one.
+    fn bind(&mut self, scope: &mut Vec<Binding>, name: &str, file_info: &CodePoint, declares: bool) {
+        let in_scope = scope.iter().any(|binding| binding.name == name);
+        let outer = self.lookup(name).is_some();
+        if !in_scope && (declares || !outer) {
+            scope.push(Binding { name: String::from(name), file_info: file_info.clone(), used: false });
+        }
+    }
+
+    /// Binds the names the statements declare in the scope they run in. A
+    /// plain assignment only declares a name no enclosing scope has yet.
+    fn declare(&mut self, scope: &mut Vec<Binding>, statements: &[AstStatement]) {
+        for statement in statements {
+            match statement.node {
+                AstNodeType::Assignment(ref assignment) => {
+                    let name = &assignment.to.name;
+                    let visible = scope.iter().any(|binding| binding.name == *name) || self.lookup(name).is_some();
+                    if assignment.declares && visible {
+                        let msg = format!("let {} shadows the {} declared before it", name, name);
+                        self.warn(&error_codes::SHADOWED_NAME, msg, statement.file_info.clone());
+                    }
+                    self.bind(scope, name, &statement.file_info, assignment.declares);
+                }
+                AstNodeType::Alias(ref alias) => {
+                    self.bind(scope, &alias.to.name, &statement.file_info, true);
+                }
+                AstNodeType::Destructure(ref destructure) => {
+                    for variable in &destructure.to {
+                        self.bind(scope, &variable.name, &statement.file_info, destructure.declares);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Statements after a `return`, `break` or `continue` of the same block
+    /// are reported once, at the first of them.
+    fn check_reachable(&mut self, statements: &[AstStatement]) {
+        let exit = statements.iter().position(|statement| {
+            matches!(statement.node, AstNodeType::Return(_) | AstNodeType::Break(_) | AstNodeType::Continue(_))
+        });
+        if let Some(unreachable) = exit.and_then(|index| statements.get(index + 1)) {
+            let keyword = match statements[exit.unwrap_or(0)].node {
+                AstNodeType::Return(_) => { "return" }
+                AstNodeType::Break(_) => { "break" }
+                _ => { "continue" }
+            };
+            let msg = format!("This statement can never run, it follows a {}", keyword);
+            self.warn(&error_codes::UNREACHABLE_CODE, msg, unreachable.file_info.clone());
+        }
+    }
+
+    fn visit_scope(&mut self, mut scope: Vec<Binding>, block: &AstBlock) {
+        self.declare(&mut scope, &block.statements);
+        self.check_reachable(&block.statements);
+        self.scopes.push(scope);
+        walk_block(self, block);
+
+        let scope = self.scopes.pop().unwrap_or_default();
+        for binding in scope {
+            if !binding.used && !binding.name.starts_with('_') {
+                let msg = format!("{} is never used, prefix it with _ if that is intended", binding.name);
+                self.warn(&error_codes::UNUSED_BINDING, msg, binding.file_info);
+            }
+        }
+    }
+
+    fn mark_used(&mut self, name: &str) {
+        if let Some(binding) = self.lookup(name) {
+            binding.used = true;
+        }
+    }
+
+    /// A binding declared by the statement being visited.
+    fn binding(&self, name: &str) -> Option<Binding> {
+        self.statement.as_ref().map(|file_info| Binding { name: String::from(name), file_info: file_info.clone(), used: false })
+    }
+}
+
+impl Visitor for Linter {
+    fn visit_statement(&mut self, statement: &AstStatement) {
+        let outer = self.statement.replace(statement.file_info.clone());
+        self.visit_node(&statement.node);
+        self.statement = outer;
+    }
+
+    fn visit_block(&mut self, block: &AstBlock) {
+        self.visit_scope(Vec::new(), block);
+    }
+
+    fn visit_function_declaration(&mut self, dec: &AstFunctionDeclaration) {
+        let parameters = dec.arguments.iter().filter_map(|argument| {
+            match argument {
+                AstNodeType::Variable(variable) => { self.binding(&variable.name) }
+                _ => { None }
+            }
+        }).collect();
+        self.visit_scope(parameters, &dec.body);
+    }
+
+    /// The first argument of a `for` is the loop variable, declared in the
+    /// scope of the body.
+    fn visit_function_call(&mut self, call: &AstFunctionCall) {
+        if call.name == "for" {
+            if let (Some(AstNodeType::Variable(variable)), Some(body)) = (call.arguments.first(), call.body.as_ref()) {
+                for argument in &call.arguments[1..] {
+                    self.visit_node(argument);
+                }
+                let variable = self.binding(&variable.name).into_iter().collect();
+                self.visit_scope(variable, body);
+                return;
+            }
+        }
+        self.mark_used(&call.name);
+        walk_function_call(self, call);
+    }
+
+    fn visit_assignment(&mut self, assignment: &AstAssignment) {
+        self.visit_node(&assignment.from);
+    }
+
+    fn visit_alias(&mut self, alias: &AstAlias) {
+        self.visit_node(&alias.from);
+    }
+
+    fn visit_destructure(&mut self, destructure: &AstDestructure) {
+        self.visit_node(&destructure.from);
+    }
+
+    fn visit_variable(&mut self, variable: &AstVariable) {
+        self.mark_used(&variable.name);
+    }
+}
+
+/// Reports the warnings for a script, sorted by position.
+pub fn lint(ast: &Ast) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        scopes: Vec::new(),
+        statement: None,
+        diagnostics: Vec::new()
+    };
+    linter.visit_node(&ast.root);
+    linter.diagnostics.sort_by_key(|diagnostic| (diagnostic.file_info.line_number_from, diagnostic.file_info.column_number_from));
+    linter.diagnostics
+}
@@ -1,27 +1,66 @@
-mod tokenizer;
-mod file_info;
-mod parser;
-mod interp;
-mod operators;
-mod leg_sdl;
+extern crate lang1;
 
-use tokenizer::tokenize;
-use parser::parse;
-use interp::interp;
+use lang1::{tokenizer, diagnostic, error_codes, analyze, repl, resolver, lint, typecheck, replay};
+use lang1::tokenizer::{tokenize, tokenize_recovering, Tokenizer, Token, TokenType, TokenizationError};
+use lang1::parser::parse;
+use lang1::interp::{Interp, InterpOptions, InterpError};
+use lang1::semantic_tokens::classify;
+use lang1::trace::Tracer;
+use lang1::coverage::Coverage;
+use lang1::profiler::Profiler;
+use lang1::pragma::read_pragmas;
+use lang1::diagnostic::{Diagnostic, Severity};
+#[cfg(feature = "jupyter")]
+use lang1::kernel;
 
 use std::fs::File;
 use std::io::BufReader;
 use std::io::prelude::*;
 
 use std::io;
+use std::env;
 use std::fs;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use std::rc::Rc;
+use std::cell::RefCell;
 
-fn read_script_from_file() -> Result<String, io::Error> {
-    let srcdir = PathBuf::from("./hello_world.leg");
-    println!("{:?}", fs::canonicalize(&srcdir));
+const FLAGS: &str = "  --repl                    start an interactive session instead
+  --check-all               report every problem found without running
+  --strict                  run in the strict dialect, type checked and
+                            with warnings as errors
+  --typecheck               check values against :Type annotations before running
+  --allow=<code>[,<code>]   do not report the warnings with these codes
+  --no-warnings             do not report any warnings
+  --error-format=json       print errors and warnings as JSON lines
+  --strict-overflow         make arithmetic that overflows an error
+  --strict-division         make dividing by zero and NaN results errors
+  --stack-size=<frames>     how deep calls may nest, 10 by default
+  --max-steps=<steps>       stop the script after evaluating this many nodes
+  --timeout=<seconds>       stop the script after running this long
+  --max-memory=<bytes>      stop the script when its values take up more memory
+  --trace                   print every statement as it runs
+  --coverage[=lcov]         report which lines ran
+  --profile[=folded]        report where time was spent
+  --record=<log>            record random results to a log
+  --replay=<log>            take random results from a log
+  --dump-tokens             print the tokens before running
+  --dump-ast                print the syntax tree before running
+  --dump-semantic-tokens    print the classified tokens and exit";
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} [flags] <script>", program);
+    eprintln!("       {} explain <error code>", program);
+    eprintln!("       {} analyze <script>", program);
+    eprintln!("       {} kernel <connection file>", program);
+    eprintln!("\nFlags:\n{}", FLAGS);
+}
+
+fn read_script_from_file(path: &str) -> Result<String, io::Error> {
+    let srcdir = PathBuf::from(path);
+    let file = File::open(&srcdir)?;
 
-    let file = File::open(srcdir)?;
     let mut buf_reader = BufReader::new(file);
     let mut contents = String::new();
     buf_reader.read_to_string(&mut contents)?;
@@ -29,57 +68,395 @@ fn read_script_from_file() -> Result<String, io::Error> {
     Ok(contents)
 }
 
+/// One token per line, with its position, type and text.
+fn dump_tokens(tokens: &[tokenizer::Token]) {
+    for token in tokens {
+        let position = token.get_file_info();
+        println!("{}:{} {:?} {:?}", position.line_number_from, position.column_number_from, token.get_type(), token.get_text());
+    }
+}
+
+fn dump_semantic_tokens(tokens: &[tokenizer::Token]) {
+    let ast = parse(tokens.iter().cloned().map(Ok::<Token, TokenizationError>)).ok();
+    for token in classify(tokens, ast.as_ref()) {
+        let position = &token.file_info;
+        println!("{}:{} {} {}", position.line_number_from, position.column_number_from, token.token_type.legend_name(), token.text);
+    }
+}
+
+#[cfg(feature = "jupyter")]
+fn run_kernel(connection_file: &str) {
+    kernel::run_kernel(connection_file);
+}
+
+#[cfg(not(feature = "jupyter"))]
+fn run_kernel(_connection_file: &str) {
+    eprintln!("This build has no Jupyter support, rebuild with --features jupyter");
+    std::process::exit(1);
+}
+
+/// Writes `lcov.info` for the lcov format, anything else annotates the source on stderr.
+/// Returns whether the report was written.
+fn write_coverage(coverage: &Coverage, format: &str, script_path: &str, script: &str) -> bool {
+    if format == "lcov" {
+        let report = coverage.lcov(script_path);
+        if let Err(error) = fs::write("lcov.info", report) {
+            eprintln!("Failed to write lcov.info: {}", error);
+            return false;
+        }
+    } else {
+        eprint!("{}", coverage.annotate(script));
+    }
+    true
+}
+
+/// Writes `profile.folded` for the folded format, anything else prints the timing report on stderr.
+/// Returns whether the report was written.
+fn write_profile(profiler: &Profiler, format: &str) -> bool {
+    if format == "folded" {
+        if let Err(error) = fs::write("profile.folded", profiler.folded_stacks()) {
+            eprintln!("Failed to write profile.folded: {}", error);
+            return false;
+        }
+    } else {
+        eprint!("{}", profiler.timing_report());
+    }
+    true
+}
+
+/// The warning codes given with `--allow`, or `all` with `--no-warnings`.
+fn allowed_warnings() -> Vec<String> {
+    let mut allowed = Vec::new();
+    for arg in env::args() {
+        if arg == "--no-warnings" {
+            allowed.push(String::from("all"));
+        } else if arg.starts_with("--allow=") {
+            allowed.extend(arg.trim_start_matches("--allow=").split(',').map(String::from));
+        }
+    }
+    allowed
+}
+
+/// Whether `--error-format=json` asks for diagnostics as JSON lines rather
+/// than for people to read.
+fn json_error_format() -> bool {
+    let value = env::args().find(|arg| arg.starts_with("--error-format="))
+        .map(|arg| String::from(arg.trim_start_matches("--error-format=")));
+    match value.as_deref() {
+        None | Some("human") => { false }
+        Some("json") => { true }
+        Some(other) => {
+            eprintln!("--error-format expects human or json, found {}", other);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `diagnostic` the way `--error-format` asks for it, ending with a newline.
+fn format_diagnostic(diagnostic: &Diagnostic, json: bool, script: &str, script_path: &str) -> String {
+    if json {
+        return format!("{}\n", diagnostic.to_json(script_path));
+    }
+    diagnostic.render(script, script_path)
+}
+
+/// Interpreter errors keep their stack trace when printed for people.
+fn print_interp_error(error: &InterpError, json: bool, script: &str, script_path: &str) {
+    if json {
+        eprintln!("{}", Diagnostic::from(error).to_json(script_path));
+    } else {
+        eprint!("{}", diagnostic::render_interp_error(error, script, script_path));
+    }
+}
+
+/// The value of `--<name>=<value>`, exiting when it is not a number of
+/// `what`.
+fn number_flag<T: FromStr>(name: &str, what: &str) -> Option<T> {
+    let prefix = format!("--{}=", name);
+    let value = env::args().find(|arg| arg.starts_with(&prefix))
+        .map(|arg| String::from(arg.trim_start_matches(&prefix[..])));
+    match value {
+        Some(value) => {
+            match value.parse::<T>() {
+                Ok(number) => { Some(number) }
+                Err(_) => {
+                    eprintln!("--{} expects a number of {}, found {}", name, what, value);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => { None }
+    }
+}
+
+fn timeout_flag() -> Option<Duration> {
+    number_flag::<f64>("timeout", "seconds").map(|seconds| {
+        match Duration::try_from_secs_f64(seconds) {
+            Ok(timeout) => { timeout }
+            Err(_) => {
+                eprintln!("--timeout expects a positive number of seconds, found {}", seconds);
+                std::process::exit(1);
+            }
+        }
+    })
+}
+
 fn main() {
-    match read_script_from_file() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() > 1 && args[1] == "kernel" {
+        match args.get(2) {
+            Some(connection_file) => { run_kernel(connection_file); }
+            None => {
+                eprintln!("Usage: {} kernel <connection file>", args[0]);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if args.len() > 1 && args[1] == "explain" {
+        match args.get(2) {
+            Some(code) => {
+                match error_codes::explain(code) {
+                    Some(explanation) => { print!("{}", explanation); }
+                    None => {
+                        eprintln!("Unknown error code {}", code);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            None => {
+                eprintln!("Usage: {} explain <error code>", args[0]);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if args.len() > 1 && args[1] == "analyze" {
+        match args.get(2) {
+            Some(path) => {
+                match fs::read_to_string(path) {
+                    Ok(source) => { print!("{}", analyze::analyze(&source).report()); }
+                    Err(error) => {
+                        eprintln!("Failed to read script: {}", error);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            None => {
+                eprintln!("Usage: {} analyze <script>", args[0]);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if env::args().any(|arg| arg == "--repl") {
+        repl::run_repl();
+        return;
+    }
+
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print_usage(&args[0]);
+        return;
+    }
+
+    let script_path = match args.iter().skip(1).find(|arg| !arg.starts_with("--")) {
+        Some(path) => { path.clone() }
+        None => {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+    };
+
+    let semantic_tokens_only = env::args().any(|arg| arg == "--dump-semantic-tokens");
+    let dump_tokens_enabled = env::args().any(|arg| arg == "--dump-tokens");
+    let dump_ast = env::args().any(|arg| arg == "--dump-ast");
+    let check_all = env::args().any(|arg| arg == "--check-all");
+    let trace = env::args().any(|arg| arg == "--trace");
+    let strict_overflow = env::args().any(|arg| arg == "--strict-overflow");
+    let strict_division = env::args().any(|arg| arg == "--strict-division");
+    let strict = env::args().any(|arg| arg == "--strict");
+    let typecheck = env::args().any(|arg| arg == "--typecheck");
+    let allowed = allowed_warnings();
+    let json_errors = json_error_format();
+    let coverage_format = env::args().find(|arg| arg == "--coverage" || arg.starts_with("--coverage="))
+        .map(|arg| String::from(arg.trim_start_matches("--coverage").trim_start_matches('=')));
+    let record_path = env::args().find(|arg| arg.starts_with("--record="))
+        .map(|arg| String::from(arg.trim_start_matches("--record=")));
+    let replay_path = env::args().find(|arg| arg.starts_with("--replay="))
+        .map(|arg| String::from(arg.trim_start_matches("--replay=")));
+    let stack_size = number_flag::<usize>("stack-size", "frames");
+    let max_steps = number_flag::<usize>("max-steps", "steps");
+    let timeout = timeout_flag();
+    let max_memory = number_flag::<usize>("max-memory", "bytes");
+    let profile_format = env::args().find(|arg| arg == "--profile" || arg.starts_with("--profile="))
+        .map(|arg| String::from(arg.trim_start_matches("--profile").trim_start_matches('=')));
+
+    match read_script_from_file(&script_path) {
         Ok(contents) => {
             let script = & contents[..];
 
-            match tokenize(script) {
-                Ok(tokens) => {
-                    println !("{:?}", tokens);
+            if check_all {
+                let mut diagnostics = diagnostic::check_all(script);
+                diagnostics.retain(|diagnostic| !diagnostic.is_allowed(&allowed));
+                for diagnostic in &diagnostics {
+                    eprint!("{}", format_diagnostic(diagnostic, json_errors, script, &script_path));
+                }
+                if diagnostic::has_errors(&diagnostics) {
+                    std::process::exit(1);
+                }
+                return;
+            }
+
+            if semantic_tokens_only {
+                match tokenize(script) {
+                    Ok(tokens) => { dump_semantic_tokens(&tokens); }
+                    Err(error) => {
+                        eprint!("{}", format_diagnostic(&Diagnostic::from(&error), json_errors, script, &script_path));
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+
+            if dump_tokens_enabled {
+                dump_tokens(&tokenize_recovering(script).0);
+            }
+
+            // The parser reads the tokens as they are made, keeping only the
+            // comments for the pragmas in them
+            let mut comments = Vec::new();
+            let parsed = parse(Tokenizer::new(script).inspect(|token| {
+                if let Ok(token) = token {
+                    if token.get_type() == TokenType::Comment {
+                        comments.push(token.clone());
+                    }
+                }
+            }));
+            match parsed {
+                Ok(ast) => {
+                    if dump_ast {
+                        println!("{:#?}", ast);
+                    }
+
+                    let pragmas = match read_pragmas(&comments) {
+                        Ok(pragmas) => { pragmas }
+                        Err(error) => {
+                            print_interp_error(&error, json_errors, script, &script_path);
+                            std::process::exit(1);
+                        }
+                    };
+                    let strict = strict || pragmas.strict;
 
-                    match parse(&tokens) {
-                        Ok(ast) => {
-                            println!("{:?}", ast);
+                    let unresolved = resolver::resolve(&ast);
+                    if !unresolved.is_empty() {
+                        for diagnostic in &unresolved {
+                            eprint!("{}", format_diagnostic(diagnostic, json_errors, script, &script_path));
+                        }
+                        std::process::exit(1);
+                    }
 
-                            println!("Output:\n");
+                    let mut errors = Vec::new();
+                    for mut warning in lint::lint(&ast) {
+                        if warning.is_allowed(&allowed) {
+                            continue;
+                        }
+                        if strict {
+                            // The strict dialect does not run scripts with warnings
+                            warning.severity = Severity::Error;
+                            errors.push(warning);
+                        } else {
+                            eprint!("{}", format_diagnostic(&warning, json_errors, script, &script_path));
+                        }
+                    }
+                    if typecheck || strict {
+                        errors.extend(typecheck::check(&ast));
+                    }
+                    if !errors.is_empty() {
+                        for diagnostic in &errors {
+                            eprint!("{}", format_diagnostic(diagnostic, json_errors, script, &script_path));
+                        }
+                        std::process::exit(1);
+                    }
 
-                            match interp(ast) {
-                                Ok(res) => {
-                                    println!("Result: {:?}", res);
-                                }
-                                Err(error) => {
-                                    println!("{}", error);
-                                }
+                    let mut interp = Interp::new();
+                    interp.set_strict_overflow(strict_overflow);
+                    interp.set_strict_division(strict_division);
+                    interp.set_options(InterpOptions {
+                        stack_size,
+                        max_steps,
+                        timeout,
+                        max_memory,
+                        ..InterpOptions::default()
+                    });
+                    if strict {
+                        interp.set_strict(true);
+                    }
+                    if let Err(error) = interp.set_bignum(pragmas.bignum) {
+                        print_interp_error(&error, json_errors, script, &script_path);
+                        std::process::exit(1);
+                    }
+                    if record_path.is_some() {
+                        interp.record_effects();
+                    }
+                    if let Some(ref path) = replay_path {
+                        match fs::read_to_string(path).map_err(|error| error.to_string()).and_then(|log| replay::read_log(&log)) {
+                            Ok(effects) => { interp.replay_effects(effects); }
+                            Err(error) => {
+                                eprintln!("Failed to read replay log {}: {}", path, error);
+                                std::process::exit(1);
                             }
                         }
-                        Err(error) => {
-                            println!("{}", error);
+                    }
+                    interp.watch_script(&script_path);
+                    if trace {
+                        interp.add_hook(Box::new(Tracer::new(script)));
+                    }
+                    let coverage = Rc::new(RefCell::new(Coverage::new(&ast)));
+                    if coverage_format.is_some() {
+                        interp.add_hook(Box::new(coverage.clone()));
+                    }
+                    let profiler = Rc::new(RefCell::new(Profiler::new(1)));
+                    if profile_format.is_some() {
+                        interp.add_hook(Box::new(profiler.clone()));
+                    }
+
+                    // Reports are still written for a script that failed, up to where it failed
+                    let mut succeeded = true;
+                    if let Err(error) = interp.run(&ast) {
+                        print_interp_error(&error, json_errors, script, &script_path);
+                        succeeded = false;
+                    }
+
+                    if let Some(ref path) = record_path {
+                        if let Err(error) = fs::write(path, replay::write_log(interp.recorded_effects())) {
+                            eprintln!("Failed to write {}: {}", path, error);
+                            succeeded = false;
                         }
                     }
+                    if let Some(ref format) = coverage_format {
+                        succeeded &= write_coverage(&coverage.borrow(), format, &script_path, script);
+                    }
+                    if let Some(ref format) = profile_format {
+                        succeeded &= write_profile(&profiler.borrow(), format);
+                    }
+                    if !succeeded {
+                        std::process::exit(1);
+                    }
                 }
-                Err(error) => {
-                    println!("{}", error);
+                Err(errors) => {
+                    for error in &errors {
+                        eprint!("{}", format_diagnostic(&Diagnostic::from(error), json_errors, script, &script_path));
+                    }
+                    std::process::exit(1);
                 }
-
             }
         }
         Err(error) => {
-            println!("Failed to read script: {}", error);
+            eprintln!("Failed to read script {}: {}", script_path, error);
+            std::process::exit(1);
         }
     }
-
-//    let script =   "\
-//    {\
-//        message :String :: \"Hello \\\" World\";\
-//        print(message);\
-//    }";
-    // func :: (a :int, b :String){ }
-    // [] dot []
-    // 10.dot ()
-    // dot()
-    // struct :: {}
-    // array :: []
-
-
 }
@@ -1,22 +1,353 @@
-use parser::{AstOperatorCall, AstOperator, AstNodeType, AstNumberValue};
+use parser::{AstOperator, AstUnaryOperator};
 use interp::{InterpValue, InterpError};
+use format::{format_debug, with_article};
+use error_codes;
+
+use std::rc::Rc;
+
+#[cfg(feature = "bignum")]
+use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive, Zero};
 
 pub fn apply_operation(lhs: InterpValue, rhs: InterpValue, operator: AstOperator) -> Result<InterpValue, InterpError> {
     use interp::InterpValue::*;
 
-    let res = match (lhs, rhs) {
+    match operator {
+        AstOperator::Equal => { return Ok(InterpBoolean(values_equal(&lhs, &rhs))); }
+        AstOperator::NotEqual => { return Ok(InterpBoolean(!values_equal(&lhs, &rhs))); }
+        _ => {}
+    }
+
+    match (lhs, rhs) {
         (InterpNumber(lhs), InterpNumber(rhs)) => {
             apply_number_number_operation(lhs, rhs, operator)
         }
+        (InterpInt(lhs), InterpInt(rhs)) => {
+            apply_int_int_operation(lhs, rhs, operator)
+        }
+        (InterpInt(lhs), InterpNumber(rhs)) => {
+            apply_number_number_operation(lhs as f64, rhs, operator)
+        }
+        (InterpNumber(lhs), InterpInt(rhs)) => {
+            apply_number_number_operation(lhs, rhs as f64, operator)
+        }
+        (InterpString(lhs), InterpString(rhs)) => {
+            apply_string_string_operation(&lhs, &rhs, operator)
+        }
+        #[cfg(feature = "bignum")]
+        (InterpBigNumber(lhs), InterpBigNumber(rhs)) => {
+            apply_big_number_operation(lhs, rhs, operator)
+        }
+        #[cfg(feature = "bignum")]
+        (InterpBigNumber(lhs), InterpNumber(rhs)) => {
+            apply_big_number_operation(lhs, to_big_number(rhs)?, operator)
+        }
+        #[cfg(feature = "bignum")]
+        (InterpNumber(lhs), InterpBigNumber(rhs)) => {
+            apply_big_number_operation(to_big_number(lhs)?, rhs, operator)
+        }
+        #[cfg(feature = "bignum")]
+        (InterpBigNumber(lhs), InterpInt(rhs)) => {
+            apply_big_number_operation(lhs, BigDecimal::from(rhs), operator)
+        }
+        #[cfg(feature = "bignum")]
+        (InterpInt(lhs), InterpBigNumber(rhs)) => {
+            apply_big_number_operation(BigDecimal::from(lhs), rhs, operator)
+        }
+        (InterpVoid, _) | (_, InterpVoid) => {
+            let msg = format!("Can not apply {:?} to null", operator);
+            Err(InterpError::new(&error_codes::NULL_OPERAND, msg))
+        }
         (tp1, tp2) => {
             let msg = format!("Operator not yet implemented. lhs: {:?}, rhs: {:?}", tp1, tp2);
-            return Err(InterpError::new(msg));
+            Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+        }
+    }
+}
+
+pub fn apply_unary_operation(operand: InterpValue, operator: AstUnaryOperator) -> Result<InterpValue, InterpError> {
+    use interp::InterpValue::*;
+
+    match (operand, operator) {
+        (InterpNumber(num), AstUnaryOperator::BitNot) => {
+            let num = to_integer(num, operator.symbol())?;
+            Ok(InterpNumber(!num as f64))
+        }
+        (InterpNumber(num), AstUnaryOperator::Negate) => {
+            Ok(InterpNumber(-num))
+        }
+        (InterpInt(num), AstUnaryOperator::BitNot) => {
+            Ok(InterpInt(!num))
+        }
+        (InterpInt(num), AstUnaryOperator::Negate) => {
+            Ok(InterpInt(num.wrapping_neg()))
+        }
+        #[cfg(feature = "bignum")]
+        (InterpBigNumber(num), AstUnaryOperator::Negate) => {
+            Ok(InterpBigNumber(-num))
+        }
+        #[cfg(feature = "bignum")]
+        (InterpBigNumber(num), AstUnaryOperator::BitNot) => {
+            let num = big_to_integer(&num, operator.symbol())?;
+            Ok(InterpBigNumber(BigDecimal::from(!num)))
+        }
+        (operand, _) => {
+            let msg = format!("Can not apply {} to {}", operator.symbol(), with_article(operand.type_name()));
+            Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg))
+        }
+    }
+}
+
+/// Bitwise operators work on the integer a number represents, anything
+/// with a fraction or outside the i64 range is an error.
+fn to_integer(num: f64, symbol: &str) -> Result<i64, InterpError> {
+    if num.fract() != 0.0 || num < i64::MIN as f64 || num >= i64::MAX as f64 {
+        let msg = format!("{} needs integer operands, got {}", symbol, num);
+        return Err(InterpError::new(&error_codes::INTEGER_REQUIRED, msg));
+    }
+    Ok(num as i64)
+}
+
+#[cfg(feature = "bignum")]
+fn big_to_integer(num: &BigDecimal, symbol: &str) -> Result<i64, InterpError> {
+    match num.to_i64() {
+        Some(integer) if num.is_integer() => { Ok(integer) }
+        _ => {
+            let msg = format!("{} needs integer operands, got {}", symbol, num);
+            Err(InterpError::new(&error_codes::INTEGER_REQUIRED, msg))
+        }
+    }
+}
+
+fn apply_integer_operation(lhs: i64, rhs: i64, operator: AstOperator) -> Result<i64, InterpError> {
+    use parser::AstOperator::*;
+    let shift = match operator {
+        ShiftLeft | ShiftRight => {
+            if !(0..64).contains(&rhs) {
+                let msg = format!("Can not shift by {} bits", rhs);
+                return Err(InterpError::new(&error_codes::INVALID_SHIFT, msg));
+            }
+            rhs as u32
+        }
+        _ => { 0 }
+    };
+
+    let val = match operator {
+        BitAnd => { lhs & rhs }
+        BitOr => { lhs | rhs }
+        ShiftLeft => { lhs.wrapping_shl(shift) }
+        ShiftRight => { lhs >> shift }
+        _ => {
+            let msg = format!("{} is not a bitwise operator", operator.symbol());
+            return Err(InterpError::new(&error_codes::INTERNAL, msg));
+        }
+    };
+    Ok(val)
+}
+
+/// Like `apply_operation`, but a result that overflows the number range is
+/// an error naming the operands instead of an infinity or an Int that
+/// wrapped around. Dividing by zero is not an overflow and behaves as usual.
+pub fn apply_checked_operation(lhs: InterpValue, rhs: InterpValue, operator: AstOperator) -> Result<InterpValue, InterpError> {
+    use interp::InterpValue::*;
+
+    if let (&InterpInt(lhs), &InterpInt(rhs)) = (&lhs, &rhs) {
+        if int_overflows(lhs, rhs, operator) {
+            let msg = format!("Integer overflow in {} {} {}", lhs, operator.symbol(), rhs);
+            return Err(InterpError::new(&error_codes::OVERFLOW, msg));
+        }
+    }
+
+    let operands = match (&lhs, &rhs) {
+        (&InterpNumber(lhs), &InterpNumber(rhs)) => { Some((lhs, rhs)) }
+        (&InterpNumber(lhs), &InterpInt(rhs)) => { Some((lhs, rhs as f64)) }
+        (&InterpInt(lhs), &InterpNumber(rhs)) => { Some((lhs as f64, rhs)) }
+        (&InterpInt(lhs), &InterpInt(rhs)) => { Some((lhs as f64, rhs as f64)) }
+        _ => { None }
+    };
+    let res = apply_operation(lhs, rhs, operator)?;
+
+    if let (Some((lhs, rhs)), &InterpNumber(num)) = (operands, &res) {
+        let divides_by_zero = rhs == 0.0 && matches!(operator, AstOperator::Div | AstOperator::Mod);
+        if lhs.is_finite() && rhs.is_finite() && num.is_infinite() && !divides_by_zero {
+            let msg = format!("Overflow in {:?} {} {:?}", lhs, operator.symbol(), rhs);
+            return Err(InterpError::new(&error_codes::OVERFLOW, msg));
+        }
+    }
+    Ok(res)
+}
+
+/// Like `apply_unary_operation`, but negating the smallest Int is an error
+/// instead of wrapping around to itself.
+pub fn apply_checked_unary_operation(operand: InterpValue, operator: AstUnaryOperator) -> Result<InterpValue, InterpError> {
+    if let (&InterpValue::InterpInt(num), AstUnaryOperator::Negate) = (&operand, operator) {
+        if num.checked_neg().is_none() {
+            let msg = format!("Integer overflow in -{}", num);
+            return Err(InterpError::new(&error_codes::OVERFLOW, msg));
+        }
+    }
+    apply_unary_operation(operand, operator)
+}
+
+/// Whether the Int result of `lhs operator rhs` is outside the i64 range,
+/// and so wraps around unless overflow is checked.
+fn int_overflows(lhs: i64, rhs: i64, operator: AstOperator) -> bool {
+    use parser::AstOperator::*;
+    match operator {
+        Add => { lhs.checked_add(rhs).is_none() }
+        Sub => { lhs.checked_sub(rhs).is_none() }
+        Mult => { lhs.checked_mul(rhs).is_none() }
+        Mod => { rhs != 0 && lhs.checked_rem(rhs).is_none() }
+        Pow => { rhs >= 0 && checked_pow(lhs, rhs as u64).is_none() }
+        _ => { false }
+    }
+}
+
+fn checked_pow(mut base: i64, mut exponent: u64) -> Option<i64> {
+    let mut res: i64 = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            res = res.checked_mul(base)?;
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            base = base.checked_mul(base)?;
+        }
+    }
+    Some(res)
+}
+
+fn wrapping_pow(mut base: i64, mut exponent: u64) -> i64 {
+    let mut res: i64 = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            res = res.wrapping_mul(base);
+        }
+        exponent >>= 1;
+        base = base.wrapping_mul(base);
+    }
+    res
+}
+
+/// With --strict-division, dividing or taking the modulo by zero is an
+/// error rather than an infinity or NaN.
+pub fn check_division(lhs: &InterpValue, rhs: &InterpValue, operator: AstOperator) -> Result<(), InterpError> {
+    use interp::InterpValue::*;
+
+    let zero = match *rhs {
+        InterpNumber(num) => { num == 0.0 }
+        InterpInt(num) => { num == 0 }
+        _ => { false }
+    };
+    if zero && matches!(operator, AstOperator::Div | AstOperator::Mod) {
+        let msg = format!("Division by zero in {} {} {}", format_debug(lhs.clone()), operator.symbol(),
+                          format_debug(rhs.clone()));
+        return Err(InterpError::new(&error_codes::DIVISION_BY_ZERO, msg));
+    }
+    Ok(())
+}
+
+/// With --strict-division, a result that is not a number, such as the one
+/// of `(-1) ^ 0.5`, is an error.
+pub fn check_number(res: &InterpValue, operator: AstOperator) -> Result<(), InterpError> {
+    if let &InterpValue::InterpNumber(num) = res {
+        if num.is_nan() {
+            let msg = format!("{} gave a result that is not a number", operator.symbol());
+            return Err(InterpError::new(&error_codes::NOT_A_NUMBER, msg));
+        }
+    }
+    Ok(())
+}
+
+/// Values of different types are never equal, null only equals null.
+pub fn values_equal(lhs: &InterpValue, rhs: &InterpValue) -> bool {
+    use interp::InterpValue::*;
+    match (lhs, rhs) {
+        (&InterpVoid, &InterpVoid) => { true }
+        (&InterpNumber(lhs), &InterpNumber(rhs)) => { lhs == rhs }
+        (&InterpInt(lhs), &InterpInt(rhs)) => { lhs == rhs }
+        (&InterpInt(lhs), &InterpNumber(rhs)) | (&InterpNumber(rhs), &InterpInt(lhs)) => { lhs as f64 == rhs }
+        #[cfg(feature = "bignum")]
+        (InterpBigNumber(lhs), InterpBigNumber(rhs)) => { lhs == rhs }
+        #[cfg(feature = "bignum")]
+        (&InterpBigNumber(ref lhs), &InterpInt(rhs)) | (&InterpInt(rhs), &InterpBigNumber(ref lhs)) => {
+            lhs.to_i64() == Some(rhs) && lhs.is_integer()
+        }
+        #[cfg(feature = "bignum")]
+        (&InterpBigNumber(ref lhs), &InterpNumber(rhs)) | (&InterpNumber(rhs), &InterpBigNumber(ref lhs)) => {
+            BigDecimal::from_f64(rhs).is_some_and(|rhs| *lhs == rhs)
+        }
+        (&InterpBoolean(lhs), &InterpBoolean(rhs)) => { lhs == rhs }
+        (InterpString(lhs), InterpString(rhs)) => { lhs == rhs }
+        (&InterpStruct(lhs), &InterpStruct(rhs)) => { lhs == rhs }
+        (&InterpFunction{id: lhs_id, closure_id: lhs_closure}, &InterpFunction{id: rhs_id, closure_id: rhs_closure}) => {
+            lhs_id == rhs_id && lhs_closure == rhs_closure
+        }
+        (&InterpBoundFunction{id: lhs_id, closure_id: lhs_closure, arguments: ref lhs_arguments},
+         &InterpBoundFunction{id: rhs_id, closure_id: rhs_closure, arguments: ref rhs_arguments}) => {
+            lhs_id == rhs_id && lhs_closure == rhs_closure && Rc::ptr_eq(lhs_arguments, rhs_arguments)
+        }
+        (InterpTuple(lhs), InterpTuple(rhs)) => {
+            lhs.len() == rhs.len() && lhs.iter().zip(rhs.iter()).all(|(lhs, rhs)| values_equal(lhs, rhs))
+        }
+        (InterpBuilder(lhs), InterpBuilder(rhs)) => { Rc::ptr_eq(lhs, rhs) }
+        (InterpIterator(lhs), InterpIterator(rhs)) => { Rc::ptr_eq(lhs, rhs) }
+        (InterpArray(lhs), InterpArray(rhs)) => { Rc::ptr_eq(lhs, rhs) }
+        (InterpInstance{fields: lhs, ..}, InterpInstance{fields: rhs, ..}) => { Rc::ptr_eq(lhs, rhs) }
+        (InterpList(lhs), InterpList(rhs)) => {
+            lhs.len() == rhs.len() && lhs.iter().zip(rhs.iter()).all(|(lhs, rhs)| values_equal(lhs, rhs))
+        }
+        (InterpMap(lhs), InterpMap(rhs)) => {
+            lhs.len() == rhs.len() && lhs.iter().zip(rhs.iter())
+                .all(|(lhs, rhs)| lhs.0 == rhs.0 && values_equal(&lhs.1, &rhs.1))
+        }
+        _ => { false }
+    }
+}
+
+/// Strings are ordered by comparing their characters one by one, so "B"
+/// comes before "a" and "ab" before "b".
+fn apply_string_string_operation(lhs: &str, rhs: &str, operator: AstOperator) -> Result<InterpValue, InterpError> {
+    use parser::AstOperator::*;
+    let val = match operator {
+        Less => { lhs < rhs }
+        LessEqual => { lhs <= rhs }
+        Greater => { lhs > rhs }
+        GreaterEqual => { lhs >= rhs }
+        _ => {
+            let msg = format!("Operator {} can not be applied to strings", operator.symbol());
+            return Err(InterpError::new(&error_codes::TYPE_MISMATCH, msg));
         }
     };
-    return Ok(res);
+    Ok(InterpValue::InterpBoolean(val))
 }
 
-fn apply_number_number_operation(lhs: f64, rhs: f64, operator: AstOperator) -> InterpValue {
+/// Ints stay Ints, except that `/` always divides exactly and so gives a
+/// Number, as does `^` with a negative exponent and `%` by zero, which is
+/// NaN like it is for Numbers. An Int result outside the i64 range wraps
+/// around, `apply_checked_operation` makes that an error.
+fn apply_int_int_operation(lhs: i64, rhs: i64, operator: AstOperator) -> Result<InterpValue, InterpError> {
+    use parser::AstOperator::*;
+    let val = match operator {
+        Add => { lhs.wrapping_add(rhs) }
+        Sub => { lhs.wrapping_sub(rhs) }
+        Mult => { lhs.wrapping_mul(rhs) }
+        Div => { return apply_number_number_operation(lhs as f64, rhs as f64, operator); }
+        Mod if rhs == 0 => { return apply_number_number_operation(lhs as f64, rhs as f64, operator); }
+        Mod => { lhs.wrapping_rem(rhs) }
+        Pow if rhs < 0 => { return apply_number_number_operation(lhs as f64, rhs as f64, operator); }
+        Pow => { wrapping_pow(lhs, rhs as u64) }
+        Equal => { return Ok(InterpValue::InterpBoolean(lhs == rhs)); }
+        NotEqual => { return Ok(InterpValue::InterpBoolean(lhs != rhs)); }
+        Less => { return Ok(InterpValue::InterpBoolean(lhs < rhs)); }
+        LessEqual => { return Ok(InterpValue::InterpBoolean(lhs <= rhs)); }
+        Greater => { return Ok(InterpValue::InterpBoolean(lhs > rhs)); }
+        GreaterEqual => { return Ok(InterpValue::InterpBoolean(lhs >= rhs)); }
+        BitAnd | BitOr | ShiftLeft | ShiftRight => { apply_integer_operation(lhs, rhs, operator)? }
+    };
+    Ok(InterpValue::InterpInt(val))
+}
+
+fn apply_number_number_operation(lhs: f64, rhs: f64, operator: AstOperator) -> Result<InterpValue, InterpError> {
     use parser::AstOperator::*;
     let val = match operator {
         Add => { lhs + rhs }
@@ -25,6 +356,65 @@ fn apply_number_number_operation(lhs: f64, rhs: f64, operator: AstOperator) -> I
         Div => { lhs / rhs }
         Pow => { lhs.powf(rhs) }
         Mod => { lhs % rhs }
+        Equal => { return Ok(InterpValue::InterpBoolean(lhs == rhs)); }
+        NotEqual => { return Ok(InterpValue::InterpBoolean(lhs != rhs)); }
+        Less => { return Ok(InterpValue::InterpBoolean(lhs < rhs)); }
+        LessEqual => { return Ok(InterpValue::InterpBoolean(lhs <= rhs)); }
+        Greater => { return Ok(InterpValue::InterpBoolean(lhs > rhs)); }
+        GreaterEqual => { return Ok(InterpValue::InterpBoolean(lhs >= rhs)); }
+        BitAnd | BitOr | ShiftLeft | ShiftRight => {
+            let lhs = to_integer(lhs, operator.symbol())?;
+            let rhs = to_integer(rhs, operator.symbol())?;
+            apply_integer_operation(lhs, rhs, operator)? as f64
+        }
+    };
+    Ok(InterpValue::InterpNumber(val))
+}
+
+/// Numbers that were not written as literals, such as builtin results, join
+/// bignum arithmetic with the exact value of their f64.
+#[cfg(feature = "bignum")]
+fn to_big_number(num: f64) -> Result<BigDecimal, InterpError> {
+    BigDecimal::from_f64(num).ok_or_else(|| {
+        let msg = format!("{} can not be used as a bignum", num);
+        InterpError::new(&error_codes::BIGNUM_UNAVAILABLE, msg)
+    })
+}
+
+#[cfg(feature = "bignum")]
+fn apply_big_number_operation(lhs: BigDecimal, rhs: BigDecimal, operator: AstOperator) -> Result<InterpValue, InterpError> {
+    use parser::AstOperator::*;
+    let val = match operator {
+        Add => { lhs + rhs }
+        Sub => { lhs - rhs }
+        Mult => { lhs * rhs }
+        Div | Mod if rhs.is_zero() => {
+            let msg = "Division by zero".to_string();
+            return Err(InterpError::new(&error_codes::DIVISION_BY_ZERO, msg));
+        }
+        Div => { lhs / rhs }
+        Mod => { lhs % rhs }
+        Pow => {
+            let exponent = match rhs.to_i64() {
+                Some(exponent) if rhs.is_integer() => { exponent }
+                _ => {
+                    let msg = format!("bignum powers need an integer exponent, got {}", rhs);
+                    return Err(InterpError::new(&error_codes::INTEGER_REQUIRED, msg));
+                }
+            };
+            lhs.powi(exponent)
+        }
+        Equal => { return Ok(InterpValue::InterpBoolean(lhs == rhs)); }
+        NotEqual => { return Ok(InterpValue::InterpBoolean(lhs != rhs)); }
+        Less => { return Ok(InterpValue::InterpBoolean(lhs < rhs)); }
+        LessEqual => { return Ok(InterpValue::InterpBoolean(lhs <= rhs)); }
+        Greater => { return Ok(InterpValue::InterpBoolean(lhs > rhs)); }
+        GreaterEqual => { return Ok(InterpValue::InterpBoolean(lhs >= rhs)); }
+        BitAnd | BitOr | ShiftLeft | ShiftRight => {
+            let lhs = big_to_integer(&lhs, operator.symbol())?;
+            let rhs = big_to_integer(&rhs, operator.symbol())?;
+            BigDecimal::from(apply_integer_operation(lhs, rhs, operator)?)
+        }
     };
-    return InterpValue::InterpNumber(val);
+    Ok(InterpValue::InterpBigNumber(val))
 }
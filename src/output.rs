@@ -0,0 +1,84 @@
+//! Where scripts write to. Every builtin that writes goes through `write`,
+//! into the `Write` the interpreter was given, which is stdout unless the
+//! host replaces it with `Interp::set_output` or `Engine::set_output`.
+
+use interp::InterpValue;
+use format::{format_value, format_debug};
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+/// The builtins that write to the output of a script. Each writes its
+/// arguments on one line, separated by spaces:
+///
+/// - `print` writes them as they are, without ending the line, so several
+///   prints can build up one line.
+/// - `println` writes them as they are and ends the line.
+/// - `debug` writes their debug form, with strings quoted, and ends the line.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Style {
+    Print,
+    Println,
+    Debug
+}
+
+impl Style {
+    /// Whether a first argument with placeholders is formatted with the
+    /// rest, as with `format`.
+    pub fn formats(self) -> bool {
+        self != Style::Debug
+    }
+}
+
+pub fn write(output: &mut dyn Write, style: Style, arguments: Vec<InterpValue>) -> io::Result<()> {
+    for (index, val) in arguments.into_iter().enumerate() {
+        if index > 0 {
+            write!(output, " ")?;
+        }
+        let text = if style == Style::Debug { format_debug(val) } else { format_value(val) };
+        write!(output, "{}", text)?;
+    }
+    if style != Style::Print {
+        writeln!(output)?;
+    }
+    output.flush()
+}
+
+/// An output sink that collects what a script prints in memory, while the
+/// host keeps a clone to read it back.
+#[derive(Clone)]
+pub struct SharedBuffer {
+    buffer: Rc<RefCell<Vec<u8>>>
+}
+
+impl Default for SharedBuffer {
+    fn default() -> SharedBuffer {
+        SharedBuffer::new()
+    }
+}
+
+impl SharedBuffer {
+    pub fn new() -> SharedBuffer {
+        SharedBuffer {
+            buffer: Rc::new(RefCell::new(Vec::new()))
+        }
+    }
+
+    /// Everything written so far, leaving the buffer empty.
+    pub fn take(&self) -> String {
+        let bytes = self.buffer.replace(Vec::new());
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,32 +1,84 @@
-use tokenizer::Token;
+use tokenizer::{Token, TokenType, TokenizationError, decode_escapes, split_radix};
+use error_codes;
+use error_codes::ErrorCode;
+use file_info::CodePoint;
 use tokenizer::TokenType::*;
-use std::slice::Iter;
 use std::iter::Peekable;
 use std::fmt;
-use std::ops::Deref;
+use std::mem;
+use std::convert::TryFrom;
+use suggest;
+use stacker;
+use interp::{STACK_RED_ZONE, STACK_SEGMENT};
 
 use std::error::Error;
 use std::fmt::Display;
 use std::fmt::Formatter;
 
+/// How tightly `^` binds, the tightest of all binary operators.
+const POW_PRECEDENCE: usize = 10;
+
+/// `|>` binds the loosest, so each stage of a pipeline is a whole expression.
+const PIPE_PRECEDENCE: usize = 0;
+const PIPE_OPERATOR: &str = "|>";
+
+/// The literal for "no value", evaluated as `InterpVoid`.
+pub const NULL_KEYWORD: &str = "null";
+pub const TRUE_KEYWORD: &str = "true";
+pub const FALSE_KEYWORD: &str = "false";
+pub const RETURN_KEYWORD: &str = "return";
+pub const BREAK_KEYWORD: &str = "break";
+pub const CONTINUE_KEYWORD: &str = "continue";
+pub const MATCH_KEYWORD: &str = "match";
+pub const LET_KEYWORD: &str = "let";
+/// The match pattern accepting any value.
+const WILDCARD_PATTERN: &str = "_";
+
+/// Calls whose body is run repeatedly, and so can contain break and continue.
+const LOOPS: [&str; 2] = ["while", "for"];
+
 #[derive(Debug)]
 pub struct ParsingError {
+    code: &'static ErrorCode,
     token: Token,
     desc: String
 }
 
 impl Display for ParsingError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        writeln!(f, "ParsingError: \n{}\n\n{:?}", self.desc, self.token)
+        let file_info = self.token.get_file_info();
+        writeln!(f, "error[{}]: {}\n --> line {}, column {}", self.code.code, self.desc,
+                 file_info.line_number_from, file_info.column_number_from)
     }
 }
 
 impl ParsingError {
-    fn new(token: &Token, desc: String) -> ParsingError {
-        return ParsingError {
+    fn new(code: &'static ErrorCode, token: &Token, desc: String) -> ParsingError {
+        ParsingError {
+            code,
             token: token.clone(),
-            desc: desc
-        };
+            desc
+        }
+    }
+
+    pub fn code(&self) -> &'static ErrorCode {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.desc
+    }
+
+    pub fn get_file_info(&self) -> CodePoint {
+        self.token.get_file_info()
+    }
+}
+
+/// Tokens that could not be read are reported among the statements that
+/// failed to parse.
+impl From<TokenizationError> for ParsingError {
+    fn from(error: TokenizationError) -> ParsingError {
+        ParsingError::new(error.code(), error.token(), String::from(error.message()))
     }
 }
 
@@ -43,22 +95,108 @@ pub enum AstOperator {
     Mult,
     Div,
     Pow,
-    Mod
+    Mod,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    BitAnd,
+    BitOr,
+    ShiftLeft,
+    ShiftRight
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum AstUnaryOperator {
+    BitNot,
+    Not,
+    Negate
+}
+
+impl AstUnaryOperator {
+    pub fn symbol(&self) -> &'static str {
+        match *self {
+            AstUnaryOperator::BitNot => { "~" }
+            AstUnaryOperator::Not => { "!" }
+            AstUnaryOperator::Negate => { "-" }
+        }
+    }
+}
+
+/// `&&` and `||`, which only evaluate their rhs when the lhs does not
+/// already decide the result.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AstLogicalOperator {
+    And,
+    Or
+}
+
+impl AstLogicalOperator {
+    pub fn symbol(&self) -> &'static str {
+        match *self {
+            AstLogicalOperator::And => { "&&" }
+            AstLogicalOperator::Or => { "||" }
+        }
+    }
+
+    fn from_text(text: &str) -> Option<AstLogicalOperator> {
+        match text {
+            "&&" => { Some(AstLogicalOperator::And) }
+            "||" => { Some(AstLogicalOperator::Or) }
+            _ => { None }
+        }
+    }
 }
 
 impl AstOperator {
-    fn from_token(token: &Token) -> AstOperator {
-        assert_eq!(token.get_type(), Operator);
-        let c = &token.get_text()[0..];
-        return match c {
+    pub fn symbol(&self) -> &'static str {
+        match *self {
+            AstOperator::Add => { "+" }
+            AstOperator::Sub => { "-" }
+            AstOperator::Mult => { "*" }
+            AstOperator::Div => { "/" }
+            AstOperator::Pow => { "^" }
+            AstOperator::Mod => { "%" }
+            AstOperator::Equal => { "==" }
+            AstOperator::NotEqual => { "!=" }
+            AstOperator::Less => { "<" }
+            AstOperator::LessEqual => { "<=" }
+            AstOperator::Greater => { ">" }
+            AstOperator::GreaterEqual => { ">=" }
+            AstOperator::BitAnd => { "&" }
+            AstOperator::BitOr => { "|" }
+            AstOperator::ShiftLeft => { "<<" }
+            AstOperator::ShiftRight => { ">>" }
+        }
+    }
+
+    fn from_token(token: &Token) -> Result<AstOperator, ParsingError> {
+        let c = &token.get_text()[..];
+        let operator = match c {
             "+" => { AstOperator::Add }
             "-" => { AstOperator::Sub }
             "*" => { AstOperator::Mult }
             "/" => { AstOperator::Div }
             "^" => { AstOperator::Pow }
             "%" => { AstOperator::Mod }
-            _ => { panic!("Can not interpret '{}' as an operator", c); }
+            "==" => { AstOperator::Equal }
+            "!=" => { AstOperator::NotEqual }
+            "<" => { AstOperator::Less }
+            "<=" => { AstOperator::LessEqual }
+            ">" => { AstOperator::Greater }
+            ">=" => { AstOperator::GreaterEqual }
+            "&" => { AstOperator::BitAnd }
+            "|" => { AstOperator::BitOr }
+            "<<" => { AstOperator::ShiftLeft }
+            ">>" => { AstOperator::ShiftRight }
+            _ => {
+                let msg = format!("Can not interpret '{}' as an operator", c);
+                return Err(ParsingError::new(&error_codes::INVALID_OPERATOR, token, msg));
+            }
         };
+        Ok(operator)
     }
 }
 
@@ -66,6 +204,8 @@ impl AstOperator {
 pub enum AstNodeType {
     Block(Box<AstBlock>),
     OperatorCall(Box<AstOperatorCall>),
+    UnaryOperatorCall(Box<AstUnaryOperatorCall>),
+    LogicalOperatorCall(Box<AstLogicalOperatorCall>),
     FunctionCall(Box<AstFunctionCall>),
     StringValue(Box<AstStringValue>),
     NumberValue(Box<AstNumberValue>),
@@ -74,7 +214,52 @@ pub enum AstNodeType {
     Variable(Box<AstVariable>),
     Assignment(Box<AstAssignment>),
     Alias(Box<AstAlias>),
-    NullValue(Box<AstNullValue>)
+    NullValue(Box<AstNullValue>),
+    BooleanValue(Box<AstBooleanValue>),
+    Return(Box<AstReturn>),
+    Break(Box<AstBreak>),
+    Continue(Box<AstContinue>),
+    Spread(Box<AstSpread>),
+    Tuple(Box<AstTuple>),
+    Destructure(Box<AstDestructure>),
+    ArrayLiteral(Box<AstArrayLiteral>),
+    Index(Box<AstIndex>),
+    FieldAccess(Box<AstFieldAccess>),
+    FieldAssignment(Box<AstFieldAssignment>),
+    Match(Box<AstMatch>)
+}
+
+impl AstNodeType {
+    /// The code the node was parsed from.
+    pub fn span(&self) -> &CodePoint {
+        match *self {
+            AstNodeType::Block(ref boxed) => { &boxed.span }
+            AstNodeType::OperatorCall(ref boxed) => { &boxed.span }
+            AstNodeType::UnaryOperatorCall(ref boxed) => { &boxed.span }
+            AstNodeType::LogicalOperatorCall(ref boxed) => { &boxed.span }
+            AstNodeType::FunctionCall(ref boxed) => { &boxed.span }
+            AstNodeType::StringValue(ref boxed) => { &boxed.span }
+            AstNodeType::NumberValue(ref boxed) => { &boxed.span }
+            AstNodeType::FunctionDeclaration(ref boxed) => { &boxed.span }
+            AstNodeType::StructDeclaration(ref boxed) => { &boxed.span }
+            AstNodeType::Variable(ref boxed) => { &boxed.span }
+            AstNodeType::Assignment(ref boxed) => { &boxed.span }
+            AstNodeType::Alias(ref boxed) => { &boxed.span }
+            AstNodeType::NullValue(ref boxed) => { &boxed.span }
+            AstNodeType::BooleanValue(ref boxed) => { &boxed.span }
+            AstNodeType::Return(ref boxed) => { &boxed.span }
+            AstNodeType::Break(ref boxed) => { &boxed.span }
+            AstNodeType::Continue(ref boxed) => { &boxed.span }
+            AstNodeType::Spread(ref boxed) => { &boxed.span }
+            AstNodeType::Tuple(ref boxed) => { &boxed.span }
+            AstNodeType::Destructure(ref boxed) => { &boxed.span }
+            AstNodeType::ArrayLiteral(ref boxed) => { &boxed.span }
+            AstNodeType::Index(ref boxed) => { &boxed.span }
+            AstNodeType::FieldAccess(ref boxed) => { &boxed.span }
+            AstNodeType::FieldAssignment(ref boxed) => { &boxed.span }
+            AstNodeType::Match(ref boxed) => { &boxed.span }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -83,29 +268,141 @@ pub struct Ast {
 }
 
 pub struct AstBlock {
-    pub statements: Vec<AstNodeType>
+    pub statements: Vec<AstStatement>,
+    pub span: CodePoint
+}
+
+/// A statement together with the source it was parsed from, including its `;`
+#[derive(Debug)]
+pub struct AstStatement {
+    pub node: AstNodeType,
+    pub file_info: CodePoint
 }
 
 impl fmt::Debug for AstBlock {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut statements_str = String::new();
-        for wrapped_statement in &self.statements {
-            let statement = wrapped_statement.deref();
-            let statement_str = format!("{:?}", statement);
-            statements_str.push_str(&statement_str);
-        }
-        write!(f, "AstBlock {{statements=[{}]}}", statements_str)
+        let nodes: Vec<&AstNodeType> = self.statements.iter().map(|statement| &statement.node).collect();
+        f.debug_struct("AstBlock").field("statements", &nodes).finish()
     }
 }
 
 #[derive(Debug)]
-pub struct AstNullValue {}
+pub struct AstNullValue {
+    pub span: CodePoint
+}
+
+#[derive(Debug)]
+pub struct AstBooleanValue {
+    pub value: bool,
+    pub span: CodePoint
+}
+
+/// `return value;` leaves the enclosing function early. Without a value the
+/// function returns null.
+#[derive(Debug)]
+pub struct AstReturn {
+    pub value: Option<AstNodeType>,
+    pub span: CodePoint
+}
+
+/// `break;` leaves the innermost loop.
+#[derive(Debug)]
+pub struct AstBreak {
+    pub span: CodePoint
+}
+
+/// `continue;` skips to the next iteration of the innermost loop.
+#[derive(Debug)]
+pub struct AstContinue {
+    pub span: CodePoint
+}
+
+/// `a, b;` evaluates to both values. As the last statement of a function
+/// body this returns several values from the function.
+#[derive(Debug)]
+pub struct AstTuple {
+    pub values: Vec<AstNodeType>,
+    pub span: CodePoint
+}
+
+/// `a, b :: f();` declares, and `a, b := f();` assigns, one name for each
+/// value `f` returns.
+#[derive(Debug)]
+pub struct AstDestructure {
+    pub to: Vec<AstVariable>,
+    pub from: AstNodeType,
+    pub declares: bool,
+    pub span: CodePoint
+}
+
+/// `[a, b, c]`
+#[derive(Debug)]
+pub struct AstArrayLiteral {
+    pub values: Vec<AstNodeType>,
+    pub span: CodePoint
+}
+
+/// `value[index]`
+#[derive(Debug)]
+pub struct AstIndex {
+    pub value: AstNodeType,
+    pub index: AstNodeType,
+    pub span: CodePoint
+}
+
+/// `value.field`
+#[derive(Debug)]
+pub struct AstFieldAccess {
+    pub value: AstNodeType,
+    pub field: String,
+    pub span: CodePoint
+}
+
+/// `value.field = from;`
+#[derive(Debug)]
+pub struct AstFieldAssignment {
+    pub to: AstFieldAccess,
+    pub from: AstNodeType,
+    pub span: CodePoint
+}
+
+/// `match(value){ pattern -> expr; ... }`
+#[derive(Debug)]
+pub struct AstMatch {
+    pub value: AstNodeType,
+    pub arms: Vec<AstMatchArm>,
+    pub span: CodePoint
+}
+
+#[derive(Debug)]
+pub struct AstMatchArm {
+    pub pattern: AstPattern,
+    pub body: AstNodeType,
+    pub span: CodePoint
+}
+
+#[derive(Debug)]
+pub enum AstPattern {
+    /// A number, string, boolean or null, compared with `==`
+    Literal(AstNodeType),
+    /// `_`, matching any value
+    Wildcard
+}
+
+/// `...value` in the arguments of a call, splicing the elements of an
+/// array into the argument list.
+#[derive(Debug)]
+pub struct AstSpread {
+    pub value: AstNodeType,
+    pub span: CodePoint
+}
 
 impl AstBlock {
-    fn new() -> AstBlock {
-        return AstBlock {
-            statements: Vec::new()
-        };
+    fn new(span: CodePoint) -> AstBlock {
+        AstBlock {
+            statements: Vec::new(),
+            span
+        }
     }
 }
 
@@ -114,123 +411,439 @@ pub struct AstFunctionCall {
     pub name: String,
     pub arguments: Vec<AstNodeType>,
     pub body: Option<AstBlock>,
-    pub next: Option<AstFunctionCall>
+    pub next: Option<Box<AstFunctionCall>>,
+    pub span: CodePoint
 }
 
 #[derive(Debug)]
 pub struct AstOperatorCall {
     pub rhs: AstNodeType,
     pub lhs: AstNodeType,
-    pub operator: AstOperator
+    pub operator: AstOperator,
+    pub span: CodePoint
+}
+
+#[derive(Debug)]
+pub struct AstLogicalOperatorCall {
+    pub lhs: AstNodeType,
+    pub rhs: AstNodeType,
+    pub operator: AstLogicalOperator,
+    pub span: CodePoint
+}
+
+#[derive(Debug)]
+pub struct AstUnaryOperatorCall {
+    pub operand: AstNodeType,
+    pub operator: AstUnaryOperator,
+    pub span: CodePoint
 }
 
 #[derive(Debug)]
 pub struct AstFunctionDeclaration {
     pub arguments: Vec<AstNodeType>,
-    pub body: AstBlock
+    pub body: AstBlock,
+    pub span: CodePoint
 }
 
 #[derive(Debug)]
 pub struct AstStructDeclaration {
+    /// The name the struct is declared with, which its instances go by.
+    pub name: String,
     pub fields: Vec<String>,
-    pub types: Vec<String>
+    pub types: Vec<String>,
+    pub span: CodePoint
 }
 
 #[derive(Debug)]
 pub struct AstStringValue {
-    pub value: String
+    pub value: String,
+    pub span: CodePoint
 }
 
 #[derive(Debug)]
 pub struct AstNumberValue {
-    pub value: f64
+    pub value: f64,
+    /// Set for literals written without a decimal point or exponent that fit
+    /// in an i64.
+    pub integer: Option<i64>,
+    /// The literal without `_` separators, and in decimal when it was written
+    /// with a radix prefix, for number backends more precise than f64.
+    pub text: String,
+    pub span: CodePoint
 }
 
+/// `type_name` is the annotation written after the name where it is
+/// declared, as in `message :String = "hi";` or `(n :Int) {}`.
 #[derive(Debug)]
 pub struct AstVariable {
-    pub name: String
+    pub name: String,
+    pub type_name: Option<String>,
+    pub span: CodePoint
 }
 
+/// `x = value;` updates the closest scope that binds `x`, while
+/// `let x = value;` declares a new `x` in the innermost one.
 #[derive(Debug)]
 pub struct AstAssignment {
     pub to: AstVariable,
-    pub from: AstNodeType
+    pub from: AstNodeType,
+    pub declares: bool,
+    pub span: CodePoint
 }
 
 #[derive(Debug)]
 pub struct AstAlias {
     pub to: AstVariable,
-    pub from: AstNodeType
+    pub from: AstNodeType,
+    pub span: CodePoint
 }
 
-pub struct Parser<'a> {
-    token_stream: Peekable<Iter<'a, Token>>,
-    token_buffer: Vec<&'a Token>,
-    current_token: &'a Token
+pub struct Parser<I: Iterator> {
+    token_stream: Peekable<I>,
+    current_token: Token,
+    /// How many function declarations the parser is inside of.
+    function_depth: usize,
+    /// How many loop bodies the parser is inside of, within the innermost
+    /// function.
+    loop_depth: usize,
+    /// The errors of the statements skipped so far, and of the tokens that
+    /// could not be read.
+    errors: Vec<ParsingError>,
+    /// How many tokens could not be read so far.
+    unreadable_tokens: usize
 }
 
-impl<'a> Parser<'a> {
-    fn next_token(&mut self) -> Option<&'a Token> {
-        return match self.token_stream.next() {
-            Some(token) => {
-                self.current_token = token;
-                Some(token)
+impl<I, E> Parser<I> where I: Iterator<Item = Result<Token, E>>, E: Into<ParsingError> {
+    fn expect_current(&self, token_type: TokenType, context: &str) -> Result<(), ParsingError> {
+        if self.current_token.get_type() != token_type {
+            let msg = format!("Expected {:?} when parsing {}", token_type, context);
+            let msg = self.unexpected_token_msg(msg, &self.current_token, &[]);
+            return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &self.current_token, msg));
+        }
+        Ok(())
+    }
+
+    /// The code from `start` up to and including the current token.
+    fn span_from(&self, start: &CodePoint) -> CodePoint {
+        CodePoint::spanning(start, &self.current_token.get_file_info())
+    }
+
+    /// Tokens that could not be read are recorded on the way, and parsing
+    /// goes on with the ones after them.
+    fn skip_comments(&mut self) {
+        loop {
+            match self.token_stream.peek() {
+                Some(Ok(token)) if token.get_type() == Comment => {}
+                Some(Err(_)) => {}
+                _ => { return; }
+            }
+            if let Some(Err(error)) = self.token_stream.next() {
+                self.errors.push(error.into());
+                self.unreadable_tokens += 1;
             }
+        }
+    }
+
+    fn expect_next_token(&mut self, context: &str) -> Result<Token, ParsingError> {
+        match self.next_token() {
+            Some(token) => { Ok(token) }
             None => {
+                let msg = format!("Unexpected end of input when parsing {}", context);
+                Err(ParsingError::new(&error_codes::UNEXPECTED_END_OF_INPUT, &self.current_token, msg))
+            }
+        }
+    }
+
+    /// Names the token that was found instead of the expected ones, with a
+    /// hint when it is a near miss of one of them.
+    fn unexpected_token_msg(&self, msg: String, found: &Token, expected: &[&str]) -> String {
+        let found_text = found.get_text();
+        let msg = format!("{}, found `{}`", msg, found_text);
+        if found_text.chars().any(|c| c.is_alphanumeric()) {
+            return msg;
+        }
+        match suggest::closest_within(&found_text, expected.iter().cloned(), 1) {
+            Some(candidate) => { format!("{}, did you mean `{}`?", msg, candidate) }
+            None => { msg }
+        }
+    }
+
+    /// Comments are skipped, so they may appear between any two tokens.
+    fn next_token(&mut self) -> Option<Token> {
+        self.skip_comments();
+        match self.token_stream.next() {
+            Some(Ok(token)) => {
+                self.current_token = token.clone();
+                Some(token)
+            }
+            _ => {
                 None
             }
-        };
+        }
     }
 
-    fn peek_token(&mut self) -> Option<&'a Token> {
-        return match self.token_stream.peek() {
-            Some(token) => {
-                Some(*token)
+    fn peek_token(&mut self) -> Option<Token> {
+        self.skip_comments();
+        match self.token_stream.peek() {
+            Some(Ok(token)) => {
+                Some(token.clone())
             }
-            None => {
+            _ => {
                 None
             }
-        };
+        }
     }
 
 
     fn parse_number(&mut self) -> Result<AstNodeType, ParsingError> {
-        assert_eq!(self.current_token.get_type(), Numeric);
-
-        let text = self.current_token.get_text();
-        let maybe_number = match text.parse::<f64>() {
-            Ok(number) => { Ok(number) }
-            Err(err) => {
-                let msg = format!("Failed to parse number: {}", text);
-                Err(ParsingError::new(self.current_token, msg))
+        self.expect_current(Numeric, "a number")?;
+
+        let text = self.current_token.get_text().replace('_', "");
+        let (number, integer, text) = match split_radix(&text) {
+            Some((radix, digits)) => {
+                match u64::from_str_radix(digits, radix) {
+                    Ok(number) => { (number as f64, i64::try_from(number).ok(), number.to_string()) }
+                    Err(_) => {
+                        let msg = format!("The number {} does not fit in 64 bits", text);
+                        return Err(ParsingError::new(&error_codes::INVALID_NUMBER, &self.current_token, msg));
+                    }
+                }
+            }
+            None => {
+                match text.parse::<f64>() {
+                    Ok(number) => {
+                        let integer = if text.contains(['.', 'e', 'E']) { None } else { text.parse::<i64>().ok() };
+                        (number, integer, text)
+                    }
+                    Err(_) => {
+                        let msg = format!("Failed to parse number: {}", text);
+                        return Err(ParsingError::new(&error_codes::INVALID_NUMBER, &self.current_token, msg));
+                    }
+                }
             }
         };
         let value = AstNumberValue {
-            value: maybe_number?
+            value: number,
+            integer,
+            text,
+            span: self.current_token.get_file_info()
         };
 
         let node = AstNodeType::NumberValue(Box::new(value));
-        return Ok(node);
+        Ok(node)
     }
 
     fn parse_string(&mut self) -> Result<AstNodeType, ParsingError> {
-        assert_eq!(self.current_token.get_type(), StaticString);
+        self.expect_current(StaticString, "a string")?;
 
         let text = self.current_token.get_text();
-        let text_without_quotes = &text[1..text.len() - 1];
-        let value = AstStringValue {
-            value: String::from(text_without_quotes)
+        let text_without_quotes = match text.strip_prefix('"').and_then(|text| text.strip_suffix('"')) {
+            Some(text) => { text }
+            None => {
+                let msg = format!("Unterminated string {}", text);
+                return Err(ParsingError::new(&error_codes::INVALID_STRING, &self.current_token, msg));
+            }
+        };
+        let value = match decode_escapes(text_without_quotes) {
+            Ok(value) => { AstStringValue { value, span: self.current_token.get_file_info() } }
+            Err(msg) => { return Err(ParsingError::new(&error_codes::INVALID_STRING, &self.current_token, msg)); }
         };
 
         let node = AstNodeType::StringValue(Box::new(value));
-        return Ok(node);
+        Ok(node)
+    }
+
+    fn parse_array_literal(&mut self) -> Result<AstNodeType, ParsingError> {
+        self.expect_current(OpenBracket, "an array")?;
+        let start = self.current_token.get_file_info();
+
+        let mut values: Vec<AstNodeType> = Vec::new();
+        while let Some(token) = self.next_token() {
+            if token.get_type() == CloseBracket {
+                let array = AstArrayLiteral { values, span: self.span_from(&start) };
+                return Ok(AstNodeType::ArrayLiteral(Box::new(array)));
+            }
+            values.push(self.parse_expression()?);
+
+            if let Some(next) = self.peek_token() {
+                if next.get_type() == ParameterDivider {
+                    self.next_token();
+                    continue;
+                }
+                if next.get_type() == CloseBracket {
+                    continue;
+                }
+            }
+
+            let msg = "Unexpected character when parsing array values".to_string();
+            let msg = match self.peek_token() {
+                Some(next) => { self.unexpected_token_msg(msg, &next, &[",", "]"]) }
+                None => { msg }
+            };
+            return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &self.current_token, msg));
+        }
+
+        let msg = "Unexpected end of stream when parsing an array".to_string();
+        Err(ParsingError::new(&error_codes::UNEXPECTED_END_OF_INPUT, &self.current_token, msg))
+    }
+
+    /// `match(value){ pattern -> expr; ... }`, where the last arm may omit its `;`.
+    fn parse_match(&mut self) -> Result<AstNodeType, ParsingError> {
+        let start = self.current_token.get_file_info();
+        let paren = self.expect_next_token("a match")?;
+        self.expect_next_token("a match")?;
+        let value = self.parse_expression()?;
+        let close = self.expect_next_token("a match")?;
+        if close.get_type() != CloseParenthesis {
+            let msg = "Missing closing parenthesis".to_string();
+            let msg = self.unexpected_token_msg(msg, &close, &[")"]);
+            return Err(ParsingError::new(&error_codes::MISSING_CLOSING_PARENTHESIS, &paren, msg));
+        }
+
+        let open = self.expect_next_token("a match")?;
+        if open.get_type() != OpenBlock {
+            let msg = "Expected the arms of the match".to_string();
+            let msg = self.unexpected_token_msg(msg, &open, &["{"]);
+            return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &open, msg));
+        }
+
+        let mut arms: Vec<AstMatchArm> = Vec::new();
+        loop {
+            let token = self.expect_next_token("a match")?;
+            if token.get_type() == CloseBlock {
+                break;
+            }
+            let arm_start = token.get_file_info();
+            let pattern = self.parse_pattern()?;
+
+            let arrow = self.expect_next_token("a match arm")?;
+            if arrow.get_type() != Arrow {
+                let msg = "Expected -> after the pattern".to_string();
+                let msg = self.unexpected_token_msg(msg, &arrow, &["->"]);
+                return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &arrow, msg));
+            }
+            self.expect_next_token("a match arm")?;
+            let body = self.parse_expression()?;
+            arms.push(AstMatchArm { pattern, body, span: self.span_from(&arm_start) });
+
+            match self.peek_token() {
+                Some(next) if next.get_type() == EndOfStatement => {
+                    self.next_token();
+                }
+                Some(next) if next.get_type() == CloseBlock => {}
+                Some(next) => {
+                    let msg = "Unexpected character after a match arm".to_string();
+                    let msg = self.unexpected_token_msg(msg, &next, &[";", "}"]);
+                    return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &next, msg));
+                }
+                None => {
+                    let msg = "Unexpected end of input when parsing a match".to_string();
+                    return Err(ParsingError::new(&error_codes::UNEXPECTED_END_OF_INPUT, &self.current_token, msg));
+                }
+            }
+        }
+
+        let node = AstMatch {
+            value,
+            arms,
+            span: self.span_from(&start)
+        };
+        Ok(AstNodeType::Match(Box::new(node)))
+    }
+
+    /// A literal or `_`; anything that has to be computed is rejected so that
+    /// a pattern always reads as the value it matches.
+    fn parse_pattern(&mut self) -> Result<AstPattern, ParsingError> {
+        let token = self.current_token.clone();
+        if token.get_type() == Alphanumeric && token.get_text() == WILDCARD_PATTERN {
+            return Ok(AstPattern::Wildcard);
+        }
+
+        let literal = self.parse_primary_expression()?;
+        let is_literal = match &literal {
+            &AstNodeType::NumberValue(_) => { true }
+            &AstNodeType::StringValue(_) => { true }
+            &AstNodeType::BooleanValue(_) => { true }
+            &AstNodeType::NullValue(_) => { true }
+            AstNodeType::UnaryOperatorCall(call) => {
+                matches!((&call.operator, &call.operand), (&AstUnaryOperator::Negate, &AstNodeType::NumberValue(_)))
+            }
+            _ => { false }
+        };
+        if !is_literal {
+            let msg = "Match patterns must be a number, string, boolean, null or _".to_string();
+            return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &token, msg));
+        }
+        Ok(AstPattern::Literal(literal))
+    }
+
+    /// Any number of `[index]`, `.field` and `.method(args)` following a value.
+    fn parse_postfix(&mut self, mut value: AstNodeType) -> Result<AstNodeType, ParsingError> {
+        while let Some(token) = self.peek_token() {
+            if token.get_type() == SubElement {
+                self.next_token();
+                let field = self.expect_next_token("a field name")?;
+                if field.get_type() != Alphanumeric {
+                    let msg = "Expected a field name after .".to_string();
+                    return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &field, msg));
+                }
+                if self.peek_token().is_some_and(|token| token.get_type() == OpenParenthesis) {
+                    value = self.parse_function_call(Some(value))?;
+                    continue;
+                }
+                let span = CodePoint::spanning(value.span(), &field.get_file_info());
+                let access = AstFieldAccess { value, field: field.get_text(), span };
+                value = AstNodeType::FieldAccess(Box::new(access));
+                continue;
+            }
+            if token.get_type() != OpenBracket {
+                break;
+            }
+            self.next_token();
+            self.expect_next_token("an index")?;
+            let index = self.parse_expression()?;
+
+            let close = self.expect_next_token("the end of an index")?;
+            if close.get_type() != CloseBracket {
+                let msg = "Missing closing bracket after index".to_string();
+                let msg = self.unexpected_token_msg(msg, &close, &["]"]);
+                return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &close, msg));
+            }
+            let span = CodePoint::spanning(value.span(), &close.get_file_info());
+            value = AstNodeType::Index(Box::new(AstIndex { value, index, span }));
+        }
+        Ok(value)
     }
 
     fn parse_partial_expression(&mut self) -> Result<AstNodeType, ParsingError> {
-        let token = self.current_token;
-        return match token.get_type() {
+        let value = self.parse_primary_expression()?;
+        self.parse_postfix(value)
+    }
+
+    fn parse_primary_expression(&mut self) -> Result<AstNodeType, ParsingError> {
+        let token = self.current_token.clone();
+        match token.get_type() {
+            Alphanumeric if token.get_text() == NULL_KEYWORD => {
+                let null = AstNullValue { span: token.get_file_info() };
+                Ok(AstNodeType::NullValue(Box::new(null)))
+            }
+            Alphanumeric if token.get_text() == TRUE_KEYWORD || token.get_text() == FALSE_KEYWORD => {
+                let boolean = AstBooleanValue { value: token.get_text() == TRUE_KEYWORD, span: token.get_file_info() };
+                Ok(AstNodeType::BooleanValue(Box::new(boolean)))
+            }
+            Alphanumeric if token.get_text() == MATCH_KEYWORD
+                && self.peek_token().is_some_and(|next| next.get_type() == OpenParenthesis) => {
+                self.parse_match()
+            }
             Alphanumeric => {
-                self.parse_variable()
+                match self.peek_token() {
+                    Some(next) if next.get_type() == OpenParenthesis => {
+                        self.parse_function_call(None)
+                    }
+                    _ => {
+                        self.parse_variable()
+                    }
+                }
             }
             Numeric => {
                 self.parse_number()
@@ -238,6 +851,21 @@ impl<'a> Parser<'a> {
             StaticString => {
                 self.parse_string()
             }
+            OpenBracket => {
+                self.parse_array_literal()
+            }
+            OpenBlock => {
+                self.parse_block()
+            }
+            Operator if token.get_text() == "~" => {
+                self.parse_unary_operator(AstUnaryOperator::BitNot)
+            }
+            Operator if token.get_text() == "!" => {
+                self.parse_unary_operator(AstUnaryOperator::Not)
+            }
+            Operator if token.get_text() == "-" => {
+                self.parse_unary_operator(AstUnaryOperator::Negate)
+            }
             OpenParenthesis => {
                 self.next_token();
                 let expr = self.parse_expression();
@@ -246,32 +874,48 @@ impl<'a> Parser<'a> {
                 if self.current_token.get_type() == CloseParenthesis {
                     expr
                 } else {
-                    let msg = format!("Missing closing parenthesis");
-                    Err(ParsingError::new(token, msg))
+                    let msg = "Missing closing parenthesis".to_string();
+                    let msg = self.unexpected_token_msg(msg, &self.current_token, &[")"]);
+                    Err(ParsingError::new(&error_codes::MISSING_CLOSING_PARENTHESIS, &token, msg))
                 }
             }
             _ => {
-                let msg = format!("Invalid token in expression");
-                Err(ParsingError::new(self.current_token, msg))
+                let msg = "Invalid token in expression".to_string();
+                Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &self.current_token, msg))
             }
-        };
+        }
     }
 
-    fn parse_expression(&mut self) -> Result<AstNodeType, ParsingError> {
-        let evaluatable = self.parse_partial_expression();
-
-        if let Some(token) = self.peek_token() {
-            if token.get_type() == Operator {
-                self.next_token();
-                return self.parse_operator(evaluatable?);
-            }
+    /// A prefix operator applies to the operand right after it, together with
+    /// any `^` following the operand, which binds tighter.
+    fn parse_unary_operator(&mut self, operator: AstUnaryOperator) -> Result<AstNodeType, ParsingError> {
+        let start = self.current_token.get_file_info();
+        if self.next_token().is_none() {
+            let msg = format!("Missing operand after {}", operator.symbol());
+            return Err(ParsingError::new(&error_codes::MISSING_OPERAND, &self.current_token, msg));
         }
 
-        return evaluatable;
+        let operand = self.parse_partial_expression()?;
+        let operand = self.parse_operator(operand, POW_PRECEDENCE)?;
+        let call = AstUnaryOperatorCall {
+            operand,
+            operator,
+            span: self.span_from(&start)
+        };
+        Ok(AstNodeType::UnaryOperatorCall(Box::new(call)))
+    }
+
+    /// Nested expressions recurse, growing the native stack as needed.
+    fn parse_expression(&mut self) -> Result<AstNodeType, ParsingError> {
+        stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, || {
+            let evaluatable = self.parse_partial_expression()?;
+            self.parse_operator(evaluatable, 0)
+        })
     }
 
     fn parse_function_declaration(&mut self) -> Result<AstNodeType, ParsingError> {
-        assert_eq!(self.current_token.get_type(), OpenParenthesis);
+        self.expect_current(OpenParenthesis, "a function declaration")?;
+        let start = self.current_token.get_file_info();
 
         let mut arguments: Vec<AstNodeType> = Vec::new();
         while let Some(token) = self.next_token() {
@@ -280,12 +924,16 @@ impl<'a> Parser<'a> {
             }
 
             if token.get_type() != Alphanumeric {
-                let msg = format!("Unexpected character when parsing function declaration");
-                return Err(ParsingError::new(self.current_token, msg));
+                let msg = "Unexpected character when parsing function declaration".to_string();
+                return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &self.current_token, msg));
             }
             let argument_name = token.get_text();
+            let argument_start = token.get_file_info();
+            let type_name = self.parse_type_annotation()?;
             let argument = AstVariable {
                 name: argument_name,
+                type_name,
+                span: self.span_from(&argument_start)
             };
             let node = AstNodeType::Variable(Box::new(argument));
             arguments.push(node);
@@ -300,23 +948,35 @@ impl<'a> Parser<'a> {
                 }
             }
 
-            let msg = format!("Unexpected character when parsing function declaration arguments");
-            return Err(ParsingError::new(self.current_token, msg));
+            let msg = "Unexpected character when parsing function declaration arguments".to_string();
+            let msg = match self.peek_token() {
+                Some(next) => { self.unexpected_token_msg(msg, &next, &[",", ")"]) }
+                None => { msg }
+            };
+            return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &self.current_token, msg));
         }
 
         self.next_token();
-        let body = self.parse_block_raw()?;
+        // A loop around the declaration can not be left from inside the body
+        let loop_depth = mem::replace(&mut self.loop_depth, 0);
+        self.function_depth += 1;
+        let body = self.parse_block_raw();
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+        let body = body?;
 
         let function = AstFunctionDeclaration {
-            arguments: arguments,
-            body: body,
+            arguments,
+            body,
+            span: self.span_from(&start)
         };
         let node = AstNodeType::FunctionDeclaration(Box::new(function));
-        return Ok(node);
+        Ok(node)
     }
 
-    fn parse_struct_declaration(&mut self)  -> Result<AstNodeType, ParsingError> {
-        assert_eq!(self.current_token.get_type(), OpenBlock);
+    fn parse_struct_declaration(&mut self, name: String) -> Result<AstNodeType, ParsingError> {
+        self.expect_current(OpenBlock, "a struct declaration")?;
+        let start = self.current_token.get_file_info();
 
         let mut fields: Vec<String> = Vec::new();
         let mut types: Vec<String> = Vec::new();
@@ -327,77 +987,99 @@ impl<'a> Parser<'a> {
 
             let field_name_token = token;
             if field_name_token.get_type() != Alphanumeric {
-                let msg = format!("Unexpected character when parsing struct declaratoin, Alphanumeric expected");
-                return Err(ParsingError::new(self.current_token, msg));
+                let msg = "Unexpected character when parsing struct declaratoin, Alphanumeric expected".to_string();
+                return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &self.current_token, msg));
             }
             let field_name = field_name_token.get_text();
             fields.push(field_name);
 
-            if self.next_token().unwrap().get_type() != Symbol {
-                let msg = format!("Unexpected character when parsing struct declaration, Symbol expected");
-                return Err(ParsingError::new(self.current_token, msg));
+            if self.expect_next_token("a struct declaration")?.get_type() != Symbol {
+                let msg = "Unexpected character when parsing struct declaration, Symbol expected".to_string();
+                return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &self.current_token, msg));
             }
 
-            let field_type_token = self.next_token().unwrap();
+            let field_type_token = self.expect_next_token("a struct declaration")?;
             if field_type_token.get_type() != Alphanumeric {
-                let msg = format!("Unexpected character when parsing struct declaration, Alphanumeric expected");
-                return Err(ParsingError::new(self.current_token, msg));
+                let msg = "Unexpected character when parsing struct declaration, Alphanumeric expected".to_string();
+                return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &self.current_token, msg));
             }
             let field_type = field_type_token.get_text();
-            fields.push(field_type);
+            types.push(field_type);
         }
         let structure = AstStructDeclaration {
-            fields: fields,
-            types: types,
+            name,
+            fields,
+            types,
+            span: self.span_from(&start)
         };
         let node = AstNodeType::StructDeclaration(Box::new(structure));
-        return Ok(node);
+        Ok(node)
     }
 
-    fn parse_static_expression(&mut self) -> Result<AstNodeType, ParsingError> {
-        let token = self.current_token;
-        return match token.get_type() {
+    fn parse_static_expression(&mut self, name: &str) -> Result<AstNodeType, ParsingError> {
+        let token = self.current_token.clone();
+        match token.get_type() {
             OpenParenthesis => {
                 self.parse_function_declaration()
             }
             OpenBlock => {
-                self.parse_struct_declaration()
+                self.parse_struct_declaration(String::from(name))
             }
-            Alphanumeric | Numeric | StaticString => {
+            Alphanumeric | Numeric | StaticString | OpenBracket => {
                 self.parse_expression()
             }
             _ => {
-                let msg = format!("Invalid token in expression");
-                Err(ParsingError::new(self.current_token, msg))
+                let msg = "Invalid token in expression".to_string();
+                Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &self.current_token, msg))
             }
-        };
+        }
+    }
+
+    /// The `:Type` after a declared name, if there is one.
+    fn parse_type_annotation(&mut self) -> Result<Option<String>, ParsingError> {
+        if !self.peek_token().is_some_and(|token| token.get_type() == Symbol) {
+            return Ok(None);
+        }
+        self.next_token();
+        let type_token = self.expect_next_token("a type annotation")?;
+        if type_token.get_type() != Alphanumeric {
+            let msg = "Expected a type name after :".to_string();
+            return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &type_token, msg));
+        }
+        Ok(Some(type_token.get_text()))
     }
 
     fn parse_assignment(&mut self) -> Result<AstNodeType, ParsingError> {
-        assert_eq!(self.current_token.get_type(), Alphanumeric);
+        self.expect_current(Alphanumeric, "an assignment")?;
+        if self.peek_token().is_some_and(|token| token.get_type() == SubElement || token.get_type() == OpenBracket) {
+            return self.parse_field_assignment();
+        }
 
+        let start = self.current_token.get_file_info();
         let variable_name = self.current_token.get_text();
-        let mut variable_type: Option<String> = None;
-
-        let maybe_type_token = self.peek_token().unwrap();
-        if maybe_type_token.get_type() == Symbol {
-            variable_type = Some(maybe_type_token.get_text());
-            self.next_token();
+        if variable_name == NULL_KEYWORD || variable_name == TRUE_KEYWORD || variable_name == FALSE_KEYWORD {
+            let msg = format!("{} can not be assigned to", variable_name);
+            return Err(ParsingError::new(&error_codes::NULL_ASSIGNMENT, &self.current_token, msg));
         }
+        let variable_type = self.parse_type_annotation()?;
+        let variable_span = self.span_from(&start);
 
-        let assignment_type_token = self.next_token().unwrap();
-        return match assignment_type_token.get_type() {
+        let assignment_type_token = self.expect_next_token("an assignment")?;
+        match assignment_type_token.get_type() {
             StaticAssignment => {
                 // Struct or function
                 let variable = AstVariable {
-                    name: variable_name
+                    name: variable_name,
+                    type_name: variable_type,
+                    span: variable_span
                 };
 
                 self.next_token();
-                let expression = self.parse_static_expression()?;
+                let expression = self.parse_static_expression(&variable.name)?;
                 let alias = AstAlias {
                     to: variable,
                     from: expression,
+                    span: self.span_from(&start)
                 };
 
                 let node = AstNodeType::Alias(Box::new(alias));
@@ -406,39 +1088,88 @@ impl<'a> Parser<'a> {
             VariableAssignment => {
                 // Variable or expression
                 let variable = AstVariable {
-                    name: variable_name
+                    name: variable_name,
+                    type_name: variable_type,
+                    span: variable_span
                 };
 
                 self.next_token();
                 let expression = self.parse_expression()?;
                 let assignment = AstAssignment {
                     to: variable,
-                    from: expression
+                    from: expression,
+                    declares: false,
+                    span: self.span_from(&start)
                 };
 
                 let node = AstNodeType::Assignment(Box::new(assignment));
                 Ok(node)
             }
             _ => {
-                let msg = format!("Unexpected character when parsing an assignment");
-                Err(ParsingError::new(self.current_token, msg))
+                let msg = "Unexpected character when parsing an assignment".to_string();
+                let msg = self.unexpected_token_msg(msg, &self.current_token, &[":=", "::"]);
+                Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &self.current_token, msg))
+            }
+        }
+    }
+
+    /// `point.x = 3;`. A statement starting with a field access that turns out
+    /// not to be assigned to is parsed as an expression. Indexing can not be
+    /// assigned to.
+    fn parse_field_assignment(&mut self) -> Result<AstNodeType, ParsingError> {
+        let target = self.parse_partial_expression()?;
+        let assigned = self.peek_token().is_some_and(|token| token.get_type() == VariableAssignment);
+        match target {
+            AstNodeType::FieldAccess(access) if assigned => {
+                self.next_token();
+                self.expect_next_token("an assignment")?;
+                let from = self.parse_expression()?;
+                let assignment = AstFieldAssignment {
+                    span: CodePoint::spanning(&access.span, &self.current_token.get_file_info()),
+                    to: *access,
+                    from
+                };
+                Ok(AstNodeType::FieldAssignment(Box::new(assignment)))
             }
+            AstNodeType::Index(_) if assigned => {
+                let token = self.expect_next_token("an assignment")?;
+                let msg = "Can not assign to an index expression, use with(list, index, value) for a list with the element replaced".to_string();
+                Err(ParsingError::new(&error_codes::INDEX_ASSIGNMENT, &token, msg))
+            }
+            target => { self.parse_operator(target, 0) }
         }
     }
 
-    fn parse_function_call(&mut self) -> Result<AstNodeType, ParsingError> {
-        assert_eq!(self.current_token.get_type(), Alphanumeric);
+    /// `f(args)`, or `receiver.f(args)` which is the same call with the
+    /// receiver as the first argument.
+    fn parse_function_call(&mut self, receiver: Option<AstNodeType>) -> Result<AstNodeType, ParsingError> {
+        self.expect_current(Alphanumeric, "a function call")?;
+        let start = match receiver {
+            Some(ref receiver) => { receiver.span().clone() }
+            None => { self.current_token.get_file_info() }
+        };
         let function_name = self.current_token.get_text();
-        if let Some(function_args_start) = self.next_token() {
-            assert_eq!(self.current_token.get_type(), OpenParenthesis);
+        if self.next_token().is_some() {
+            self.expect_current(OpenParenthesis, "a function call")?;
 
-            let mut arguments: Vec<AstNodeType> = Vec::new();
+            let mut arguments: Vec<AstNodeType> = receiver.into_iter().collect();
             while let Some(token) = self.next_token() {
                 if token.get_type() == CloseParenthesis {
                     break;
                 }
 
-                let expression = self.parse_expression()?;
+                let expression = if token.get_type() == Spread {
+                    if self.next_token().is_none() {
+                        let msg = "Missing value to spread after ...".to_string();
+                        return Err(ParsingError::new(&error_codes::MISSING_OPERAND, &self.current_token, msg));
+                    }
+                    let spread_start = token.get_file_info();
+                    let value = self.parse_expression()?;
+                    let spread = AstSpread { value, span: self.span_from(&spread_start) };
+                    AstNodeType::Spread(Box::new(spread))
+                } else {
+                    self.parse_expression()?
+                };
                 arguments.push(expression);
 
                 if let Some(next) = self.peek_token() {
@@ -451,8 +1182,12 @@ impl<'a> Parser<'a> {
                     }
                 }
 
-                let msg = format!("Unexpected character when parsing function call arguments");
-                return Err(ParsingError::new(self.current_token, msg));
+                let msg = "Unexpected character when parsing function call arguments".to_string();
+                let msg = match self.peek_token() {
+                    Some(next) => { self.unexpected_token_msg(msg, &next, &[",", ")"]) }
+                    None => { msg }
+                };
+                return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &self.current_token, msg));
             }
 
             let body = match self.peek_token() {
@@ -460,8 +1195,15 @@ impl<'a> Parser<'a> {
                     match token.get_type() {
                         OpenBlock => {
                             self.next_token();
-                            let block = self.parse_block_raw()?;
-                            Some(block)
+                            let is_loop = LOOPS.contains(&&function_name[..]);
+                            if is_loop {
+                                self.loop_depth += 1;
+                            }
+                            let block = self.parse_block_raw();
+                            if is_loop {
+                                self.loop_depth -= 1;
+                            }
+                            Some(block?)
                         },
                         _ => {None}
                     }
@@ -469,118 +1211,380 @@ impl<'a> Parser<'a> {
                 None => {None}
             };
 
+            let next = if function_name == "if" && body.is_some() {
+                self.parse_else()?
+            } else {
+                None
+            };
+
             let call = AstFunctionCall {
                 name: function_name,
-                arguments: arguments,
-                body: body,
-                next: None
+                arguments,
+                body,
+                next,
+                span: self.span_from(&start)
             };
 
             let node = AstNodeType::FunctionCall(Box::new(call));
             return Ok(node);
         }
 
-        let msg = format!("Unexpected character when parsing function call");
-        return Err(ParsingError::new(self.current_token, msg));
+        let msg = "Unexpected character when parsing function call".to_string();
+        Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &self.current_token, msg))
+    }
+
+    /// Parses the `else { ... }` or `else if (...) { ... }` following the body
+    /// of an if, if there is one. A final else is a call named `else`.
+    fn parse_else(&mut self) -> Result<Option<Box<AstFunctionCall>>, ParsingError> {
+        let has_else = self.peek_token()
+            .is_some_and(|token| token.get_type() == Alphanumeric && token.get_text() == "else");
+        if !has_else {
+            return Ok(None);
+        }
+        self.next_token();
+        let start = self.current_token.get_file_info();
+        let token = self.expect_next_token("an else")?;
+
+        if token.get_type() == OpenBlock {
+            let body = self.parse_block_raw()?;
+            let call = AstFunctionCall {
+                name: String::from("else"),
+                arguments: Vec::new(),
+                body: Some(body),
+                next: None,
+                span: self.span_from(&start)
+            };
+            return Ok(Some(Box::new(call)));
+        }
+
+        if token.get_type() == Alphanumeric && token.get_text() == "if" {
+            if let AstNodeType::FunctionCall(call) = self.parse_function_call(None)? {
+                if call.body.is_some() {
+                    return Ok(Some(call));
+                }
+            }
+            let msg = "An else if needs a body".to_string();
+            return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &self.current_token, msg));
+        }
+
+        let msg = self.unexpected_token_msg("Expected a block or if after else".to_string(), &token, &["{", "if"]);
+        Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &token, msg))
     }
 
     fn parse_variable(&mut self) -> Result<AstNodeType, ParsingError> {
-        assert_eq!(self.current_token.get_type(), Alphanumeric);
+        self.expect_current(Alphanumeric, "a variable")?;
 
         let name = self.current_token.get_text();
         let variable = AstVariable {
-            name: name
+            name,
+            type_name: None,
+            span: self.current_token.get_file_info()
         };
 
         let node = AstNodeType::Variable(Box::new(variable));
-        return Ok(node);
+        Ok(node)
     }
 
     fn parse_named(&mut self) -> Result<AstNodeType, ParsingError> {
         if let Some(token) = self.peek_token() {
-            return match token.get_type() {
-                Symbol | VariableAssignment | StaticAssignment => {
+            match token.get_type() {
+                Symbol | VariableAssignment | StaticAssignment | SubElement | OpenBracket => {
                     self.parse_assignment()
                 }
-                OpenParenthesis => {
-                    self.parse_function_call()
-                }
                 _ => {
                     self.parse_expression()
                 }
-            };
+            }
         } else {
-            let msg = format!("Unexpected end of stream when parsing a variable");
-            return Err(ParsingError::new(self.current_token, msg));
+            let msg = "Unexpected end of stream when parsing a variable".to_string();
+            Err(ParsingError::new(&error_codes::UNEXPECTED_END_OF_INPUT, &self.current_token, msg))
         }
     }
 
     fn get_operator_precedence(&self, token: &Token) -> Result<usize, ParsingError> {
-        assert_eq!(token.get_type(), Operator);
-
         let precedence = match token.get_type() {
             Operator => {
                 match token.get_text().as_ref() {
-                    "+" | "-" => {
+                    "|>" => {
+                        PIPE_PRECEDENCE
+                    }
+                    "||" => {
                         1
                     }
-                    "*" | "/" | "%" => {
+                    "&&" => {
                         2
                     }
-                    "^" => {
+                    "==" | "!=" => {
                         3
                     }
+                    "<" | "<=" | ">" | ">=" => {
+                        4
+                    }
+                    "|" => {
+                        5
+                    }
+                    "&" => {
+                        6
+                    }
+                    "<<" | ">>" => {
+                        7
+                    }
+                    "+" | "-" => {
+                        8
+                    }
+                    "*" | "/" | "%" => {
+                        9
+                    }
+                    "^" => {
+                        POW_PRECEDENCE
+                    }
                     _ => {
                         let msg = format!("Invalid operator: {}", token.get_text());
-                        return Err(ParsingError::new(token, msg));
+                        return Err(ParsingError::new(&error_codes::INVALID_OPERATOR, token, msg));
                     }
                 }
             }
             OpenParenthesis => {
-                4
+                POW_PRECEDENCE + 1
             }
             _ => {
-                let msg = format!("Invalid token after operator: ");
-                return Err(ParsingError::new(token, msg));
+                let msg = "Invalid token after operator: ".to_string();
+                return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, token, msg));
             }
         };
 
-        return Ok(precedence);
+        Ok(precedence)
     }
 
-    fn parse_operator(&mut self, lhs: AstNodeType) -> Result<AstNodeType, ParsingError> {
-        let lhs_operator = self.current_token;
-        assert_eq!(lhs_operator.get_type(), Operator);
+    fn is_right_associative(&self, token: &Token) -> bool {
+        token.get_text() == "^"
+    }
 
-        if let Some(rhs_token) = self.next_token() {
-            let mut rhs = self.parse_partial_expression()?;
+    fn is_comparison(&self, token: &Token) -> bool {
+        matches!(token.get_text().as_ref(), "==" | "!=" | "<" | "<=" | ">" | ">=")
+    }
 
-            if let Some(rhs_operator) = self.peek_token() {
-                if rhs_operator.get_type() == Operator {
-                    if self.get_operator_precedence(lhs_operator)? < self.get_operator_precedence(rhs_operator)? {
-                        self.next_token();
-                        rhs = self.parse_operator(rhs)?;
-                    }
-                }
+    /// Folds the operators following `lhs` into it for as long as they bind
+    /// at least as tightly as `min_precedence`, leaving the current token on
+    /// the last token of the expression.
+    fn parse_operator(&mut self, lhs: AstNodeType, min_precedence: usize) -> Result<AstNodeType, ParsingError> {
+        let mut lhs = lhs;
+        let mut previous_comparison: Option<usize> = None;
+
+        while let Some(operator_token) = self.peek_token() {
+            if operator_token.get_type() != Operator {
+                break;
+            }
+            let precedence = self.get_operator_precedence(&operator_token)?;
+            if precedence < min_precedence {
+                break;
             }
 
-            let operator = AstOperator::from_token(lhs_operator);
+            // `a < b < c` would compare a boolean with c, which is never what
+            // was meant, so comparisons on the same level don't associate
+            if previous_comparison == Some(precedence) && self.is_comparison(&operator_token) {
+                let msg = "Comparisons can not be chained, compare each pair separately or add parentheses".to_string();
+                return Err(ParsingError::new(&error_codes::CHAINED_COMPARISON, &operator_token, msg));
+            }
+            if self.is_comparison(&operator_token) {
+                previous_comparison = Some(precedence);
+            }
+            self.next_token();
+
+            if self.next_token().is_none() {
+                let msg = "Missing rhs operand".to_string();
+                return Err(ParsingError::new(&error_codes::MISSING_OPERAND, &self.current_token, msg));
+            }
+
+            let rhs_precedence = if self.is_right_associative(&operator_token) { precedence } else { precedence + 1 };
+            let rhs = self.parse_partial_expression()?;
+            let rhs = self.parse_operator(rhs, rhs_precedence)?;
+
+            if operator_token.get_text() == PIPE_OPERATOR {
+                lhs = self.pipe_into(lhs, rhs, &operator_token)?;
+                continue;
+            }
+
+            if let Some(operator) = AstLogicalOperator::from_text(&operator_token.get_text()) {
+                let call = AstLogicalOperatorCall {
+                    span: CodePoint::spanning(lhs.span(), rhs.span()),
+                    lhs,
+                    rhs,
+                    operator
+                };
+                lhs = AstNodeType::LogicalOperatorCall(Box::new(call));
+                continue;
+            }
+
+            let operator = AstOperator::from_token(&operator_token)?;
             let call = AstOperatorCall {
-                lhs: lhs,
-                rhs: rhs,
-                operator: operator
+                span: CodePoint::spanning(lhs.span(), rhs.span()),
+                lhs,
+                rhs,
+                operator
             };
-            let node = AstNodeType::OperatorCall(Box::new(call));
-            return Ok(node);
+            lhs = AstNodeType::OperatorCall(Box::new(call));
+        }
+
+        Ok(lhs)
+    }
+
+    /// `value |> f` is parsed as `f(value)` and `value |> g(x)` as
+    /// `g(value, x)`, so pipes evaluate exactly like the nested calls.
+    fn pipe_into(&self, value: AstNodeType, target: AstNodeType, operator_token: &Token) -> Result<AstNodeType, ParsingError> {
+        match target {
+            AstNodeType::Variable(variable) => {
+                let call = AstFunctionCall {
+                    span: CodePoint::spanning(value.span(), &variable.span),
+                    name: variable.name,
+                    arguments: vec![value],
+                    body: None,
+                    next: None
+                };
+                Ok(AstNodeType::FunctionCall(Box::new(call)))
+            }
+            AstNodeType::FunctionCall(mut call) => {
+                call.span = CodePoint::spanning(value.span(), &call.span);
+                call.arguments.insert(0, value);
+                Ok(AstNodeType::FunctionCall(call))
+            }
+            _ => {
+                let msg = format!("The right side of {} has to be a function or a call", PIPE_OPERATOR);
+                Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, operator_token, msg))
+            }
+        }
+    }
+
+    /// Continues a statement that starts with `first` as a comma separated
+    /// tuple, or as the names of a destructuring assignment.
+    fn parse_value_list(&mut self, first: AstNodeType) -> Result<AstNodeType, ParsingError> {
+        let is_list = self.peek_token().is_some_and(|token| token.get_type() == ParameterDivider);
+        let is_assignment = matches!(first, AstNodeType::Assignment(_) | AstNodeType::Alias(_));
+        if !is_list || is_assignment {
+            return Ok(first);
+        }
+
+        let mut values = vec![first];
+        self.parse_more_values(&mut values)?;
+
+        let declares = match self.peek_token().map(|token| token.get_type()) {
+            Some(StaticAssignment) => { true }
+            Some(VariableAssignment) => { false }
+            _ => {
+                return Ok(tuple(values));
+            }
+        };
+        self.next_token();
+
+        let mut to = Vec::new();
+        for value in values {
+            match value {
+                AstNodeType::Variable(variable) => { to.push(*variable); }
+                _ => {
+                    let msg = format!("Only names can be assigned to with {}", self.current_token.get_text());
+                    return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &self.current_token, msg));
+                }
+            }
+        }
+
+        self.expect_next_token("a destructuring assignment")?;
+        let from = self.parse_values()?;
+        let destructure = AstDestructure {
+            span: CodePoint::spanning(&to[0].span, from.span()),
+            to,
+            from,
+            declares
+        };
+        Ok(AstNodeType::Destructure(Box::new(destructure)))
+    }
+
+    /// One expression, or a tuple of several separated by commas.
+    fn parse_values(&mut self) -> Result<AstNodeType, ParsingError> {
+        let mut values = vec![self.parse_expression()?];
+        self.parse_more_values(&mut values)?;
+        if values.len() == 1 {
+            return Ok(values.remove(0));
+        }
+        Ok(tuple(values))
+    }
+
+    /// `let x = value;`, which shadows any `x` of an outer scope instead of
+    /// assigning to it.
+    fn parse_let(&mut self) -> Result<AstNodeType, ParsingError> {
+        let start = self.current_token.get_file_info();
+        let name = self.expect_next_token("a let declaration")?;
+        let assigned = self.peek_token().is_some_and(|token| token.get_type() == VariableAssignment || token.get_type() == Symbol);
+        if !assigned {
+            let msg = "Expected = after the name declared by let".to_string();
+            return Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &name, msg));
+        }
+
+        match self.parse_assignment()? {
+            AstNodeType::Assignment(mut assignment) => {
+                assignment.declares = true;
+                assignment.span = CodePoint::spanning(&start, &assignment.span);
+                Ok(AstNodeType::Assignment(assignment))
+            }
+            _ => {
+                let msg = "let declares variables with =, functions and structs are declared with ::".to_string();
+                Err(ParsingError::new(&error_codes::UNEXPECTED_TOKEN, &name, msg))
+            }
+        }
+    }
+
+    fn parse_return(&mut self) -> Result<AstNodeType, ParsingError> {
+        let start = self.current_token.get_file_info();
+        if self.function_depth == 0 {
+            let msg = "return can only be used inside a function".to_string();
+            return Err(ParsingError::new(&error_codes::RETURN_OUTSIDE_FUNCTION, &self.current_token, msg));
+        }
+
+        let has_value = self.peek_token().is_some_and(|token| token.get_type() != EndOfStatement);
+        let value = if has_value {
+            self.expect_next_token("a return")?;
+            Some(self.parse_values()?)
+        } else {
+            None
+        };
+        Ok(AstNodeType::Return(Box::new(AstReturn { value, span: self.span_from(&start) })))
+    }
+
+    fn parse_loop_control(&mut self) -> Result<AstNodeType, ParsingError> {
+        let keyword = self.current_token.get_text();
+        if self.loop_depth == 0 {
+            let msg = format!("{} can only be used inside a loop", keyword);
+            return Err(ParsingError::new(&error_codes::LOOP_CONTROL_OUTSIDE_LOOP, &self.current_token, msg));
+        }
+
+        let span = self.current_token.get_file_info();
+        if keyword == BREAK_KEYWORD {
+            return Ok(AstNodeType::Break(Box::new(AstBreak { span })));
         }
+        Ok(AstNodeType::Continue(Box::new(AstContinue { span })))
+    }
 
-        let msg = format!("Missing rhs operand");
-        return Err(ParsingError::new(self.current_token, msg));
+    fn parse_more_values(&mut self, values: &mut Vec<AstNodeType>) -> Result<(), ParsingError> {
+        while self.peek_token().is_some_and(|token| token.get_type() == ParameterDivider) {
+            self.next_token();
+            self.expect_next_token("a list of values")?;
+            values.push(self.parse_expression()?);
+        }
+        Ok(())
     }
 
     fn parse_statement(&mut self) -> Result<AstNodeType, ParsingError> {
         if let Some(token) = self.next_token() {
-            let mut evaluatable = match token.get_type() {
+            let evaluatable = match token.get_type() {
+                Alphanumeric if token.get_text() == RETURN_KEYWORD => {
+                    self.parse_return()
+                }
+                Alphanumeric if token.get_text() == BREAK_KEYWORD || token.get_text() == CONTINUE_KEYWORD => {
+                    self.parse_loop_control()
+                }
+                Alphanumeric if token.get_text() == LET_KEYWORD
+                    && self.peek_token().is_some_and(|next| next.get_type() == Alphanumeric) => {
+                    self.parse_let()
+                }
                 Alphanumeric => {
                     self.parse_named()
                 }
@@ -588,40 +1592,45 @@ impl<'a> Parser<'a> {
                     return self.parse_block();
                 }
                 EndOfStatement => {
-                    let null = AstNullValue {};
+                    let null = AstNullValue { span: token.get_file_info() };
                     return Ok(AstNodeType::NullValue(Box::new(null)));
                 }
                 _ => {
                     self.parse_expression()
                 }
             }?;
+            let evaluatable = self.parse_value_list(evaluatable)?;
 
-            if let Some(token) = self.next_token() {
-                if token.get_type() == Operator {
-                    evaluatable = self.parse_operator(evaluatable)?;
-                }
+            // The last statement of a block may leave out its ;, which reads
+            // well when the block is used for its value: if (a) { 1 } else { 2 }
+            if self.peek_token().is_some_and(|token| token.get_type() == CloseBlock) {
+                return Ok(evaluatable);
+            }
 
+            if let Some(token) = self.next_token() {
                 match token.get_type() {
                     EndOfStatement => {
                         return Ok(evaluatable);
                     }
                     _ => {
-                        let msg = format!("Statements must end with a ; token");
-                        return Err(ParsingError::new(self.current_token, msg));
+                        let msg = "Statements must end with a ; token".to_string();
+                        let msg = self.unexpected_token_msg(msg, &self.current_token, &[";"]);
+                        return Err(ParsingError::new(&error_codes::MISSING_SEMICOLON, &self.current_token, msg));
                     }
                 }
             }
         }
 
-        let msg = format!("Unexpected end of stream when parsing statement");
-        return Err(ParsingError::new(self.current_token, msg));
+        let msg = "Unexpected end of stream when parsing statement".to_string();
+        Err(ParsingError::new(&error_codes::UNEXPECTED_END_OF_INPUT, &self.current_token, msg))
     }
 
     fn parse_block_raw(&mut self) -> Result<AstBlock, ParsingError> {
         if !self.current_token.is_null() {
-            assert_eq!(self.current_token.get_type(), OpenBlock);
+            self.expect_current(OpenBlock, "a block")?;
         }
-        let mut block = AstBlock::new();
+        let start = self.current_token.get_file_info();
+        let mut block = AstBlock::new(start.clone());
 
         while let Some(token) = self.peek_token() {
             if token.get_type() == CloseBlock {
@@ -629,43 +1638,98 @@ impl<'a> Parser<'a> {
                 break;
             }
 
-            if token.get_type() == Comment {
-                self.next_token();
-                continue;
-            }
+            let first_token = token;
+            let unreadable_tokens = self.unreadable_tokens;
+            let evaluatable = match self.parse_statement() {
+                Ok(evaluatable) => { evaluatable }
+                Err(error) => {
+                    // A token missing from the statement already explains why it failed
+                    if self.unreadable_tokens == unreadable_tokens {
+                        self.errors.push(error);
+                    }
+                    if self.synchronize() {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            let statement = AstStatement {
+                node: evaluatable,
+                file_info: CodePoint::spanning(&first_token.get_file_info(), &self.current_token.get_file_info())
+            };
+            block.statements.push(statement);
+        }
+        block.span = self.span_from(&start);
+        Ok(block)
+    }
 
-            let evaluatable = self.parse_statement()?;
-            block.statements.push(evaluatable);
+    /// Skips the rest of a statement that failed to parse, up to and
+    /// including its `;`, so parsing can go on with the next statement.
+    /// Returns true when the `}` ending the current block was skipped too.
+    fn synchronize(&mut self) -> bool {
+        let mut depth = 0;
+        match self.current_token.get_type() {
+            EndOfStatement => { return false; }
+            CloseBlock => { return true; }
+            OpenBlock => { depth = 1; }
+            _ => {}
         }
-        return Ok(block);
+
+        while let Some(token) = self.peek_token() {
+            match token.get_type() {
+                OpenBlock => { depth += 1; }
+                CloseBlock if depth == 0 => { return false; }
+                CloseBlock => { depth -= 1; }
+                EndOfStatement if depth == 0 => {
+                    self.next_token();
+                    return false;
+                }
+                _ => {}
+            }
+            self.next_token();
+        }
+        false
     }
 
     fn parse_block(&mut self) -> Result<AstNodeType, ParsingError> {
         let block = self.parse_block_raw()?;
         let node = AstNodeType::Block(Box::new(block));
-        return Ok(node);
+        Ok(node)
     }
 
-    fn parse(&mut self) -> Result<Ast, ParsingError> {
-        let root = self.parse_block()?;
-        let ast = Ast {
-            root: root
-        };
-
-        return Ok(ast);
+    fn parse(&mut self) -> Result<Ast, Vec<ParsingError>> {
+        let root = self.parse_block();
+        match root {
+            Ok(root) if self.errors.is_empty() => { Ok(Ast { root }) }
+            Ok(_) => { Err(mem::take(&mut self.errors)) }
+            Err(error) => {
+                self.errors.push(error);
+                Err(mem::take(&mut self.errors))
+            }
+        }
     }
 }
 
-pub fn parse(tokens: &Vec<Token>) -> Result<Ast, ParsingError> {
-    let null_token = Token::null();
-
-    let mut iter = tokens.iter().peekable();
+/// `a, b, c`, spanning the code from the first value to the last.
+fn tuple(values: Vec<AstNodeType>) -> AstNodeType {
+    let span = CodePoint::spanning(values[0].span(), values[values.len() - 1].span());
+    AstNodeType::Tuple(Box::new(AstTuple { values, span }))
+}
 
+/// Parses every statement it can, skipping those that fail to parse, and
+/// returns the errors of all of them in order when any did. Tokens are read
+/// as they are needed, so a `Tokenizer` can be passed as is; tokens it could
+/// not read are reported like statements that failed to parse.
+pub fn parse<T, E>(tokens: T) -> Result<Ast, Vec<ParsingError>>
+    where T: IntoIterator<Item = Result<Token, E>>, E: Into<ParsingError> {
     let mut parser = Parser {
-        token_buffer: Vec::new(),
-        token_stream: iter,
-        current_token: &null_token
+        token_stream: tokens.into_iter().peekable(),
+        current_token: Token::null(),
+        function_depth: 0,
+        loop_depth: 0,
+        errors: Vec::new(),
+        unreadable_tokens: 0
     };
-    return parser.parse();
+    parser.parse()
 }
 
@@ -0,0 +1,253 @@
+use std::rc::Rc;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+
+type Link<T> = Option<Rc<Node<T>>>;
+
+/// A node of an AVL tree ordered by position. Nodes are never changed once
+/// built, so every version of a collection shares all the nodes an update
+/// did not touch.
+struct Node<T> {
+    value: T,
+    left: Link<T>,
+    right: Link<T>,
+    height: usize,
+    size: usize
+}
+
+fn height<T>(link: &Link<T>) -> usize {
+    link.as_ref().map_or(0, |node| node.height)
+}
+
+fn size<T>(link: &Link<T>) -> usize {
+    link.as_ref().map_or(0, |node| node.size)
+}
+
+fn node<T>(left: Link<T>, value: T, right: Link<T>) -> Rc<Node<T>> {
+    Rc::new(Node {
+        height: 1 + height(&left).max(height(&right)),
+        size: 1 + size(&left) + size(&right),
+        value,
+        left,
+        right
+    })
+}
+
+/// Builds a node from subtrees whose heights differ by at most two,
+/// rotating to restore the AVL balance.
+fn balance<T: Clone>(left: Link<T>, value: T, right: Link<T>) -> Rc<Node<T>> {
+    if height(&left) > height(&right) + 1 {
+        if let Some(ref l) = left {
+            if height(&l.left) >= height(&l.right) {
+                return node(l.left.clone(), l.value.clone(), Some(node(l.right.clone(), value, right)));
+            }
+            if let Some(ref lr) = l.right {
+                let new_left = node(l.left.clone(), l.value.clone(), lr.left.clone());
+                let new_right = node(lr.right.clone(), value, right);
+                return node(Some(new_left), lr.value.clone(), Some(new_right));
+            }
+        }
+    } else if height(&right) > height(&left) + 1 {
+        if let Some(ref r) = right {
+            if height(&r.right) >= height(&r.left) {
+                return node(Some(node(left, value, r.left.clone())), r.value.clone(), r.right.clone());
+            }
+            if let Some(ref rl) = r.left {
+                let new_left = node(left, value, rl.left.clone());
+                let new_right = node(rl.right.clone(), r.value.clone(), r.right.clone());
+                return node(Some(new_left), rl.value.clone(), Some(new_right));
+            }
+        }
+    }
+    node(left, value, right)
+}
+
+fn get_at<T>(link: &Link<T>, index: usize) -> Option<&T> {
+    let node = link.as_ref()?;
+    let left_size = size(&node.left);
+    match index.cmp(&left_size) {
+        Ordering::Less => { get_at(&node.left, index) }
+        Ordering::Equal => { Some(&node.value) }
+        Ordering::Greater => { get_at(&node.right, index - left_size - 1) }
+    }
+}
+
+fn insert_at<T: Clone>(link: &Link<T>, index: usize, value: T) -> Rc<Node<T>> {
+    match *link {
+        None => { node(None, value, None) }
+        Some(ref node) => {
+            let left_size = size(&node.left);
+            if index <= left_size {
+                balance(Some(insert_at(&node.left, index, value)), node.value.clone(), node.right.clone())
+            } else {
+                balance(node.left.clone(), node.value.clone(), Some(insert_at(&node.right, index - left_size - 1, value)))
+            }
+        }
+    }
+}
+
+fn set_at<T: Clone>(link: &Link<T>, index: usize, value: T) -> Link<T> {
+    let node = link.as_ref()?;
+    let left_size = size(&node.left);
+    match index.cmp(&left_size) {
+        Ordering::Less => { Some(self::node(set_at(&node.left, index, value), node.value.clone(), node.right.clone())) }
+        Ordering::Equal => { Some(self::node(node.left.clone(), value, node.right.clone())) }
+        Ordering::Greater => {
+            Some(self::node(node.left.clone(), node.value.clone(), set_at(&node.right, index - left_size - 1, value)))
+        }
+    }
+}
+
+fn remove_at<T: Clone>(link: &Link<T>, index: usize) -> Link<T> {
+    let node = link.as_ref()?;
+    let left_size = size(&node.left);
+    match index.cmp(&left_size) {
+        Ordering::Less => { Some(balance(remove_at(&node.left, index), node.value.clone(), node.right.clone())) }
+        Ordering::Greater => {
+            Some(balance(node.left.clone(), node.value.clone(), remove_at(&node.right, index - left_size - 1)))
+        }
+        Ordering::Equal => {
+            match get_at(&node.right, 0) {
+                Some(first) => { Some(balance(node.left.clone(), first.clone(), remove_at(&node.right, 0))) }
+                None => { node.left.clone() }
+            }
+        }
+    }
+}
+
+fn collect<'a, T>(link: &'a Link<T>, values: &mut Vec<&'a T>) {
+    if let Some(ref node) = *link {
+        collect(&node.left, values);
+        values.push(&node.value);
+        collect(&node.right, values);
+    }
+}
+
+/// An immutable list. Updates return a new list in O(log n) and leave the
+/// original untouched.
+#[derive(Clone)]
+pub struct PersistentVec<T> {
+    root: Link<T>
+}
+
+impl<T: Clone> PersistentVec<T> {
+    pub fn new() -> PersistentVec<T> {
+        PersistentVec { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        get_at(&self.root, index)
+    }
+
+    pub fn push(&self, value: T) -> PersistentVec<T> {
+        self.insert(self.len(), value)
+    }
+
+    /// `index` may be at most `len()`.
+    pub fn insert(&self, index: usize, value: T) -> PersistentVec<T> {
+        PersistentVec { root: Some(insert_at(&self.root, index, value)) }
+    }
+
+    /// Returns None when `index` is out of bounds.
+    pub fn set(&self, index: usize, value: T) -> Option<PersistentVec<T>> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(PersistentVec { root: set_at(&self.root, index, value) })
+    }
+
+    /// Returns None when `index` is out of bounds.
+    pub fn remove(&self, index: usize) -> Option<PersistentVec<T>> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(PersistentVec { root: remove_at(&self.root, index) })
+    }
+
+    pub fn iter(&self) -> ::std::vec::IntoIter<&T> {
+        let mut values = Vec::with_capacity(self.len());
+        collect(&self.root, &mut values);
+        values.into_iter()
+    }
+}
+
+impl<T: Clone + Debug> Debug for PersistentVec<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// An immutable map, kept as a list of entries sorted by key.
+#[derive(Clone)]
+pub struct PersistentMap<K, V> {
+    entries: PersistentVec<(K, V)>
+}
+
+impl<K: Ord + Clone, V: Clone> PersistentMap<K, V> {
+    pub fn new() -> PersistentMap<K, V> {
+        PersistentMap { entries: PersistentVec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The index of `key` among the entries, or where it would be inserted.
+    fn position(&self, key: &K) -> Result<usize, usize> {
+        let (mut low, mut high) = (0, self.entries.len());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match self.entries.get(middle).map(|entry| entry.0.cmp(key)) {
+                Some(Ordering::Less) => { low = middle + 1; }
+                Some(Ordering::Greater) => { high = middle; }
+                Some(Ordering::Equal) => { return Ok(middle); }
+                None => { break; }
+            }
+        }
+        Err(low)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let index = self.position(key).ok()?;
+        self.entries.get(index).map(|entry| &entry.1)
+    }
+
+    pub fn insert(&self, key: K, value: V) -> PersistentMap<K, V> {
+        let entries = match self.position(&key) {
+            Ok(index) => { self.entries.set(index, (key, value)) }
+            Err(index) => { Some(self.entries.insert(index, (key, value))) }
+        };
+        PersistentMap { entries: entries.unwrap_or_else(|| self.entries.clone()) }
+    }
+
+    /// Returns the map unchanged when it has no entry for `key`.
+    pub fn remove(&self, key: &K) -> PersistentMap<K, V> {
+        let entries = match self.position(key) {
+            Ok(index) => { self.entries.remove(index) }
+            Err(_) => { None }
+        };
+        PersistentMap { entries: entries.unwrap_or_else(|| self.entries.clone()) }
+    }
+
+    /// The entry at `index` in key order.
+    pub fn entry(&self, index: usize) -> Option<&(K, V)> {
+        self.entries.get(index)
+    }
+
+    /// The entries in key order.
+    pub fn iter(&self) -> ::std::vec::IntoIter<&(K, V)> {
+        self.entries.iter()
+    }
+}
+
+impl<K: Ord + Clone + Debug, V: Clone + Debug> Debug for PersistentMap<K, V> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter().map(|entry| (&entry.0, &entry.1))).finish()
+    }
+}
@@ -0,0 +1,40 @@
+use tokenizer::{Token, TokenType};
+use interp::InterpError;
+use error_codes;
+
+const PRAGMA_PREFIX: &str = "#pragma";
+
+/// Script wide settings, each given on its own `#pragma name` line.
+#[derive(Default)]
+pub struct Pragmas {
+    /// Evaluate number literals and arithmetic with arbitrary precision.
+    pub bignum: bool,
+    /// Run in the strict dialect, as with `--strict`.
+    pub strict: bool
+}
+
+/// Collects the pragmas of a script. The tokenizer keeps `#` lines as
+/// comments, so they are found among the comment tokens.
+pub fn read_pragmas(tokens: &[Token]) -> Result<Pragmas, InterpError> {
+    let mut pragmas = Pragmas::default();
+
+    for token in tokens {
+        if token.get_type() != TokenType::Comment {
+            continue;
+        }
+        let text = token.get_text();
+        if !text.starts_with(PRAGMA_PREFIX) {
+            continue;
+        }
+
+        match text[PRAGMA_PREFIX.len()..].trim() {
+            "bignum" => { pragmas.bignum = true; }
+            "strict" => { pragmas.strict = true; }
+            other => {
+                let msg = format!("Unknown pragma {}, expected bignum or strict", other);
+                return Err(InterpError::new(&error_codes::UNKNOWN_PRAGMA, msg));
+            }
+        }
+    }
+    Ok(pragmas)
+}
@@ -0,0 +1,111 @@
+use interp::InterpHook;
+use parser::AstStatement;
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+const ROOT_FRAME: &str = "main";
+
+struct ActiveCall {
+    name: String,
+    started: Instant,
+    in_callees: Duration
+}
+
+#[derive(Default)]
+struct FunctionTiming {
+    calls: usize,
+    total: Duration,
+    own: Duration
+}
+
+/// Samples the leg call stack every `interval` executed statements, which
+/// makes samples proportional to interpreter work rather than wall time, and
+/// times every function call on the side.
+pub struct Profiler {
+    interval: usize,
+    steps: usize,
+    stack: Vec<ActiveCall>,
+    samples: BTreeMap<String, usize>,
+    timings: BTreeMap<String, FunctionTiming>
+}
+
+impl Profiler {
+    pub fn new(interval: usize) -> Profiler {
+        Profiler {
+            interval: interval.max(1),
+            steps: 0,
+            stack: Vec::new(),
+            samples: BTreeMap::new(),
+            timings: BTreeMap::new()
+        }
+    }
+
+    fn sample(&mut self) {
+        let mut folded = String::from(ROOT_FRAME);
+        for call in &self.stack {
+            folded.push(';');
+            folded.push_str(&call.name);
+        }
+        *self.samples.entry(folded).or_insert(0) += 1;
+    }
+
+    /// One line per distinct call stack with its sample count, the folded
+    /// format read by flamegraph.pl and inferno.
+    pub fn folded_stacks(&self) -> String {
+        let mut folded = String::new();
+        for (stack, count) in &self.samples {
+            folded.push_str(&format!("{} {}\n", stack, count));
+        }
+        folded
+    }
+
+    /// Calls and time per function, most expensive first. Total time counts
+    /// recursive calls once, own time excludes time spent in callees.
+    pub fn timing_report(&self) -> String {
+        let mut timings: Vec<(&String, &FunctionTiming)> = self.timings.iter().collect();
+        timings.sort_by_key(|timing| Reverse(timing.1.total));
+
+        let mut report = format!("{:<24} {:>8} {:>12} {:>12}\n", "function", "calls", "total ms", "own ms");
+        for (name, timing) in timings {
+            report.push_str(&format!("{:<24} {:>8} {:>12.3} {:>12.3}\n", name, timing.calls,
+                                     timing.total.as_secs_f64() * 1000.0, timing.own.as_secs_f64() * 1000.0));
+        }
+        report
+    }
+}
+
+impl InterpHook for Profiler {
+    fn before_statement(&mut self, _statement: &AstStatement) {
+        self.steps += 1;
+        if self.steps.is_multiple_of(self.interval) {
+            self.sample();
+        }
+    }
+
+    fn enter_function(&mut self, name: &str) {
+        self.stack.push(ActiveCall {
+            name: String::from(name),
+            started: Instant::now(),
+            in_callees: Duration::new(0, 0)
+        });
+    }
+
+    fn exit_function(&mut self, _name: &str) {
+        if let Some(call) = self.stack.pop() {
+            let elapsed = call.started.elapsed();
+            let recursive = self.stack.iter().any(|caller| caller.name == call.name);
+            if let Some(caller) = self.stack.last_mut() {
+                caller.in_callees += elapsed;
+            }
+
+            let timing = self.timings.entry(call.name).or_default();
+            timing.calls += 1;
+            timing.own += elapsed.checked_sub(call.in_callees).unwrap_or_default();
+            if !recursive {
+                timing.total += elapsed;
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::f64::consts::PI;
+
+/// A small seedable generator (xorshift64*). Fast and reproducible for a
+/// given seed, which is what games and tests need, but not suitable for
+/// anything security related.
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // Spread the seed with splitmix64, xorshift state must not be zero
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        Rng {
+            state: if z == 0 { 1 } else { z }
+        }
+    }
+
+    pub fn from_time() -> Rng {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or(0);
+        Rng::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in [low, high).
+    pub fn range(&mut self, low: f64, high: f64) -> f64 {
+        low + (high - low) * self.next_f64()
+    }
+
+    /// Uniform over `low..=high`, without the bias of a plain modulo.
+    pub fn range_int(&mut self, low: i64, high: i64) -> i64 {
+        let span = (high as i128 - low as i128 + 1) as u128;
+        if span > u64::MAX as u128 {
+            return self.next_u64() as i64;
+        }
+        let span = span as u64;
+        let limit = u64::MAX - u64::MAX % span;
+
+        loop {
+            let value = self.next_u64();
+            if value < limit {
+                return (low as i128 + (value % span) as i128) as i64;
+            }
+        }
+    }
+
+    /// Normally distributed, by the Box-Muller transform.
+    pub fn normal(&mut self, mean: f64, sd: f64) -> f64 {
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        let z = (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos();
+        mean + sd * z
+    }
+}
@@ -0,0 +1,188 @@
+use tokenizer::{Tokenizer, Token, TokenType};
+use parser::{parse, Ast};
+use interp::{InterpValue, BUILTINS};
+use engine::Engine;
+use format;
+use diagnostic::{Diagnostic, render_interp_error};
+
+use rustyline::{Editor, Helper, Context};
+use rustyline::completion::Completer;
+use rustyline::hint::Hinter;
+use rustyline::highlight::Highlighter;
+use rustyline::validate::Validator;
+use rustyline::history::DefaultHistory;
+use rustyline::error::ReadlineError;
+
+use std::env;
+use std::iter;
+use std::path::PathBuf;
+use std::cell::RefCell;
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = ".. ";
+/// What errors in an entry name the file they are in.
+pub const REPL_FILE_NAME: &str = "<repl>";
+
+struct ReplHelper {
+    names: Vec<String>
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].char_indices().rev()
+            .take_while(|&(_, c)| c.is_alphanumeric() || c == '_')
+            .last()
+            .map(|(index, _)| index)
+            .unwrap_or(pos);
+
+        let prefix = &line[start..pos];
+        let mut candidates: Vec<String> = self.names.iter()
+            .filter(|name| name.starts_with(prefix))
+            .cloned()
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".leg_history"))
+}
+
+/// Input is incomplete while it has more opening than closing braces,
+/// brackets or parentheses, in which case the prompt continues on the next line.
+pub fn is_incomplete(source: &str) -> bool {
+    let mut depth: isize = 0;
+    for token in Tokenizer::new(source) {
+        let token = match token {
+            Ok(token) => { token }
+            Err(_) => { return false; }
+        };
+        match token.get_type() {
+            TokenType::OpenBlock | TokenType::OpenBracket | TokenType::OpenParenthesis => { depth += 1; }
+            TokenType::CloseBlock | TokenType::CloseBracket | TokenType::CloseParenthesis => { depth -= 1; }
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+/// Parses one complete REPL entry. The trailing `;` of the last statement is
+/// optional, which keeps quick expressions like `1 + 2` short to type.
+pub fn parse_entry(source: &str) -> Result<Ast, String> {
+    // The last token read that is not a comment, which the `;` goes after
+    // unless it is one
+    let last = RefCell::new(None);
+    let tokens = Tokenizer::new(source).inspect(|token| {
+        if let Ok(token) = token {
+            if token.get_type() != TokenType::Comment {
+                *last.borrow_mut() = Some(token.clone());
+            }
+        }
+    });
+    let end = iter::once(()).filter_map(|_| match *last.borrow() {
+        Some(ref token) if token.get_type() != TokenType::EndOfStatement => {
+            Some(Ok(Token::end_of_statement_after(token)))
+        }
+        _ => { None }
+    });
+
+    parse(tokens.chain(end)).map_err(|errors| {
+        errors.iter().map(|error| Diagnostic::from(error).render(source, REPL_FILE_NAME)).collect::<String>()
+    })
+}
+
+fn read_entry(editor: &mut Editor<ReplHelper, DefaultHistory>) -> Result<String, ReadlineError> {
+    let mut source = String::new();
+    let mut prompt = PROMPT;
+
+    loop {
+        let line = editor.readline(prompt)?;
+        source.push_str(&line);
+        source.push('\n');
+
+        if !is_incomplete(&source) {
+            return Ok(source);
+        }
+        prompt = CONTINUATION_PROMPT;
+    }
+}
+
+pub fn run_repl() {
+    let mut editor: Editor<ReplHelper, DefaultHistory> = match Editor::new() {
+        Ok(editor) => { editor }
+        Err(error) => {
+            eprintln!("Failed to start the REPL: {}", error);
+            return;
+        }
+    };
+    editor.set_helper(Some(ReplHelper { names: Vec::new() }));
+
+    let history = history_path();
+    if let Some(ref path) = history {
+        // A missing history file just means this is the first session
+        let _ = editor.load_history(path);
+    }
+
+    let mut engine = Engine::new();
+
+    loop {
+        if let Some(helper) = editor.helper_mut() {
+            let mut names = engine.visible_names();
+            names.extend(BUILTINS.iter().map(|name| String::from(*name)));
+            helper.names = names;
+        }
+
+        let source = match read_entry(&mut editor) {
+            Ok(source) => { source }
+            Err(ReadlineError::Interrupted) => { continue; }
+            Err(ReadlineError::Eof) => { break; }
+            Err(error) => {
+                eprintln!("{}", error);
+                break;
+            }
+        };
+
+        if source.trim().is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(source.trim_end());
+
+        match parse_entry(&source) {
+            Ok(ast) => {
+                match engine.eval_ast(ast) {
+                    Ok(InterpValue::InterpVoid) => {}
+                    Ok(value) => {
+                        println!("{}", format::format_debug(value));
+                    }
+                    Err(error) => {
+                        eprint!("{}", render_interp_error(&error, &source, REPL_FILE_NAME));
+                    }
+                }
+            }
+            Err(error) => {
+                eprint!("{}", error);
+            }
+        }
+    }
+
+    if let Some(ref path) = history {
+        if let Err(error) = editor.save_history(path) {
+            eprintln!("Failed to save history: {}", error);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::collections::VecDeque;
+
+/// Builtins whose results depend on something outside the script, which
+/// are the ones recorded and replayed.
+pub const NONDETERMINISTIC_BUILTINS: [&str; 9] = ["random", "rand_int", "rand_normal", "random_range", "shuffle", "choice",
+                                                   "now", "timer", "elapsed"];
+
+/// The result one nondeterministic builtin call produced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Effect {
+    pub builtin: String,
+    pub value: f64
+}
+
+pub enum EffectMode {
+    Live,
+    Record(Vec<Effect>),
+    /// Results still to be handed out, in call order.
+    Replay(VecDeque<Effect>)
+}
+
+/// One effect per line as `builtin value`. Values are written with enough
+/// digits to read back exactly the same number.
+pub fn write_log(effects: &[Effect]) -> String {
+    let mut log = String::new();
+    for effect in effects {
+        log.push_str(&format!("{} {:?}\n", effect.builtin, effect.value));
+    }
+    log
+}
+
+pub fn read_log(log: &str) -> Result<Vec<Effect>, String> {
+    let mut effects = Vec::new();
+    for (index, line) in log.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let builtin = parts.next().unwrap_or("");
+        let value = match parts.next().map(|value| value.parse::<f64>()) {
+            Some(Ok(value)) if parts.next().is_none() => { value }
+            _ => { return Err(format!("Invalid replay log entry on line {}: {}", index + 1, line)); }
+        };
+        effects.push(Effect { builtin: String::from(builtin), value });
+    }
+    Ok(effects)
+}
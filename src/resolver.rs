@@ -0,0 +1,138 @@
+use parser::{Ast, AstNodeType, AstBlock, AstStatement, AstFunctionCall, AstFunctionDeclaration, AstVariable, AstAssignment,
+             AstAlias, AstDestructure};
+use visitor::{Visitor, walk_block, walk_function_call};
+use diagnostic::{Diagnostic, Severity};
+use interp::BUILTINS;
+use file_info::CodePoint;
+use error_codes;
+use suggest;
+
+/// Finds the names a script uses without declaring them in any scope
+/// around the use. A scope sees every name declared anywhere in it, not only
+/// above the use, since a function may call one declared after it.
+struct Resolver {
+    scopes: Vec<Vec<String>>,
+    statement: Option<CodePoint>,
+    diagnostics: Vec<Diagnostic>
+}
+
+/// The names the statements declare in the scope they run in, with `=`,
+/// `::`, `let` or a destructuring assignment.
+fn declared_names(statements: &[AstStatement]) -> Vec<String> {
+    let mut names = Vec::new();
+    for statement in statements {
+        match statement.node {
+            AstNodeType::Assignment(ref assignment) => { names.push(assignment.to.name.clone()); }
+            AstNodeType::Alias(ref alias) => { names.push(alias.to.name.clone()); }
+            AstNodeType::Destructure(ref destructure) => {
+                names.extend(destructure.to.iter().map(|variable| variable.name.clone()));
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+impl Resolver {
+    fn is_declared(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.iter().any(|declared| declared == name))
+    }
+
+    fn check(&mut self, kind: &str, name: &str) {
+        if self.is_declared(name) {
+            return;
+        }
+
+        let mut candidates: Vec<&str> = self.scopes.iter().flat_map(|scope| scope.iter().map(|name| &name[..])).collect();
+        if kind == "function" {
+            candidates.extend(BUILTINS.iter().cloned());
+        }
+        let msg = format!("Unable to find {} {}", kind, name);
+        let msg = suggest::with_suggestion(msg, name, candidates);
+        let file_info = match self.statement {
+            Some(ref file_info) => { file_info.clone() }
+            None => { return; }
+        };
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            code: &error_codes::UNKNOWN_NAME,
+            message: msg,
+            file_info
+        });
+    }
+
+    fn visit_scope(&mut self, mut names: Vec<String>, block: &AstBlock) {
+        names.extend(declared_names(&block.statements));
+        self.scopes.push(names);
+        walk_block(self, block);
+        self.scopes.pop();
+    }
+}
+
+impl Visitor for Resolver {
+    fn visit_statement(&mut self, statement: &AstStatement) {
+        let outer = self.statement.replace(statement.file_info.clone());
+        self.visit_node(&statement.node);
+        self.statement = outer;
+    }
+
+    fn visit_block(&mut self, block: &AstBlock) {
+        self.visit_scope(Vec::new(), block);
+    }
+
+    fn visit_function_declaration(&mut self, dec: &AstFunctionDeclaration) {
+        let mut parameters = Vec::new();
+        for argument in &dec.arguments {
+            if let AstNodeType::Variable(ref variable) = *argument {
+                parameters.push(variable.name.clone());
+            }
+        }
+        self.visit_scope(parameters, &dec.body);
+    }
+
+    /// The first argument of a `for` is the loop variable, declared in the
+    /// scope of the body.
+    fn visit_function_call(&mut self, call: &AstFunctionCall) {
+        if call.name == "for" {
+            if let (Some(AstNodeType::Variable(variable)), Some(body)) = (call.arguments.first(), call.body.as_ref()) {
+                for argument in &call.arguments[1..] {
+                    self.visit_node(argument);
+                }
+                self.visit_scope(vec![variable.name.clone()], body);
+                return;
+            }
+        }
+        if call.name != "else" && !BUILTINS.contains(&&call.name[..]) {
+            self.check("function", &call.name);
+        }
+        walk_function_call(self, call);
+    }
+
+    fn visit_assignment(&mut self, assignment: &AstAssignment) {
+        self.visit_node(&assignment.from);
+    }
+
+    fn visit_alias(&mut self, alias: &AstAlias) {
+        self.visit_node(&alias.from);
+    }
+
+    fn visit_destructure(&mut self, destructure: &AstDestructure) {
+        self.visit_node(&destructure.from);
+    }
+
+    fn visit_variable(&mut self, variable: &AstVariable) {
+        self.check("variable", &variable.name);
+    }
+}
+
+/// Reports every use of a name that no enclosing scope declares, in the
+/// order they appear.
+pub fn resolve(ast: &Ast) -> Vec<Diagnostic> {
+    let mut resolver = Resolver {
+        scopes: Vec::new(),
+        statement: None,
+        diagnostics: Vec::new()
+    };
+    resolver.visit_node(&ast.root);
+    resolver.diagnostics
+}
@@ -0,0 +1,39 @@
+use tokenizer::{Tokenizer, TokenType};
+use parser::parse;
+use pragma::read_pragmas;
+use interp::{Interp, InterpValue};
+use output::SharedBuffer;
+use error::LegError;
+
+/// The seed `run_to_string` gives the random builtins, so that scripts using
+/// them produce the same output on every run.
+pub const GOLDEN_SEED: u64 = 0;
+
+/// Runs `source` with everything it prints captured, returning the output
+/// together with the value of the last statement. Meant for snapshotting
+/// script behaviour, so the random builtins are seeded with `GOLDEN_SEED`
+/// and the clock is frozen at the Unix epoch. Scripts using the graphics
+/// builtins still depend on the machine they run on. Pragmas apply as they
+/// do on the command line.
+pub fn run_to_string(source: &str) -> Result<(String, InterpValue), LegError> {
+    let mut comments = Vec::new();
+    let ast = parse(Tokenizer::new(source).inspect(|token| {
+        if let Ok(token) = token {
+            if token.get_type() == TokenType::Comment {
+                comments.push(token.clone());
+            }
+        }
+    }))?;
+    let pragmas = read_pragmas(&comments)?;
+
+    let output = SharedBuffer::new();
+    let mut interp = Interp::new();
+    interp.set_bignum(pragmas.bignum)?;
+    interp.set_strict(pragmas.strict);
+    interp.set_output(Box::new(output.clone()));
+    interp.seed_rng(GOLDEN_SEED);
+    interp.freeze_clock();
+
+    let value = interp.run(&ast)?;
+    Ok((output.take(), value))
+}
@@ -0,0 +1,250 @@
+use tokenizer::{Token, TokenType};
+use parser::{Ast, AstNodeType, AstBlock, AstFunctionCall, AstFunctionDeclaration, AstAlias, AstVariable, AstAssignment,
+             AstDestructure};
+use visitor::{Visitor, walk_block, walk_function_call};
+use file_info::CodePoint;
+use std::collections::HashSet;
+
+const KEYWORDS: [&str; 12] = ["let", "if", "else", "while", "for", "match", "return", "break", "continue", "null", "true", "false"];
+
+/// The kind of a source span as far as an editor is concerned. The order of
+/// the variants is the order of the LSP legend returned by `legend`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SemanticTokenType {
+    Keyword,
+    Function,
+    Parameter,
+    Variable,
+    Type,
+    String,
+    Number,
+    Comment,
+    Operator
+}
+
+impl SemanticTokenType {
+    pub fn legend_name(&self) -> &'static str {
+        use self::SemanticTokenType::*;
+        match *self {
+            Keyword => { "keyword" }
+            Function => { "function" }
+            Parameter => { "parameter" }
+            Variable => { "variable" }
+            Type => { "type" }
+            String => { "string" }
+            Number => { "number" }
+            Comment => { "comment" }
+            Operator => { "operator" }
+        }
+    }
+
+    pub fn legend() -> Vec<&'static str> {
+        use self::SemanticTokenType::*;
+        let types = [Keyword, Function, Parameter, Variable, Type, String, Number, Comment, Operator];
+        types.iter().map(|tp| tp.legend_name()).collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct SemanticToken {
+    pub token_type: SemanticTokenType,
+    pub text: String,
+    pub file_info: CodePoint
+}
+
+struct DeclaredNames {
+    functions: HashSet<String>,
+    /// The line and column of every name that refers to a parameter.
+    parameters: HashSet<(usize, usize)>,
+    /// The names declared in each scope around the current node, and whether
+    /// they are parameters.
+    scopes: Vec<Vec<(String, bool)>>
+}
+
+impl DeclaredNames {
+    fn new() -> DeclaredNames {
+        DeclaredNames {
+            functions: HashSet::new(),
+            parameters: HashSet::new(),
+            scopes: Vec::new()
+        }
+    }
+
+    fn visit_scope(&mut self, names: Vec<(String, bool)>, block: &AstBlock) {
+        self.scopes.push(names);
+        walk_block(self, block);
+        self.scopes.pop();
+    }
+
+    /// Declares a name in the innermost scope from here on, hiding any
+    /// parameter of the same name.
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push((String::from(name), false));
+        }
+    }
+
+    /// Whether the latest declaration of `name` in the innermost scope
+    /// declaring it is a parameter.
+    fn is_parameter(&self, name: &str) -> bool {
+        self.scopes.iter().rev()
+            .filter_map(|scope| scope.iter().rev().find(|declared| declared.0 == name))
+            .next()
+            .is_some_and(|declared| declared.1)
+    }
+}
+
+impl Visitor for DeclaredNames {
+    fn visit_block(&mut self, block: &AstBlock) {
+        self.visit_scope(Vec::new(), block);
+    }
+
+    /// The loop variable of a `for` hides parameters within the body.
+    fn visit_function_call(&mut self, call: &AstFunctionCall) {
+        self.functions.insert(call.name.clone());
+        if call.name == "for" {
+            if let (Some(AstNodeType::Variable(variable)), Some(body)) = (call.arguments.first(), call.body.as_ref()) {
+                for argument in &call.arguments[1..] {
+                    self.visit_node(argument);
+                }
+                self.visit_scope(vec![(variable.name.clone(), false)], body);
+                return;
+            }
+        }
+        walk_function_call(self, call);
+    }
+
+    fn visit_function_declaration(&mut self, dec: &AstFunctionDeclaration) {
+        let mut parameters = Vec::new();
+        for arg in &dec.arguments {
+            if let AstNodeType::Variable(variable) = arg {
+                self.parameters.insert((variable.span.line_number_from, variable.span.column_number_from));
+                parameters.push((variable.name.clone(), true));
+            }
+        }
+        self.visit_scope(parameters, &dec.body);
+    }
+
+    fn visit_variable(&mut self, variable: &AstVariable) {
+        if self.is_parameter(&variable.name) {
+            self.parameters.insert((variable.span.line_number_from, variable.span.column_number_from));
+        }
+    }
+
+    fn visit_alias(&mut self, alias: &AstAlias) {
+        if let AstNodeType::FunctionDeclaration(_) = alias.from {
+            self.functions.insert(alias.to.name.clone());
+        }
+        self.visit_node(&alias.from);
+        self.declare(&alias.to.name);
+    }
+
+    /// `let` declares a new name, a plain assignment to a parameter updates
+    /// the parameter.
+    fn visit_assignment(&mut self, assignment: &AstAssignment) {
+        self.visit_node(&assignment.from);
+        if assignment.declares {
+            self.declare(&assignment.to.name);
+        }
+        self.visit_variable(&assignment.to);
+    }
+
+    fn visit_destructure(&mut self, destructure: &AstDestructure) {
+        self.visit_node(&destructure.from);
+        for variable in &destructure.to {
+            if destructure.declares {
+                self.declare(&variable.name);
+            }
+            self.visit_variable(variable);
+        }
+    }
+}
+
+fn token_type_at(tokens: &[Token], index: usize) -> Option<TokenType> {
+    tokens.get(index).map(|token| token.get_type())
+}
+
+fn classify_name(tokens: &[Token], index: usize, names: &DeclaredNames) -> SemanticTokenType {
+    let name = tokens[index].get_text();
+    let previous = if index > 0 { token_type_at(tokens, index - 1) } else { None };
+    let next = token_type_at(tokens, index + 1);
+
+    if KEYWORDS.contains(&&name[..]) {
+        return SemanticTokenType::Keyword;
+    }
+    if previous == Some(TokenType::Symbol) {
+        return SemanticTokenType::Type;
+    }
+
+    // Without an AST, `name :: (` is still recognizable as a function declaration
+    let declares_function = next == Some(TokenType::StaticAssignment)
+        && token_type_at(tokens, index + 2) == Some(TokenType::OpenParenthesis);
+    if names.functions.contains(&name) || next == Some(TokenType::OpenParenthesis) || declares_function {
+        return SemanticTokenType::Function;
+    }
+    let position = tokens[index].get_file_info();
+    if names.parameters.contains(&(position.line_number_from, position.column_number_from)) {
+        return SemanticTokenType::Parameter;
+    }
+    SemanticTokenType::Variable
+}
+
+/// Classifies every highlightable token. The AST is optional so that editors
+/// can still highlight a file which currently fails to parse.
+pub fn classify(tokens: &[Token], ast: Option<&Ast>) -> Vec<SemanticToken> {
+    let mut names = DeclaredNames::new();
+    if let Some(ast) = ast {
+        names.visit_node(&ast.root);
+    }
+
+    let mut semantic_tokens = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        let token_type = match token.get_type() {
+            TokenType::Alphanumeric => { Some(classify_name(tokens, index, &names)) }
+            TokenType::Numeric => { Some(SemanticTokenType::Number) }
+            TokenType::StaticString => { Some(SemanticTokenType::String) }
+            TokenType::Comment => { Some(SemanticTokenType::Comment) }
+            TokenType::Operator | TokenType::Arrow => { Some(SemanticTokenType::Operator) }
+            _ => { None }
+        };
+
+        if let Some(token_type) = token_type {
+            semantic_tokens.push(SemanticToken {
+                token_type,
+                text: token.get_text(),
+                file_info: token.get_file_info()
+            });
+        }
+    }
+    semantic_tokens
+}
+
+/// Encodes the tokens as the relative `data` array of an LSP `SemanticTokens`
+/// response, with token types indexed into `SemanticTokenType::legend`.
+/// Tokens spanning several lines are split per line since clients are not
+/// required to support multiline tokens.
+pub fn encode_lsp(tokens: &[SemanticToken]) -> Vec<u32> {
+    let mut data = Vec::new();
+    let mut previous_line = 0;
+    let mut previous_start = 0;
+
+    for token in tokens {
+        let first_line = token.file_info.line_number_from - 1;
+
+        for (offset, segment) in token.text.split('\n').enumerate() {
+            let line = first_line + offset;
+            let start = if offset == 0 { token.file_info.column_number_from - 1 } else { 0 };
+
+            let length = segment.encode_utf16().count();
+            if length > 0 {
+                let delta_line = line - previous_line;
+                let delta_start = if delta_line == 0 { start - previous_start } else { start };
+                data.extend_from_slice(&[delta_line as u32, delta_start as u32, length as u32, token.token_type as u32, 0]);
+
+                previous_line = line;
+                previous_start = start;
+            }
+        }
+    }
+    data
+}
@@ -0,0 +1,146 @@
+use interp::InterpValue;
+use persistent::PersistentMap;
+use collections::MapKey;
+use format::with_article;
+
+use serde::ser::{Serialize, Serializer, SerializeSeq, SerializeMap, Error as SerError};
+use serde::de::{Deserialize, Deserializer, Visitor, SeqAccess, MapAccess, Error as DeError};
+
+use std::fmt;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+/// Values are written as the plain data they hold, so a script result
+/// serializes to JSON as it would be written by hand. Arrays, ILists and
+/// Tuples become sequences, IMaps maps, Builders the string they hold and
+/// instances the sequence of their fields in declaration order. Functions,
+/// structs and iterators only mean something to the interpreter that made
+/// them and fail to serialize.
+impl Serialize for InterpValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            &InterpValue::InterpVoid => { serializer.serialize_unit() }
+            &InterpValue::InterpNumber(num) => { serializer.serialize_f64(num) }
+            &InterpValue::InterpInt(num) => { serializer.serialize_i64(num) }
+            #[cfg(feature = "bignum")]
+            InterpValue::InterpBigNumber(num) => { serializer.serialize_str(&num.to_string()) }
+            &InterpValue::InterpBoolean(val) => { serializer.serialize_bool(val) }
+            InterpValue::InterpString(val) => { serializer.serialize_str(val) }
+            InterpValue::InterpBuilder(buffer) => { serializer.serialize_str(&buffer.borrow()) }
+            InterpValue::InterpTuple(values) => { serialize_seq(values.iter(), values.len(), serializer) }
+            InterpValue::InterpList(list) => { serialize_seq(list.iter(), list.len(), serializer) }
+            InterpValue::InterpArray(values) => {
+                let values = values.borrow();
+                serialize_seq(values.iter(), values.len(), serializer)
+            }
+            InterpValue::InterpInstance{fields, ..} => {
+                let fields = fields.borrow();
+                serialize_seq(fields.iter(), fields.len(), serializer)
+            }
+            InterpValue::InterpMap(map) => {
+                let mut entries = serializer.serialize_map(Some(map.len()))?;
+                for (key, value) in map.iter() {
+                    entries.serialize_entry(&key.to_value(), value)?;
+                }
+                entries.end()
+            }
+            other => {
+                let msg = format!("Can not serialize {}", with_article(other.type_name()));
+                Err(S::Error::custom(msg))
+            }
+        }
+    }
+}
+
+fn serialize_seq<'v, I, S>(values: I, len: usize, serializer: S) -> Result<S::Ok, S::Error>
+    where I: Iterator<Item = &'v InterpValue>, S: Serializer {
+    let mut seq = serializer.serialize_seq(Some(len))?;
+    for value in values {
+        seq.serialize_element(value)?;
+    }
+    seq.end()
+}
+
+struct ValueVisitor;
+
+/// Reads back what `Serialize` writes. Sequences become Arrays, since the
+/// kind of list is not kept, and maps become IMaps. Integers that do not
+/// fit an Int become Numbers.
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = InterpValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a value a script can hold")
+    }
+
+    fn visit_unit<E: DeError>(self) -> Result<InterpValue, E> {
+        Ok(InterpValue::InterpVoid)
+    }
+
+    fn visit_none<E: DeError>(self) -> Result<InterpValue, E> {
+        Ok(InterpValue::InterpVoid)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<InterpValue, D::Error> {
+        InterpValue::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: DeError>(self, val: bool) -> Result<InterpValue, E> {
+        Ok(InterpValue::InterpBoolean(val))
+    }
+
+    fn visit_i64<E: DeError>(self, num: i64) -> Result<InterpValue, E> {
+        Ok(InterpValue::InterpInt(num))
+    }
+
+    fn visit_u64<E: DeError>(self, num: u64) -> Result<InterpValue, E> {
+        if num > i64::MAX as u64 {
+            return Ok(InterpValue::InterpNumber(num as f64));
+        }
+        Ok(InterpValue::InterpInt(num as i64))
+    }
+
+    fn visit_f64<E: DeError>(self, num: f64) -> Result<InterpValue, E> {
+        Ok(InterpValue::InterpNumber(num))
+    }
+
+    fn visit_str<E: DeError>(self, val: &str) -> Result<InterpValue, E> {
+        Ok(InterpValue::InterpString(String::from(val)))
+    }
+
+    fn visit_string<E: DeError>(self, val: String) -> Result<InterpValue, E> {
+        Ok(InterpValue::InterpString(val))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<InterpValue, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(InterpValue::InterpArray(Rc::new(RefCell::new(values))))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut entries: A) -> Result<InterpValue, A::Error> {
+        let mut map = PersistentMap::new();
+        while let Some((key, value)) = entries.next_entry::<InterpValue, InterpValue>()? {
+            let key = match key {
+                InterpValue::InterpBoolean(val) => { MapKey::Boolean(val) }
+                InterpValue::InterpNumber(num) => { MapKey::Number(num + 0.0) }
+                InterpValue::InterpInt(num) => { MapKey::Number(num as f64) }
+                InterpValue::InterpString(val) => { MapKey::String(val) }
+                other => {
+                    let msg = format!("Can not use {} as a map key", with_article(other.type_name()));
+                    return Err(A::Error::custom(msg));
+                }
+            };
+            map = map.insert(key, value);
+        }
+        Ok(InterpValue::InterpMap(map))
+    }
+}
+
+impl<'de> Deserialize<'de> for InterpValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<InterpValue, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
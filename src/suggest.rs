@@ -0,0 +1,73 @@
+use std::cmp::min;
+
+/// Edit distance between two names, counted in chars, where swapping two
+/// adjacent chars counts as a single edit.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a_chars: Vec<char> = a.chars().collect();
+    let b_chars: Vec<char> = b.chars().collect();
+
+    let mut distances = vec![vec![0; b_chars.len() + 1]; a_chars.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a_chars.len() {
+        for j in 1..=b_chars.len() {
+            let cost = if a_chars[i - 1] == b_chars[j - 1] { 0 } else { 1 };
+            let mut distance = min(distances[i - 1][j - 1] + cost, min(distances[i - 1][j] + 1, distances[i][j - 1] + 1));
+            if i > 1 && j > 1 && a_chars[i - 1] == b_chars[j - 2] && a_chars[i - 2] == b_chars[j - 1] {
+                distance = min(distance, distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[a_chars.len()][b_chars.len()]
+}
+
+/// The candidate closest to `name`, as long as it is close enough to be a
+/// plausible typo. About a third of the name may differ, which keeps short
+/// names like `x` from suggesting every other short name in scope.
+pub fn closest<'a, I>(name: &str, candidates: I) -> Option<&'a str>
+    where I: IntoIterator<Item = &'a str> {
+    let length = name.chars().count();
+    if length < 2 {
+        return None;
+    }
+    closest_within(name, candidates, (length / 3).max(1))
+}
+
+/// The first candidate at the smallest distance from `name`, if that
+/// distance is at most `max_distance`.
+pub fn closest_within<'a, I>(name: &str, candidates: I, max_distance: usize) -> Option<&'a str>
+    where I: IntoIterator<Item = &'a str> {
+    let mut best: Option<(usize, &'a str)> = None;
+    for candidate in candidates {
+        if candidate == name {
+            continue;
+        }
+        let distance = edit_distance(name, candidate);
+        if distance > max_distance {
+            continue;
+        }
+        match best {
+            Some((best_distance, _)) if best_distance <= distance => {}
+            _ => { best = Some((distance, candidate)); }
+        }
+    }
+
+    best.map(|(_, candidate)| candidate)
+}
+
+/// `msg` with a "did you mean" hint appended when one of the candidates is
+/// close to `name`.
+pub fn with_suggestion<'a, I>(msg: String, name: &str, candidates: I) -> String
+    where I: IntoIterator<Item = &'a str> {
+    match closest(name, candidates) {
+        Some(candidate) => { format!("{}, did you mean `{}`?", msg, candidate) }
+        None => { msg }
+    }
+}
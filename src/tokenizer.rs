@@ -1,4 +1,6 @@
 use file_info::CodePoint;
+use error_codes;
+use error_codes::ErrorCode;
 use std::mem;
 use std::iter::Peekable;
 use std::str::Chars;
@@ -10,23 +12,44 @@ use std::fmt;
 
 #[derive(Debug)]
 pub struct TokenizationError {
+    code: &'static ErrorCode,
     token: Token,
     desc: String
 }
 
 impl Display for TokenizationError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        writeln!(f, "TokenizationErro: \n{}\n\n{:?}", self.desc, self.token)
+        let file_info = self.token.get_file_info();
+        writeln!(f, "error[{}]: {}\n --> line {}, column {}", self.code.code, self.desc,
+                 file_info.line_number_from, file_info.column_number_from)
     }
 }
 
 impl TokenizationError {
-    fn new(token: Token, desc: String) -> TokenizationError {
-        return TokenizationError {
-            token: token,
-            desc: desc
+    fn new(code: &'static ErrorCode, token: Token, desc: String) -> TokenizationError {
+        TokenizationError {
+            code,
+            token,
+            desc
         }
     }
+
+    pub fn code(&self) -> &'static ErrorCode {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.desc
+    }
+
+    pub fn get_file_info(&self) -> CodePoint {
+        self.token.get_file_info()
+    }
+
+    /// The text read up to the error.
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
 }
 
 impl Error for TokenizationError {
@@ -34,7 +57,7 @@ impl Error for TokenizationError {
         "Tokenization error!!!"
     }
 
-    fn cause(&self) -> Option<&Error> {
+    fn cause(&self) -> Option<&dyn Error> {
         None
     }
 }
@@ -44,33 +67,41 @@ pub struct Token {
     null_value: bool,
     text: String,
     token_type: TokenType,
-    file_info: CodePoint
+    file_info: CodePoint,
+    leading_trivia: String
 }
 
 impl Clone for Token {
     fn clone(&self) -> Self {
-        return Token {
+        Token {
             null_value: self.null_value,
             text: self.text.clone(),
             token_type: self.token_type,
             file_info: self.file_info.clone(),
+            leading_trivia: self.leading_trivia.clone(),
         }
     }
 }
 
+impl Default for Token {
+    fn default() -> Token {
+        Token::new()
+    }
+}
+
 impl Token {
     pub fn new() -> Token {
-        return Token::typed(TokenType::Undefined);
+        Token::typed(TokenType::Undefined)
     }
 
     pub fn null() -> Token {
         let mut token = Token::typed(TokenType::Undefined);
         token.null_value = true;
-        return token;
+        token
     }
 
     pub fn typed(tp : TokenType) -> Token {
-        return Token {
+        Token {
             null_value: false,
             text: String::new(),
             token_type: tp,
@@ -80,21 +111,72 @@ impl Token {
 
                 line_number_to: 0,
                 column_number_to: 0,
-            }
-        };
+            },
+            leading_trivia: String::new()
+        }
     }
 
     pub fn is_null(&self) -> bool {
-        return self.null_value;
+        self.null_value
     }
 
     pub fn get_type(&self) -> TokenType {
-        return self.token_type;
+        self.token_type
     }
 
     pub fn get_text(&self) -> String {
-        return self.text.clone();
+        self.text.clone()
+    }
+
+    pub fn get_file_info(&self) -> CodePoint {
+        self.file_info.clone()
+    }
+
+    /// The whitespace between the previous token and this one.
+    pub fn get_leading_trivia(&self) -> &str {
+        &self.leading_trivia
     }
+
+    /// A `;` right after `token`, for sources that may leave out their last
+    /// one.
+    pub fn end_of_statement_after(token: &Token) -> Token {
+        let mut end = Token::typed(TokenType::EndOfStatement);
+        end.text = String::from(";");
+        let line = token.file_info.line_number_to;
+        let column = token.file_info.column_number_to + 1;
+        end.file_info = CodePoint {
+            line_number_from: line,
+            column_number_from: column,
+
+            line_number_to: line,
+            column_number_to: column,
+        };
+        end
+    }
+}
+
+/// The tokens of a source together with the whitespace after the last one,
+/// which is everything needed to give back the source exactly as written.
+pub struct LosslessTokens {
+    pub tokens: Vec<Token>,
+    pub trailing_trivia: String
+}
+
+impl LosslessTokens {
+    pub fn source(&self) -> String {
+        reconstruct_source(&self.tokens, &self.trailing_trivia)
+    }
+}
+
+/// Joins the tokens with their leading trivia, followed by `trailing_trivia`.
+pub fn reconstruct_source(tokens: &[Token], trailing_trivia: &str) -> String {
+    let mut source = String::new();
+    for token in tokens {
+        source.push_str(&token.leading_trivia);
+        source.push_str(&token.text);
+    }
+    source.push_str(trailing_trivia);
+    source
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -108,8 +190,13 @@ pub enum TokenType {
     OpenBlock,
     CloseBlock,
 
+    OpenBracket,
+    CloseBracket,
+
     ParameterDivider,
     SubElement,
+    Spread,
+    Arrow,
 
     StaticAssignment,
     VariableAssignment,
@@ -125,39 +212,195 @@ pub enum TokenType {
     Undefined
 }
 
-struct Tokenizer<'a> {
+/// Decodes the escape sequences of a string literal without its quotes:
+/// `\n`, `\t`, `\\`, `\"` and `\u{XXXX}`.
+pub fn decode_escapes(text: &str) -> Result<String, String> {
+    let mut decoded = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => { decoded.push('\n'); }
+            Some('t') => { decoded.push('\t'); }
+            Some('\\') => { decoded.push('\\'); }
+            Some('"') => { decoded.push('"'); }
+            Some('u') => {
+                if chars.next() != Some('{') {
+                    return Err(String::from("\\u has to be followed by {"));
+                }
+                let mut code = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    code.push(c);
+                }
+                if !closed {
+                    return Err(format!("\\u{{{} is missing a closing }}", code));
+                }
+                let character = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32);
+                match character {
+                    Some(character) if !code.is_empty() && code.len() <= 6 => { decoded.push(character); }
+                    _ => { return Err(format!("\\u{{{}}} is not a valid character", code)); }
+                }
+            }
+            Some(other) => { return Err(format!("Unknown escape sequence \\{}", other)); }
+            None => { return Err(String::from("Missing character after \\")); }
+        }
+    }
+    Ok(decoded)
+}
+
+const RADIX_PREFIXES: [(&str, u32); 3] = [("0x", 16), ("0b", 2), ("0o", 8)];
+
+/// The radix and digits of a `0x`, `0b` or `0o` literal.
+pub fn split_radix(text: &str) -> Option<(u32, &str)> {
+    RADIX_PREFIXES.iter()
+        .find(|prefix| text.starts_with(prefix.0))
+        .map(|prefix| (prefix.1, &text[prefix.0.len()..]))
+}
+
+/// Checks that `digits` is not empty and only has digits of `radix` and
+/// separators, pointing at the first that is not. `offset` is where the
+/// digits start in the literal.
+fn check_digits(digits: &str, offset: usize, radix: u32, problem: &str) -> Result<(), (usize, String)> {
+    if digits.is_empty() {
+        return Err((offset, String::from(problem)));
+    }
+    match digits.char_indices().find(|&(_, c)| c != '_' && !c.is_digit(radix)) {
+        Some((index, _)) => { Err((offset + index, String::from(problem))) }
+        None => { Ok(()) }
+    }
+}
+
+/// Numbers are digits with at most one decimal point, which has to be
+/// followed by digits, and an optional exponent such as `e-3`. Integers may
+/// also be written in hexadecimal, binary or octal, as in `0xFF`, and any
+/// two digits may be separated by a `_`, as in `1_000`. A problem is
+/// returned along with its offset in the literal.
+fn validate_number(text: &str) -> Result<(), (usize, String)> {
+    let radix = split_radix(text).map_or(10, |prefix| prefix.0);
+    for (index, c) in text.char_indices() {
+        if c != '_' {
+            continue;
+        }
+        let before = text[..index].chars().next_back();
+        let after = text[index + 1..].chars().next();
+        if !before.is_some_and(|c| c.is_digit(radix)) || !after.is_some_and(|c| c.is_digit(radix)) {
+            return Err((index, String::from("_ can only separate two digits")));
+        }
+    }
+
+    if let Some((radix, digits)) = split_radix(text) {
+        let problem = format!("expected base {} digits after {}", radix, &text[..2]);
+        return check_digits(digits, text.len() - digits.len(), radix, &problem);
+    }
+
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(index) => { (&text[..index], Some(&text[index + 1..])) }
+        None => { (text, None) }
+    };
+
+    let mut parts = mantissa.split('.');
+    let integer = parts.next().unwrap_or("");
+    let fraction = parts.next();
+    if let Some(fraction) = fraction {
+        if parts.next().is_some() {
+            return Err((integer.len() + 1 + fraction.len(), String::from("more than one decimal point")));
+        }
+    }
+    check_digits(integer, 0, 10, "unexpected character in number")?;
+    if let Some(fraction) = fraction {
+        check_digits(fraction, integer.len() + 1, 10, "expected digits after the decimal point")?;
+    }
+
+    if let Some(exponent) = exponent {
+        let start = mantissa.len() + 1;
+        let digits = exponent.trim_start_matches(['+', '-']);
+        let sign_length = exponent.len() - digits.len();
+        if sign_length > 1 {
+            return Err((start + 1, String::from("expected digits in the exponent")));
+        }
+        check_digits(digits, start + sign_length, 10, "expected digits in the exponent")?;
+    }
+    Ok(())
+}
+
+/// Reads the tokens of a source one at a time, as an iterator. After an
+/// error it goes on with the characters after the broken token, so every
+/// error in the source can be found in one pass.
+pub struct Tokenizer<'a> {
+    /// The tokens read but not yet taken, at most the one the last character
+    /// completed.
     tokens: Vec<Token>,
     char_stream: Peekable<Chars<'a>>,
     current_char: char,
+    trivia: String,
 
     line_number: usize,
     column_number: usize,
 }
 
 pub fn tokenize(string: &str) -> Result<Vec<Token>, TokenizationError> {
-    let mut tokenizer = Tokenizer::new();
-    return tokenizer.tokenize(string);
+    Tokenizer::new(string).collect()
+}
+
+/// Like `tokenize`, but also keeps the whitespace after the last token so
+/// that the source can be reconstructed exactly, as formatters need.
+pub fn tokenize_lossless(string: &str) -> Result<LosslessTokens, TokenizationError> {
+    let mut tokenizer = Tokenizer::new(string);
+    let tokens = tokenizer.by_ref().collect::<Result<Vec<Token>, TokenizationError>>()?;
+
+    Ok(LosslessTokens {
+        tokens,
+        trailing_trivia: mem::take(&mut tokenizer.trivia)
+    })
+}
+
+/// Tokenizes all of `string`, returning the tokens that could be read along
+/// with every error on the way.
+pub fn tokenize_recovering(string: &str) -> (Vec<Token>, Vec<TokenizationError>) {
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    for res in Tokenizer::new(string) {
+        match res {
+            Ok(token) => { tokens.push(token); }
+            Err(error) => { errors.push(error); }
+        }
+    }
+    (tokens, errors)
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token, TokenizationError>;
+
+    fn next(&mut self) -> Option<Result<Token, TokenizationError>> {
+        while self.tokens.is_empty() {
+            let c = self.next_char()?;
+            if let Err(error) = self.tokenize_char(c) {
+                return Some(Err(error));
+            }
+        }
+        Some(Ok(self.tokens.remove(0)))
+    }
 }
 
 impl<'a> Tokenizer<'a> {
-    fn new() -> Tokenizer<'a> {
-        return Tokenizer {
+    pub fn new(source: &'a str) -> Tokenizer<'a> {
+        Tokenizer {
             line_number: 1,
             column_number: 0,
 
-            char_stream: "".chars().peekable(),
+            char_stream: source.chars().peekable(),
             current_char: '\n',
+            trivia: String::new(),
             tokens: Vec::new()
-        };
-    }
-
-    fn reset(&mut self) {
-        self.line_number = 1;
-        self.column_number = 0;
-
-        self.char_stream = "".chars().peekable();
-        self.current_char = '\n';
-        self.tokens = Vec::new();
+        }
     }
 
     fn increment_file_info(&mut self) {
@@ -176,13 +419,13 @@ impl<'a> Tokenizer<'a> {
 
     fn next_char(&mut self) -> Option<char> {
         self.increment_file_info();
-        return match self.char_stream.next() {
+        match self.char_stream.next() {
             Some(c) => {
                 self.current_char = c;
                 Some(c)
             }
             None => None
-        };
+        }
     }
 
     fn add_next_char(&mut self, token: &mut Token) -> Option<char> {
@@ -192,25 +435,24 @@ impl<'a> Tokenizer<'a> {
             token.text.push(c);
         }
 
-        return next;
+        next
     }
 
     fn peek_char(&mut self) -> Option<char> {
-        return match self.char_stream.peek() {
-            Some(c) => Some(*c),
-            None => None
-        };
+        self.char_stream.peek().cloned()
     }
 
     fn save_token(&mut self, mut token: Token) {
         token.file_info.column_number_to = self.column_number;
         token.file_info.line_number_to = self.line_number;
+        token.leading_trivia = mem::take(&mut self.trivia);
 
         self.tokens.push(token);
     }
 
     fn new_token(&mut self, tp: TokenType) -> Token {
-        let token = Token {
+        
+        Token {
             null_value: false,
             text: self.current_char.to_string(),
             token_type: tp,
@@ -220,9 +462,9 @@ impl<'a> Tokenizer<'a> {
 
                 line_number_to: self.line_number,
                 column_number_to: self.column_number,
-            }
-        };
-        return token;
+            },
+            leading_trivia: String::new()
+        }
     }
 
     fn save_new_token(&mut self, tp: TokenType) {
@@ -230,21 +472,18 @@ impl<'a> Tokenizer<'a> {
         self.save_token(token);
     }
 
-    fn tokenize(&mut self, target: &'a str) -> Result<Vec<Token>, TokenizationError> {
-        self.char_stream = target.chars().peekable();
-        let res = self.tokenize_using_state();
-        self.reset();
-
-        return res;
-    }
-
-    fn tokenize_number(&mut self) -> Token {
+    fn tokenize_number(&mut self) -> Result<Token, TokenizationError> {
         let mut token = self.new_token(TokenType::Numeric);
 
+        // Everything that could belong to the literal is taken, so that a
+        // malformed number is reported as a whole rather than split up
         loop {
+            // In hexadecimal e is a digit, so the sign is the next operator
+            let exponent_sign = token.text.ends_with(['e', 'E']) && split_radix(&token.text).is_none();
             match self.peek_char() {
                 Some(c) => match c {
-                    '0' ... '9' | '.' => { self.add_next_char(&mut token); }
+                    '0' ..= '9' | '.' | 'a' ..= 'z' | 'A' ..= 'Z' | '_' => { self.add_next_char(&mut token); }
+                    '+' | '-' if exponent_sign => { self.add_next_char(&mut token); }
                     _ => { break; }
                 },
                 None => {
@@ -253,25 +492,25 @@ impl<'a> Tokenizer<'a> {
             }
         }
 
-        return token;
+        if let Err((offset, problem)) = validate_number(&token.text) {
+            // Point at the offending character, literals never span lines
+            let column = token.file_info.column_number_from + offset;
+            token.file_info.column_number_from = column;
+            token.file_info.column_number_to = column;
+            let msg = format!("Malformed number {}: {}", token.text, problem);
+            return Err(TokenizationError::new(&error_codes::MALFORMED_NUMBER, token, msg));
+        }
+        Ok(token)
     }
 
     fn tokenize_word(&mut self) -> Token {
         let mut token = self.new_token(TokenType::Alphanumeric);
 
-        loop {
-            match self.peek_char() {
-                Some(c) => match c {
-                    'a' ... 'z' | 'A' ... 'Z' | '_' | '0' ... '9' => { self.add_next_char(&mut token); }
-                    _ => { break; }
-                },
-                None => {
-                    break;
-                }
-            }
+        while let Some('a' ..= 'z' | 'A' ..= 'Z' | '_' | '0' ..= '9') = self.peek_char() {
+            self.add_next_char(&mut token);
         }
 
-        return token;
+        token
     }
 
     fn tokenize_string(&mut self) -> Result<Token, TokenizationError> {
@@ -285,177 +524,277 @@ impl<'a> Tokenizer<'a> {
                     _ => {}
                 },
                 None => {
-                    let msg = format!("Invalid end of input for \"string\"");
-                    return Err(TokenizationError::new(token, msg));
+                    let msg = "Invalid end of input for \"string\"".to_string();
+                    return Err(TokenizationError::new(&error_codes::UNTERMINATED_STRING, token, msg));
                 }
             }
         }
 
-        return Ok(token);
+        let text = token.get_text();
+        if let Err(msg) = decode_escapes(&text[1..text.len() - 1]) {
+            return Err(TokenizationError::new(&error_codes::INVALID_ESCAPE, token, msg));
+        }
+        Ok(token)
     }
 
-    fn tokenize_comment(&mut self) -> Result<Token, TokenizationError> {
+    fn tokenize_comment(&mut self) -> Token {
         let mut token = self.new_token(TokenType::Comment);
 
-        loop {
-            match self.peek_char() {
-                Some(c) => match c {
-                    '\n' => {break}
-                    _ => {
-                        self.add_next_char(&mut token);
-                    }
-                },
+        while let Some(c) = self.peek_char() {
+            if c == '\n' {
+                break;
+            }
+            self.add_next_char(&mut token);
+        }
+
+        token
+    }
+
+    /// `/* ... */`, which may contain further block comments.
+    fn tokenize_block_comment(&mut self) -> Result<Token, TokenizationError> {
+        let mut token = self.new_token(TokenType::Comment);
+        self.add_next_char(&mut token);
+
+        let mut depth = 1;
+        while depth > 0 {
+            match self.add_next_char(&mut token) {
+                Some('*') if self.peek_char() == Some('/') => {
+                    self.add_next_char(&mut token);
+                    depth -= 1;
+                }
+                Some('/') if self.peek_char() == Some('*') => {
+                    self.add_next_char(&mut token);
+                    depth += 1;
+                }
+                Some(_) => {}
                 None => {
-                    let msg = format!("Invalid end of input for // comment");
-                    return Err(TokenizationError::new(token, msg));
+                    let msg = format!("Invalid end of input for /* comment, missing {} */", depth);
+                    return Err(TokenizationError::new(&error_codes::UNTERMINATED_COMMENT, token, msg));
                 }
             }
         }
 
-        return Ok(token);
+        Ok(token)
+    }
+
+    /// `#` lines such as `#pragma bignum` are kept as comments, so that only
+    /// the tools interested in them have to look at them.
+    fn tokenize_hash_line(&mut self) -> Token {
+        let mut token = self.new_token(TokenType::Comment);
+
+        while let Some(c) = self.peek_char() {
+            if c == '\n' {
+                break;
+            }
+            self.add_next_char(&mut token);
+        }
+
+        token
     }
 
     fn tokenize_static_assignment(&mut self) -> Token {
         let mut token = self.new_token(TokenType::StaticAssignment);
         self.add_next_char(&mut token);
-        return token;
+        token
     }
 
     fn tokenize_variable_assignment(&mut self) -> Token {
         let mut token = self.new_token(TokenType::VariableAssignment);
         self.add_next_char(&mut token);
-        return token;
+        token
     }
 
     fn tokenize_symbol(&mut self) -> Token {
-        let token = self.new_token(TokenType::Symbol);
-        return token;
+        
+        self.new_token(TokenType::Symbol)
     }
 
     fn tokenize_operator(&mut self) -> Token {
-        let token = self.new_token(TokenType::Operator);
-        return token;
+        
+        self.new_token(TokenType::Operator)
     }
 
-    fn tokenize_using_state(&mut self) -> Result<Vec<Token>, TokenizationError> {
-        while let Some(c) = self.next_char() {
-            match c {
-                '0' ... '9' => {
-                    let token = self.tokenize_number();
-                    self.save_token(token);
-                }
-                'a' ... 'z' | 'A' ... 'Z' | '_' => {
-                    let token = self.tokenize_word();
-                    self.save_token(token);
-                }
-                '"' => {
-                    let token = self.tokenize_string()?;
-                    self.save_token(token);
-                }
-                '(' => {
-                    let token = self.new_token(TokenType::OpenParenthesis);
-                    self.save_token(token);
-                }
-                ')' => {
-                    let token = self.new_token(TokenType::CloseParenthesis);
-                    self.save_token(token);
-                }
-                '+' | '-' | '*' | '^' | '%' => {
-                    let token = self.tokenize_operator();
-                    self.save_token(token);
-                }
-                '{' => {
-                    let token = self.new_token(TokenType::OpenBlock);
-                    self.save_token(token);
-                }
-                '}' => {
-                    let token = self.new_token(TokenType::CloseBlock);
-                    self.save_token(token);
-                }
-                '/' => {
-                    // Add comment support
-                    match self.peek_char() {
-                        Some(c) => {
-                            match c {
-                                '/' => {
-                                    let token = self.tokenize_comment()?;
-                                    self.save_token(token);
-                                }
-                                _ => {
-                                    let token = self.tokenize_operator();
-                                    self.save_token(token);
-                                }
+    fn tokenize_two_char_operator(&mut self) -> Token {
+        let mut token = self.new_token(TokenType::Operator);
+        self.add_next_char(&mut token);
+        token
+    }
+
+    fn tokenize_char(&mut self, c: char) -> Result<(), TokenizationError> {
+        match c {
+            '0' ..= '9' => {
+                let token = self.tokenize_number()?;
+                self.save_token(token);
+            }
+            'a' ..= 'z' | 'A' ..= 'Z' | '_' => {
+                let token = self.tokenize_word();
+                self.save_token(token);
+            }
+            '"' => {
+                let token = self.tokenize_string()?;
+                self.save_token(token);
+            }
+            '(' => {
+                let token = self.new_token(TokenType::OpenParenthesis);
+                self.save_token(token);
+            }
+            ')' => {
+                let token = self.new_token(TokenType::CloseParenthesis);
+                self.save_token(token);
+            }
+            '|' if self.peek_char() == Some('>') || self.peek_char() == Some('|') => {
+                let token = self.tokenize_two_char_operator();
+                self.save_token(token);
+            }
+            '&' if self.peek_char() == Some('&') => {
+                let token = self.tokenize_two_char_operator();
+                self.save_token(token);
+            }
+            '-' if self.peek_char() == Some('>') => {
+                let mut token = self.new_token(TokenType::Arrow);
+                self.add_next_char(&mut token);
+                self.save_token(token);
+            }
+            '+' | '-' | '*' | '^' | '%' | '&' | '|' | '~' => {
+                let token = self.tokenize_operator();
+                self.save_token(token);
+            }
+            '{' => {
+                let token = self.new_token(TokenType::OpenBlock);
+                self.save_token(token);
+            }
+            '}' => {
+                let token = self.new_token(TokenType::CloseBlock);
+                self.save_token(token);
+            }
+            '[' => {
+                let token = self.new_token(TokenType::OpenBracket);
+                self.save_token(token);
+            }
+            ']' => {
+                let token = self.new_token(TokenType::CloseBracket);
+                self.save_token(token);
+            }
+            '/' => {
+                // Add comment support
+                match self.peek_char() {
+                    Some(c) => {
+                        match c {
+                            '/' => {
+                                let token = self.tokenize_comment();
+                                self.save_token(token);
+                            }
+                            '*' => {
+                                let token = self.tokenize_block_comment()?;
+                                self.save_token(token);
+                            }
+                            _ => {
+                                let token = self.tokenize_operator();
+                                self.save_token(token);
                             }
                         }
-                        None => {
-                            let token = self.new_token(TokenType::Undefined);
-                            let msg = format!("Invalid end of input after /");
-                            return Err(TokenizationError::new(token, msg));
-                        }
+                    }
+                    None => {
+                        let token = self.new_token(TokenType::Undefined);
+                        let msg = "Invalid end of input after /".to_string();
+                        return Err(TokenizationError::new(&error_codes::INCOMPLETE_TOKEN, token, msg));
                     }
                 }
-                '=' => {
-                    match self.peek_char() {
-                        Some(c) => {
-                            let token = self.tokenize_variable_assignment();
-                            self.save_token(token);
-                        }
-                        None => {
-                            let token = self.new_token(TokenType::Undefined);
-                            let msg = format!("Invalid end of input after =");
-                            return Err(TokenizationError::new(token, msg));
-                        }
+            }
+            '=' if self.peek_char() == Some('=') => {
+                let token = self.tokenize_two_char_operator();
+                self.save_token(token);
+            }
+            '!' if self.peek_char() == Some('=') => {
+                let token = self.tokenize_two_char_operator();
+                self.save_token(token);
+            }
+            '!' => {
+                let token = self.tokenize_operator();
+                self.save_token(token);
+            }
+            '<' | '>' => {
+                let token = if self.peek_char() == Some('=') || self.peek_char() == Some(c) {
+                    self.tokenize_two_char_operator()
+                } else {
+                    self.tokenize_operator()
+                };
+                self.save_token(token);
+            }
+            '=' => {
+                match self.peek_char() {
+                    Some(_) => {
+                        let token = self.tokenize_variable_assignment();
+                        self.save_token(token);
+                    }
+                    None => {
+                        let token = self.new_token(TokenType::Undefined);
+                        let msg = "Invalid end of input after =".to_string();
+                        return Err(TokenizationError::new(&error_codes::INCOMPLETE_TOKEN, token, msg));
                     }
                 }
-                ':' => {
-                    match self.peek_char() {
-                        Some(c) => {
-                            match c {
-                                ':' => {
-                                    let token = self.tokenize_static_assignment();
-                                    self.save_token(token);
-                                }
-                                '=' => {
-                                    let token = self.tokenize_variable_assignment();
-                                    self.save_token(token);
-                                }
-                                'a' ... 'z' | 'A' ... 'Z' => {
-                                    let token = self.tokenize_symbol();
-                                    self.save_token(token);
-                                }
-                                _ => {
-                                    let token = self.new_token(TokenType::Undefined);
-                                    let msg = format!("Invalid character preceding (:): {}", c);
-                                    return Err(TokenizationError::new(token, msg));
-                                }
+            }
+            ':' => {
+                match self.peek_char() {
+                    Some(c) => {
+                        match c {
+                            ':' => {
+                                let token = self.tokenize_static_assignment();
+                                self.save_token(token);
+                            }
+                            '=' => {
+                                let token = self.tokenize_variable_assignment();
+                                self.save_token(token);
+                            }
+                            'a' ..= 'z' | 'A' ..= 'Z' => {
+                                let token = self.tokenize_symbol();
+                                self.save_token(token);
+                            }
+                            _ => {
+                                let token = self.new_token(TokenType::Undefined);
+                                let msg = format!("Invalid character preceding (:): {}", c);
+                                return Err(TokenizationError::new(&error_codes::INVALID_COLON, token, msg));
                             }
-                        }
-                        None => {
-                            let token = self.new_token(TokenType::Undefined);
-                            let msg = format!("Invalid end of input after :");
-                            return Err(TokenizationError::new(token, msg));
                         }
                     }
+                    None => {
+                        let token = self.new_token(TokenType::Undefined);
+                        let msg = "Invalid end of input after :".to_string();
+                        return Err(TokenizationError::new(&error_codes::INCOMPLETE_TOKEN, token, msg));
+                    }
                 }
-                ';' => {
-                    self.save_new_token(TokenType::EndOfStatement);
-                }
-                ',' => {
-                    self.save_new_token(TokenType::ParameterDivider);
-                }
-                '.' => {
-                    self.save_new_token(TokenType::SubElement);
-                }
-                ' ' | '\n' | '\r' | '\t' => {}
-                _ => {
-                    let token = self.new_token(TokenType::Undefined);
-                    let msg = format!("Invalid end of input: {}", c);
-                    return Err(TokenizationError::new(token, msg));
+            }
+            '#' => {
+                let token = self.tokenize_hash_line();
+                self.save_token(token);
+            }
+            ';' => {
+                self.save_new_token(TokenType::EndOfStatement);
+            }
+            ',' => {
+                self.save_new_token(TokenType::ParameterDivider);
+            }
+            '.' if self.peek_char() == Some('.') => {
+                let mut token = self.new_token(TokenType::Spread);
+                self.add_next_char(&mut token);
+                if self.peek_char() != Some('.') {
+                    let msg = "Expected ... but found ..".to_string();
+                    return Err(TokenizationError::new(&error_codes::INVALID_CHARACTER, token, msg));
                 }
+                self.add_next_char(&mut token);
+                self.save_token(token);
+            }
+            '.' => {
+                self.save_new_token(TokenType::SubElement);
+            }
+            ' ' | '\n' | '\r' | '\t' => { self.trivia.push(c); }
+            _ => {
+                let token = self.new_token(TokenType::Undefined);
+                let msg = format!("Invalid end of input: {}", c);
+                return Err(TokenizationError::new(&error_codes::INVALID_CHARACTER, token, msg));
             }
         }
 
-        let tokens = mem::replace(&mut self.tokens, Vec::new());
-        return Ok(tokens);
+        Ok(())
     }
 }
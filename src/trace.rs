@@ -0,0 +1,29 @@
+use interp::{InterpHook, InterpValue};
+use parser::AstStatement;
+
+/// Prints every statement with its position and resulting value to stderr,
+/// in the order the statements finish executing.
+pub struct Tracer {
+    source: String
+}
+
+impl Tracer {
+    pub fn new(source: &str) -> Tracer {
+        Tracer {
+            source: String::from(source)
+        }
+    }
+}
+
+impl InterpHook for Tracer {
+    fn after_statement(&mut self, statement: &AstStatement, result: &InterpValue) {
+        let position = &statement.file_info;
+        let text = position.source_text(&self.source);
+        let text = text.lines().map(|line| line.trim()).collect::<Vec<&str>>().join(" ");
+
+        eprintln!("[trace] {}:{}-{}:{} {} => {:?}",
+                  position.line_number_from, position.column_number_from,
+                  position.line_number_to, position.column_number_to,
+                  text, result);
+    }
+}
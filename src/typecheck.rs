@@ -0,0 +1,274 @@
+use parser::{Ast, AstNodeType, AstBlock, AstStatement, AstFunctionCall, AstFunctionDeclaration, AstAssignment, AstOperatorCall,
+             AstOperator, AstUnaryOperator};
+use visitor::{Visitor, walk_block, walk_function_call, walk_operator_call};
+use diagnostic::{Diagnostic, Severity};
+use file_info::CodePoint;
+use format::with_article;
+use error_codes;
+
+use std::collections::HashMap;
+
+/// What the checker knows about a name: the annotated type of a variable,
+/// or the annotated parameter types of a function declared with `::`.
+enum Binding {
+    Variable(Option<String>),
+    Function(Vec<Option<String>>)
+}
+
+/// Checks the values given to names declared with a `:Type` annotation, and
+/// the operands of operators, wherever the type of a value is known without
+/// running the script. Values of unknown type are never reported.
+struct TypeChecker {
+    scopes: Vec<HashMap<String, Binding>>,
+    statement: Option<CodePoint>,
+    diagnostics: Vec<Diagnostic>
+}
+
+/// Whether a value of type `actual` can be given where `expected` is
+/// annotated. Ints are promoted to Numbers, as for struct fields.
+fn accepts(expected: &str, actual: &str) -> bool {
+    expected == actual || (expected == "Number" && actual == "Int")
+}
+
+fn is_numeric(type_name: &str) -> bool {
+    type_name == "Int" || type_name == "Number"
+}
+
+fn is_comparison(operator: AstOperator) -> bool {
+    matches!(operator, AstOperator::Less | AstOperator::LessEqual | AstOperator::Greater | AstOperator::GreaterEqual)
+}
+
+/// The type of `lhs operator rhs`, `Ok(None)` when it depends on the values,
+/// or `Err` when the operator can not be applied to the operand types.
+fn operation_type(lhs: &str, rhs: &str, operator: AstOperator) -> Result<Option<&'static str>, ()> {
+    if is_numeric(lhs) && is_numeric(rhs) {
+        let ints = lhs == "Int" && rhs == "Int";
+        return match operator {
+            AstOperator::Equal | AstOperator::NotEqual => { Ok(Some("Boolean")) }
+            _ if is_comparison(operator) => { Ok(Some("Boolean")) }
+            AstOperator::Div => { Ok(Some("Number")) }
+            AstOperator::Pow if ints => { Ok(None) }
+            _ if ints => { Ok(Some("Int")) }
+            _ => { Ok(Some("Number")) }
+        };
+    }
+    if lhs == "String" && rhs == "String" && is_comparison(operator) {
+        return Ok(Some("Boolean"));
+    }
+    Err(())
+}
+
+impl TypeChecker {
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().filter_map(|scope| scope.get(name)).next()
+    }
+
+    fn report(&mut self, msg: String) {
+        let file_info = match self.statement {
+            Some(ref file_info) => { file_info.clone() }
+            None => { return; }
+        };
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            code: &error_codes::ANNOTATION_MISMATCH,
+            message: msg,
+            file_info
+        });
+    }
+
+    /// The type `node` evaluates to, when it is known without running it.
+    fn infer(&self, node: &AstNodeType) -> Option<String> {
+        match node {
+            AstNodeType::NumberValue(boxed) => {
+                Some(String::from(if boxed.integer.is_some() { "Int" } else { "Number" }))
+            }
+            &AstNodeType::StringValue(_) => { Some(String::from("String")) }
+            &AstNodeType::BooleanValue(_) => { Some(String::from("Boolean")) }
+            &AstNodeType::NullValue(_) => { Some(String::from("Void")) }
+            &AstNodeType::FunctionDeclaration(_) => { Some(String::from("Function")) }
+            &AstNodeType::StructDeclaration(_) => { Some(String::from("Struct")) }
+            &AstNodeType::Tuple(_) => { Some(String::from("Tuple")) }
+            &AstNodeType::ArrayLiteral(_) => { Some(String::from("Array")) }
+            &AstNodeType::LogicalOperatorCall(_) => { Some(String::from("Boolean")) }
+            AstNodeType::Variable(boxed) => {
+                match self.lookup(&boxed.name) {
+                    Some(Binding::Variable(type_name)) => { type_name.clone() }
+                    Some(&Binding::Function(_)) => { Some(String::from("Function")) }
+                    None => { None }
+                }
+            }
+            AstNodeType::UnaryOperatorCall(boxed) => {
+                match boxed.operator {
+                    AstUnaryOperator::Not => { Some(String::from("Boolean")) }
+                    AstUnaryOperator::Negate | AstUnaryOperator::BitNot => { self.infer(&boxed.operand) }
+                }
+            }
+            AstNodeType::OperatorCall(boxed) => {
+                match boxed.operator {
+                    AstOperator::Equal | AstOperator::NotEqual => { Some(String::from("Boolean")) }
+                    _ => {
+                        match (self.infer(&boxed.lhs), self.infer(&boxed.rhs)) {
+                            (Some(lhs), Some(rhs)) => {
+                                operation_type(&lhs, &rhs, boxed.operator).ok().and_then(|type_name| type_name).map(String::from)
+                            }
+                            _ => { None }
+                        }
+                    }
+                }
+            }
+            _ => { None }
+        }
+    }
+
+    /// Binds the names the statements declare in the scope they run in. A
+    /// plain assignment only declares a name no enclosing scope has yet.
+    fn declare(&self, scope: &mut HashMap<String, Binding>, statements: &[AstStatement]) {
+        for statement in statements {
+            match statement.node {
+                AstNodeType::Assignment(ref assignment) => {
+                    let name = assignment.to.name.clone();
+                    if assignment.to.type_name.is_some() || assignment.declares {
+                        scope.insert(name, Binding::Variable(assignment.to.type_name.clone()));
+                    } else if self.lookup(&name).is_none() {
+                        scope.entry(name).or_insert(Binding::Variable(None));
+                    }
+                }
+                AstNodeType::Alias(ref alias) => {
+                    let binding = match alias.from {
+                        AstNodeType::FunctionDeclaration(ref dec) => { Binding::Function(parameter_types(dec)) }
+                        _ => { Binding::Variable(alias.to.type_name.clone()) }
+                    };
+                    scope.insert(alias.to.name.clone(), binding);
+                }
+                AstNodeType::Destructure(ref destructure) => {
+                    for variable in &destructure.to {
+                        if destructure.declares || self.lookup(&variable.name).is_none() {
+                            scope.insert(variable.name.clone(), Binding::Variable(None));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn visit_scope(&mut self, mut scope: HashMap<String, Binding>, block: &AstBlock) {
+        self.declare(&mut scope, &block.statements);
+        self.scopes.push(scope);
+        walk_block(self, block);
+        self.scopes.pop();
+    }
+
+    fn check_arguments(&mut self, call: &AstFunctionCall) {
+        let parameters = match self.lookup(&call.name) {
+            Some(Binding::Function(parameters)) => { parameters.clone() }
+            _ => { return; }
+        };
+        for (index, (argument, parameter)) in call.arguments.iter().zip(parameters.iter()).enumerate() {
+            if let AstNodeType::Spread(_) = *argument {
+                return;
+            }
+            if let (Some(expected), Some(actual)) = (parameter.as_ref(), self.infer(argument)) {
+                if !accepts(expected, &actual) {
+                    let msg = format!("Argument {} of {} has type {} but was given {}", index + 1, call.name, expected, with_article(&actual));
+                    self.report(msg);
+                }
+            }
+        }
+    }
+}
+
+fn parameter_types(dec: &AstFunctionDeclaration) -> Vec<Option<String>> {
+    dec.arguments.iter().map(|argument| {
+        match argument {
+            AstNodeType::Variable(variable) => { variable.type_name.clone() }
+            _ => { None }
+        }
+    }).collect()
+}
+
+impl Visitor for TypeChecker {
+    fn visit_statement(&mut self, statement: &AstStatement) {
+        let outer = self.statement.replace(statement.file_info.clone());
+        self.visit_node(&statement.node);
+        self.statement = outer;
+    }
+
+    fn visit_block(&mut self, block: &AstBlock) {
+        self.visit_scope(HashMap::new(), block);
+    }
+
+    fn visit_function_declaration(&mut self, dec: &AstFunctionDeclaration) {
+        let mut parameters = HashMap::new();
+        for argument in &dec.arguments {
+            if let AstNodeType::Variable(ref variable) = *argument {
+                parameters.insert(variable.name.clone(), Binding::Variable(variable.type_name.clone()));
+            }
+        }
+        self.visit_scope(parameters, &dec.body);
+    }
+
+    /// The loop variable of a `for` has no known type and hides any outer
+    /// variable of the same name inside the body.
+    fn visit_function_call(&mut self, call: &AstFunctionCall) {
+        if call.name == "for" {
+            if let (Some(AstNodeType::Variable(variable)), Some(body)) = (call.arguments.first(), call.body.as_ref()) {
+                for argument in &call.arguments[1..] {
+                    self.visit_node(argument);
+                }
+                let mut scope = HashMap::new();
+                scope.insert(variable.name.clone(), Binding::Variable(None));
+                self.visit_scope(scope, body);
+                return;
+            }
+        }
+        self.check_arguments(call);
+        walk_function_call(self, call);
+    }
+
+    fn visit_assignment(&mut self, assignment: &AstAssignment) {
+        let expected = match assignment.to.type_name {
+            Some(ref type_name) => { Some(type_name.clone()) }
+            None if !assignment.declares => {
+                match self.lookup(&assignment.to.name) {
+                    Some(Binding::Variable(type_name)) => { type_name.clone() }
+                    _ => { None }
+                }
+            }
+            None => { None }
+        };
+        if let (Some(expected), Some(actual)) = (expected, self.infer(&assignment.from)) {
+            if !accepts(&expected, &actual) {
+                let msg = format!("{} has type {} but was given {}", assignment.to.name, expected, with_article(&actual));
+                self.report(msg);
+            }
+        }
+        self.visit_node(&assignment.from);
+    }
+
+    fn visit_operator_call(&mut self, operation: &AstOperatorCall) {
+        walk_operator_call(self, operation);
+        match operation.operator {
+            AstOperator::Equal | AstOperator::NotEqual => { return; }
+            _ => {}
+        }
+        if let (Some(lhs), Some(rhs)) = (self.infer(&operation.lhs), self.infer(&operation.rhs)) {
+            if operation_type(&lhs, &rhs, operation.operator).is_err() {
+                let msg = format!("Operator {} can not be applied to {} and {}", operation.operator.symbol(), with_article(&lhs), with_article(&rhs));
+                self.report(msg);
+            }
+        }
+    }
+}
+
+/// Reports every value given to an annotated name, annotated parameter or
+/// operator that has the wrong type, in the order they appear.
+pub fn check(ast: &Ast) -> Vec<Diagnostic> {
+    let mut checker = TypeChecker {
+        scopes: Vec::new(),
+        statement: None,
+        diagnostics: Vec::new()
+    };
+    checker.visit_node(&ast.root);
+    checker.diagnostics
+}
@@ -0,0 +1,362 @@
+use parser::{AstNodeType, AstBlock, AstStatement, AstOperatorCall, AstUnaryOperatorCall, AstLogicalOperatorCall, AstFunctionCall, AstStringValue, AstNumberValue,
+             AstFunctionDeclaration, AstStructDeclaration, AstVariable, AstAssignment, AstAlias, AstNullValue, AstBooleanValue, AstReturn, AstBreak, AstContinue, AstSpread,
+             AstTuple, AstDestructure, AstArrayLiteral, AstIndex, AstFieldAccess, AstFieldAssignment,
+             AstMatch, AstPattern};
+use interp::{STACK_RED_ZONE, STACK_SEGMENT};
+use stacker;
+
+/// Read-only traversal of the AST. Every method defaults to visiting the
+/// node's children through the matching `walk_*` function, so implementors
+/// only override the nodes they care about and call `walk_*` themselves to
+/// keep descending.
+pub trait Visitor {
+    fn visit_node(&mut self, node: &AstNodeType) {
+        walk_node(self, node);
+    }
+
+    fn visit_block(&mut self, block: &AstBlock) {
+        walk_block(self, block);
+    }
+
+    fn visit_statement(&mut self, statement: &AstStatement) {
+        self.visit_node(&statement.node);
+    }
+
+    fn visit_operator_call(&mut self, operation: &AstOperatorCall) {
+        walk_operator_call(self, operation);
+    }
+
+    fn visit_unary_operator_call(&mut self, operation: &AstUnaryOperatorCall) {
+        self.visit_node(&operation.operand);
+    }
+
+    fn visit_logical_operator_call(&mut self, operation: &AstLogicalOperatorCall) {
+        self.visit_node(&operation.lhs);
+        self.visit_node(&operation.rhs);
+    }
+
+    fn visit_function_call(&mut self, call: &AstFunctionCall) {
+        walk_function_call(self, call);
+    }
+
+    fn visit_function_declaration(&mut self, dec: &AstFunctionDeclaration) {
+        walk_function_declaration(self, dec);
+    }
+
+    fn visit_assignment(&mut self, assignment: &AstAssignment) {
+        self.visit_variable(&assignment.to);
+        self.visit_node(&assignment.from);
+    }
+
+    fn visit_alias(&mut self, alias: &AstAlias) {
+        self.visit_variable(&alias.to);
+        self.visit_node(&alias.from);
+    }
+
+    fn visit_struct_declaration(&mut self, _dec: &AstStructDeclaration) {}
+
+    fn visit_variable(&mut self, _variable: &AstVariable) {}
+
+    fn visit_string(&mut self, _string: &AstStringValue) {}
+
+    fn visit_number(&mut self, _number: &AstNumberValue) {}
+
+    fn visit_null(&mut self, _null: &AstNullValue) {}
+
+    fn visit_boolean(&mut self, _boolean: &AstBooleanValue) {}
+
+    fn visit_break(&mut self, _break: &AstBreak) {}
+
+    fn visit_continue(&mut self, _continue: &AstContinue) {}
+
+    fn visit_return(&mut self, ret: &AstReturn) {
+        if let Some(ref value) = ret.value {
+            self.visit_node(value);
+        }
+    }
+
+    fn visit_spread(&mut self, spread: &AstSpread) {
+        self.visit_node(&spread.value);
+    }
+
+    fn visit_tuple(&mut self, tuple: &AstTuple) {
+        for value in &tuple.values {
+            self.visit_node(value);
+        }
+    }
+
+    fn visit_destructure(&mut self, destructure: &AstDestructure) {
+        for variable in &destructure.to {
+            self.visit_variable(variable);
+        }
+        self.visit_node(&destructure.from);
+    }
+
+    fn visit_array_literal(&mut self, array: &AstArrayLiteral) {
+        for value in &array.values {
+            self.visit_node(value);
+        }
+    }
+
+    fn visit_index(&mut self, index: &AstIndex) {
+        self.visit_node(&index.value);
+        self.visit_node(&index.index);
+    }
+
+    fn visit_field_access(&mut self, access: &AstFieldAccess) {
+        self.visit_node(&access.value);
+    }
+
+    fn visit_field_assignment(&mut self, assignment: &AstFieldAssignment) {
+        self.visit_field_access(&assignment.to);
+        self.visit_node(&assignment.from);
+    }
+
+    fn visit_match(&mut self, node: &AstMatch) {
+        self.visit_node(&node.value);
+        for arm in &node.arms {
+            if let AstPattern::Literal(ref literal) = arm.pattern {
+                self.visit_node(literal);
+            }
+            self.visit_node(&arm.body);
+        }
+    }
+}
+
+pub fn walk_node<V: Visitor + ?Sized>(visitor: &mut V, node: &AstNodeType) {
+    stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, || visit_children(visitor, node));
+}
+
+fn visit_children<V: Visitor + ?Sized>(visitor: &mut V, node: &AstNodeType) {
+    match node {
+        AstNodeType::Block(block) => { visitor.visit_block(block); }
+        AstNodeType::OperatorCall(operation) => { visitor.visit_operator_call(operation); }
+        AstNodeType::UnaryOperatorCall(operation) => { visitor.visit_unary_operator_call(operation); }
+        AstNodeType::LogicalOperatorCall(operation) => { visitor.visit_logical_operator_call(operation); }
+        AstNodeType::FunctionCall(call) => { visitor.visit_function_call(call); }
+        AstNodeType::StringValue(string) => { visitor.visit_string(string); }
+        AstNodeType::NumberValue(number) => { visitor.visit_number(number); }
+        AstNodeType::FunctionDeclaration(dec) => { visitor.visit_function_declaration(dec); }
+        AstNodeType::StructDeclaration(dec) => { visitor.visit_struct_declaration(dec); }
+        AstNodeType::Variable(variable) => { visitor.visit_variable(variable); }
+        AstNodeType::Assignment(assignment) => { visitor.visit_assignment(assignment); }
+        AstNodeType::Alias(alias) => { visitor.visit_alias(alias); }
+        AstNodeType::NullValue(null) => { visitor.visit_null(null); }
+        AstNodeType::BooleanValue(boolean) => { visitor.visit_boolean(boolean); }
+        AstNodeType::Return(ret) => { visitor.visit_return(ret); }
+        AstNodeType::Break(brk) => { visitor.visit_break(brk); }
+        AstNodeType::Continue(cont) => { visitor.visit_continue(cont); }
+        AstNodeType::Spread(spread) => { visitor.visit_spread(spread); }
+        AstNodeType::Tuple(tuple) => { visitor.visit_tuple(tuple); }
+        AstNodeType::Destructure(destructure) => { visitor.visit_destructure(destructure); }
+        AstNodeType::ArrayLiteral(array) => { visitor.visit_array_literal(array); }
+        AstNodeType::Index(index) => { visitor.visit_index(index); }
+        AstNodeType::FieldAccess(access) => { visitor.visit_field_access(access); }
+        AstNodeType::FieldAssignment(assignment) => { visitor.visit_field_assignment(assignment); }
+        AstNodeType::Match(node) => { visitor.visit_match(node); }
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &AstBlock) {
+    for statement in &block.statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_operator_call<V: Visitor + ?Sized>(visitor: &mut V, operation: &AstOperatorCall) {
+    visitor.visit_node(&operation.lhs);
+    visitor.visit_node(&operation.rhs);
+}
+
+/// Visits the arguments and body of the call; a chained `next` call is
+/// visited as a call of its own.
+pub fn walk_function_call<V: Visitor + ?Sized>(visitor: &mut V, call: &AstFunctionCall) {
+    for arg in &call.arguments {
+        visitor.visit_node(arg);
+    }
+    if let Some(ref body) = call.body {
+        visitor.visit_block(body);
+    }
+    if let Some(ref next) = call.next {
+        visitor.visit_function_call(next);
+    }
+}
+
+pub fn walk_function_declaration<V: Visitor + ?Sized>(visitor: &mut V, dec: &AstFunctionDeclaration) {
+    for arg in &dec.arguments {
+        visitor.visit_node(arg);
+    }
+    visitor.visit_block(&dec.body);
+}
+
+/// The mutable counterpart of `Visitor`, for rewriting the tree in place.
+/// Replacing a whole node is done by assigning to it in `mutate_node`.
+pub trait Mutator {
+    fn mutate_node(&mut self, node: &mut AstNodeType) {
+        walk_node_mut(self, node);
+    }
+
+    fn mutate_block(&mut self, block: &mut AstBlock) {
+        walk_block_mut(self, block);
+    }
+
+    fn mutate_statement(&mut self, statement: &mut AstStatement) {
+        self.mutate_node(&mut statement.node);
+    }
+
+    fn mutate_operator_call(&mut self, operation: &mut AstOperatorCall) {
+        self.mutate_node(&mut operation.lhs);
+        self.mutate_node(&mut operation.rhs);
+    }
+
+    fn mutate_unary_operator_call(&mut self, operation: &mut AstUnaryOperatorCall) {
+        self.mutate_node(&mut operation.operand);
+    }
+
+    fn mutate_logical_operator_call(&mut self, operation: &mut AstLogicalOperatorCall) {
+        self.mutate_node(&mut operation.lhs);
+        self.mutate_node(&mut operation.rhs);
+    }
+
+    fn mutate_function_call(&mut self, call: &mut AstFunctionCall) {
+        walk_function_call_mut(self, call);
+    }
+
+    fn mutate_function_declaration(&mut self, dec: &mut AstFunctionDeclaration) {
+        for arg in &mut dec.arguments {
+            self.mutate_node(arg);
+        }
+        self.mutate_block(&mut dec.body);
+    }
+
+    fn mutate_assignment(&mut self, assignment: &mut AstAssignment) {
+        self.mutate_variable(&mut assignment.to);
+        self.mutate_node(&mut assignment.from);
+    }
+
+    fn mutate_alias(&mut self, alias: &mut AstAlias) {
+        self.mutate_variable(&mut alias.to);
+        self.mutate_node(&mut alias.from);
+    }
+
+    fn mutate_struct_declaration(&mut self, _dec: &mut AstStructDeclaration) {}
+
+    fn mutate_variable(&mut self, _variable: &mut AstVariable) {}
+
+    fn mutate_string(&mut self, _string: &mut AstStringValue) {}
+
+    fn mutate_number(&mut self, _number: &mut AstNumberValue) {}
+
+    fn mutate_null(&mut self, _null: &mut AstNullValue) {}
+
+    fn mutate_boolean(&mut self, _boolean: &mut AstBooleanValue) {}
+
+    fn mutate_break(&mut self, _break: &mut AstBreak) {}
+
+    fn mutate_continue(&mut self, _continue: &mut AstContinue) {}
+
+    fn mutate_return(&mut self, ret: &mut AstReturn) {
+        if let Some(ref mut value) = ret.value {
+            self.mutate_node(value);
+        }
+    }
+
+    fn mutate_spread(&mut self, spread: &mut AstSpread) {
+        self.mutate_node(&mut spread.value);
+    }
+
+    fn mutate_tuple(&mut self, tuple: &mut AstTuple) {
+        for value in &mut tuple.values {
+            self.mutate_node(value);
+        }
+    }
+
+    fn mutate_destructure(&mut self, destructure: &mut AstDestructure) {
+        for variable in &mut destructure.to {
+            self.mutate_variable(variable);
+        }
+        self.mutate_node(&mut destructure.from);
+    }
+
+    fn mutate_array_literal(&mut self, array: &mut AstArrayLiteral) {
+        for value in &mut array.values {
+            self.mutate_node(value);
+        }
+    }
+
+    fn mutate_index(&mut self, index: &mut AstIndex) {
+        self.mutate_node(&mut index.value);
+        self.mutate_node(&mut index.index);
+    }
+
+    fn mutate_field_access(&mut self, access: &mut AstFieldAccess) {
+        self.mutate_node(&mut access.value);
+    }
+
+    fn mutate_field_assignment(&mut self, assignment: &mut AstFieldAssignment) {
+        self.mutate_field_access(&mut assignment.to);
+        self.mutate_node(&mut assignment.from);
+    }
+
+    fn mutate_match(&mut self, node: &mut AstMatch) {
+        self.mutate_node(&mut node.value);
+        for arm in &mut node.arms {
+            if let AstPattern::Literal(ref mut literal) = arm.pattern {
+                self.mutate_node(literal);
+            }
+            self.mutate_node(&mut arm.body);
+        }
+    }
+}
+
+pub fn walk_node_mut<M: Mutator + ?Sized>(mutator: &mut M, node: &mut AstNodeType) {
+    stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, || mutate_children(mutator, node));
+}
+
+fn mutate_children<M: Mutator + ?Sized>(mutator: &mut M, node: &mut AstNodeType) {
+    match *node {
+        AstNodeType::Block(ref mut block) => { mutator.mutate_block(block); }
+        AstNodeType::OperatorCall(ref mut operation) => { mutator.mutate_operator_call(operation); }
+        AstNodeType::UnaryOperatorCall(ref mut operation) => { mutator.mutate_unary_operator_call(operation); }
+        AstNodeType::LogicalOperatorCall(ref mut operation) => { mutator.mutate_logical_operator_call(operation); }
+        AstNodeType::FunctionCall(ref mut call) => { mutator.mutate_function_call(call); }
+        AstNodeType::StringValue(ref mut string) => { mutator.mutate_string(string); }
+        AstNodeType::NumberValue(ref mut number) => { mutator.mutate_number(number); }
+        AstNodeType::FunctionDeclaration(ref mut dec) => { mutator.mutate_function_declaration(dec); }
+        AstNodeType::StructDeclaration(ref mut dec) => { mutator.mutate_struct_declaration(dec); }
+        AstNodeType::Variable(ref mut variable) => { mutator.mutate_variable(variable); }
+        AstNodeType::Assignment(ref mut assignment) => { mutator.mutate_assignment(assignment); }
+        AstNodeType::Alias(ref mut alias) => { mutator.mutate_alias(alias); }
+        AstNodeType::NullValue(ref mut null) => { mutator.mutate_null(null); }
+        AstNodeType::BooleanValue(ref mut boolean) => { mutator.mutate_boolean(boolean); }
+        AstNodeType::Return(ref mut ret) => { mutator.mutate_return(ret); }
+        AstNodeType::Break(ref mut brk) => { mutator.mutate_break(brk); }
+        AstNodeType::Continue(ref mut cont) => { mutator.mutate_continue(cont); }
+        AstNodeType::Spread(ref mut spread) => { mutator.mutate_spread(spread); }
+        AstNodeType::Tuple(ref mut tuple) => { mutator.mutate_tuple(tuple); }
+        AstNodeType::Destructure(ref mut destructure) => { mutator.mutate_destructure(destructure); }
+        AstNodeType::ArrayLiteral(ref mut array) => { mutator.mutate_array_literal(array); }
+        AstNodeType::Index(ref mut index) => { mutator.mutate_index(index); }
+        AstNodeType::FieldAccess(ref mut access) => { mutator.mutate_field_access(access); }
+        AstNodeType::FieldAssignment(ref mut assignment) => { mutator.mutate_field_assignment(assignment); }
+        AstNodeType::Match(ref mut node) => { mutator.mutate_match(node); }
+    }
+}
+
+pub fn walk_block_mut<M: Mutator + ?Sized>(mutator: &mut M, block: &mut AstBlock) {
+    for statement in &mut block.statements {
+        mutator.mutate_statement(statement);
+    }
+}
+
+pub fn walk_function_call_mut<M: Mutator + ?Sized>(mutator: &mut M, call: &mut AstFunctionCall) {
+    for arg in &mut call.arguments {
+        mutator.mutate_node(arg);
+    }
+    if let Some(ref mut body) = call.body {
+        mutator.mutate_block(body);
+    }
+    if let Some(ref mut next) = call.next {
+        mutator.mutate_function_call(next);
+    }
+}
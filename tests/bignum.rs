@@ -0,0 +1,60 @@
+#![cfg(feature = "bignum")]
+extern crate lang1;
+
+use lang1::run::run_to_string;
+
+fn error_message(source: &str) -> (&'static str, String) {
+    match run_to_string(source) {
+        Ok((output, _)) => { panic!("expected an error, got {:?}", output) }
+        Err(error) => { (error.code().code, error.to_string()) }
+    }
+}
+
+#[test]
+fn bignum_arithmetic_is_exact() {
+    let (output, _) = run_to_string("#pragma bignum\nprintln(0.1 + 0.2, 2 ^ 100, 10 - 0.25);").unwrap();
+    assert_eq!(output, "0.3 1267650600228229401496703205376 9.75\n");
+}
+
+#[test]
+fn bignum_division_is_rounded_and_powers_need_integers() {
+    let (output, _) = run_to_string("#pragma bignum\nprintln(1 / 3);").unwrap();
+    assert!(output.starts_with("0.3333333333333333333333333333333333"), "{}", output);
+
+    let (code, message) = error_message("#pragma bignum\n2 ^ 0.5;");
+    assert_eq!(code, "R0206");
+    assert!(message.contains("bignum powers need an integer exponent, got 0.5"), "{}", message);
+}
+
+#[test]
+fn exact_integers_index_arrays() {
+    let (output, _) = run_to_string("#pragma bignum\na := [10, 20, 30];\nprintln(a[1], a[2.0], get(ilist(4, 5), 1));").unwrap();
+    assert_eq!(output, "20 30 5\n");
+}
+
+#[test]
+fn exact_indices_are_bounds_checked() {
+    let (code, message) = error_message("#pragma bignum\na := [1];\na[100000000000000000000000];");
+    assert_eq!(code, "R0219");
+    assert!(message.contains("out of bounds for a length of 1"), "{}", message);
+
+    let (code, message) = error_message("#pragma bignum\na := [1];\na[0.5];");
+    assert_eq!(code, "R0206");
+    assert!(message.contains("non negative integers, found 0.5"), "{}", message);
+}
+
+#[test]
+fn loops_ranges_and_number_builtins_take_exact_numbers() {
+    let source = "#pragma bignum
+a := [10, 20, 30];
+total := 0;
+for (i, 0, 3) { total = total + a[i]; };
+for (x, range(1, 3)) { total = total + x; };
+seed(4);
+n := rand_int(1, 6);
+sleep(0);
+r := random_range(2, 3);
+if (r >= 2 && r < 3) { println(total, n); };";
+    let (output, _) = run_to_string(source).unwrap();
+    assert_eq!(output, "63 1\n");
+}
@@ -0,0 +1,73 @@
+extern crate lang1;
+
+use lang1::{Tokenizer, parse};
+use lang1::run::run_to_string;
+
+fn output(source: &str) -> String {
+    run_to_string(source).unwrap().0
+}
+
+fn error(source: &str) -> (&'static str, String) {
+    let error = run_to_string(source).unwrap_err();
+    (error.code().code, error.to_string())
+}
+
+fn parse_error(source: &str) -> String {
+    parse(Tokenizer::new(source)).unwrap_err()[0].message().to_string()
+}
+
+#[test]
+fn a_pipe_passes_the_value_as_the_first_argument() {
+    let source = "double :: (x) { return x * 2; };\nadd :: (a, b) { return a + b; };\nprintln(3 |> double |> add(1));";
+    assert_eq!(output(source), "7\n");
+}
+
+#[test]
+fn a_pipe_binds_looser_than_every_other_operator() {
+    assert_eq!(output("double :: (x) { return x * 2; };\nprintln(1 + 2 |> double);"), "6\n");
+}
+
+#[test]
+fn a_pipe_needs_a_function_on_its_right() {
+    assert_eq!(parse_error("x := 3 |> 4;"), "The right side of |> has to be a function or a call");
+}
+
+#[test]
+fn spread_expands_an_array_into_arguments() {
+    let source = "add :: (a, b, c) { return a + b + c; };\nxs := [1, 2];\nprintln(add(...xs, 3), add(0, ...[4, 5]));";
+    assert_eq!(output(source), "6 9\n");
+}
+
+#[test]
+fn arity_is_checked_on_the_spread_arguments() {
+    let (code, message) = error("add :: (a, b) { return a + b; };\nadd(...[1, 2, 3]);");
+    assert_eq!(code, "R0203");
+    assert!(message.contains("add expects 2 arguments but was called with 3"), "{}", message);
+}
+
+#[test]
+fn only_arrays_can_be_spread() {
+    let (code, message) = error("add :: (a, b) { return a + b; };\nadd(...5);");
+    assert_eq!(code, "R0204");
+    assert!(message.contains("Only arrays can be spread with ..., got an Int"), "{}", message);
+}
+
+#[test]
+fn bind_fills_in_the_first_arguments() {
+    let source = "add :: (a, b, c) { return a + b + c; };\nadd1 := bind(add, 1);\nadd12 := bind(add1, 2);\nprintln(add1(2, 3), add12(4));";
+    assert_eq!(output(source), "6 7\n");
+}
+
+#[test]
+fn bind_can_not_fill_in_more_arguments_than_declared() {
+    let (code, message) = error("add :: (a, b) { return a + b; };\nbind(add, 1, 2, 3);");
+    assert_eq!(code, "R0203");
+    assert!(message.contains("Can not bind 3 arguments to a function taking 2"), "{}", message);
+}
+
+#[test]
+fn a_bound_function_counts_only_the_remaining_arguments() {
+    let (code, message) = error("add :: (a, b, c) { return a + b + c; };\nadd1 := bind(add, 1);\nadd1(2);");
+    assert_eq!(code, "R0203");
+    assert!(message.contains("add1 expects 2 arguments but was called with 1"), "{}", message);
+}
@@ -0,0 +1,28 @@
+extern crate lang1;
+
+use lang1::diagnostic::check_all;
+
+use std::env;
+use std::fs;
+use std::process::{self, Command};
+
+#[test]
+fn check_all_includes_type_errors() {
+    let diagnostics = check_all("x :String := 5;\nprintln(x, z);");
+    let codes: Vec<&str> = diagnostics.iter().map(|diagnostic| diagnostic.code.code).collect();
+    assert_eq!(codes, vec!["T0301", "R0201"]);
+}
+
+#[test]
+fn check_all_flag_renders_diagnostics() {
+    let path = env::temp_dir().join(format!("lang1-check-all-{}.leg", process::id()));
+    fs::write(&path, "x :String := 5;\nprintln(x);").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lang1")).arg("--check-all").arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let expected = format!("error[T0301]: x has type String but was given an Int\n --> {}:1:1\n  |\n1 | x :String := 5;\n  | ^^^^^^^^^^^^^^^\n  = help: Type annotation mismatch, see `lang1 explain T0301`\n\n",
+                           path.display());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), expected);
+    assert!(output.stdout.is_empty());
+    assert!(!output.status.success());
+}
@@ -0,0 +1,84 @@
+extern crate lang1;
+
+use lang1::run::run_to_string;
+
+fn output(source: &str) -> String {
+    run_to_string(source).unwrap().0
+}
+
+fn error(source: &str) -> (&'static str, String) {
+    let error = run_to_string(source).unwrap_err();
+    (error.code().code, error.to_string())
+}
+
+#[test]
+fn updating_an_ilist_leaves_the_original_unchanged() {
+    let source = "l := ilist(1, 2);\nl2 := with(l, 3);\nl3 := with(l2, 0, 9);\nprintln(l, l2, l3, without(l3, 1), get(l3, 2), len(l3));";
+    assert_eq!(output(source), "[1, 2] [1, 2, 3] [9, 2, 3] [9, 3] 3 3\n");
+}
+
+#[test]
+fn an_imap_is_ordered_by_key_and_updates_into_a_new_map() {
+    let source = "m := imap(\"b\", 2, \"a\", 1);\nm2 := with(m, \"c\", 3);\nprintln(m, m2, without(m2, \"a\"), get(m, \"z\"), m2[\"c\"], len(m2));";
+    assert_eq!(output(source), "{\"a\": 1, \"b\": 2} {\"a\": 1, \"b\": 2, \"c\": 3} {\"b\": 2, \"c\": 3} null 3 3\n");
+}
+
+#[test]
+fn persistent_collections_report_bad_arguments() {
+    let (code, message) = error("imap(1, \"x\", 2);");
+    assert_eq!(code, "R0203");
+    assert!(message.contains("imap expects keys and values in pairs"), "{}", message);
+
+    let (code, message) = error("with(ilist(1), 5, 2);");
+    assert_eq!(code, "R0219");
+    assert!(message.contains("Index 5 is out of bounds for a length of 1"), "{}", message);
+}
+
+#[test]
+fn arrays_are_indexed_from_zero_and_copies_share_their_elements() {
+    let source = "a := [1, 2, 3];\nb := a;\npush(b, 4);\nprintln(a[0], a[3], len(a), [], [[1], \"s\"]);";
+    assert_eq!(output(source), "1 4 4 [] [[1], \"s\"]\n");
+}
+
+#[test]
+fn indices_have_to_be_in_bounds_non_negative_integers() {
+    let cases = [
+        ("[1][3];", "R0219", "Index 3 is out of bounds for a length of 1"),
+        ("[1][-1];", "R0206", "Indices must be non negative integers, found -1"),
+        ("[1][0.5];", "R0206", "Indices must be non negative integers, found 0.5"),
+        ("[1][\"a\"];", "R0204", "Indices must be integers, found a String"),
+        ("5[0];", "R0204", "Can not index an Int")
+    ];
+    for &(source, expected_code, expected_message) in &cases {
+        let (code, message) = error(source);
+        assert_eq!(code, expected_code, "{}", source);
+        assert!(message.contains(expected_message), "{}", message);
+    }
+}
+
+#[test]
+fn arrays_change_in_place() {
+    let source = "a := [1, 2, 3];\nprintln(pop(a), a);\ninsert(a, 0, 0);\nprintln(remove(a, 1), a, pop([]));";
+    assert_eq!(output(source), "3 [1, 2]\n1 [0, 2] null\n");
+}
+
+#[test]
+fn slice_concat_and_join_make_new_values() {
+    let source = "println(slice([1, 2, 3, 4], 1), slice([1, 2, 3, 4], 1, 3), slice(ilist(1, 2, 3), 2));\nprintln(concat([1], ilist(2), [3]), join([1, \"a\", true], \"-\"));";
+    assert_eq!(output(source), "[2, 3, 4] [2, 3] [3]\n[1, 2, 3] 1-a-true\n");
+}
+
+#[test]
+fn list_builtins_check_their_arguments() {
+    let cases = [
+        ("insert([1], 3, 0);", "R0219", "Index 3 is out of bounds for a length of 1"),
+        ("slice([1, 2], 2, 1);", "R0219", "slice starts at 2 which is after its end 1"),
+        ("push(ilist(1), 2);", "R0204", "push expects an Array or a Builder but was given an IList"),
+        ("join([1], 2);", "R0204", "join expects a String separator but was given an Int")
+    ];
+    for &(source, expected_code, expected_message) in &cases {
+        let (code, message) = error(source);
+        assert_eq!(code, expected_code, "{}", source);
+        assert!(message.contains(expected_message), "{}", message);
+    }
+}
@@ -0,0 +1,56 @@
+extern crate lang1;
+
+use lang1::{Engine, InterpValue, SharedBuffer, LegError};
+
+#[test]
+fn later_scripts_see_what_earlier_ones_declared() {
+    let mut engine = Engine::new();
+    engine.eval_str("base := 40;\nadd :: (a) { return base + a; };").unwrap();
+    assert!(matches!(engine.eval_str("add(2);"), Ok(InterpValue::InterpInt(42))));
+    assert!(matches!(engine.get_global("base"), Some(InterpValue::InterpInt(40))));
+}
+
+#[test]
+fn functions_outlive_many_scripts() {
+    let mut engine = Engine::new();
+    for index in 0..100 {
+        engine.eval_str(&format!("f{} :: () {{ return {}; }};", index, index)).unwrap();
+    }
+    assert!(matches!(engine.eval_str("f0() + f99();"), Ok(InterpValue::InterpInt(99))));
+    drop(engine);
+}
+
+#[test]
+fn an_error_keeps_what_was_declared_before_it() {
+    let mut engine = Engine::new();
+    let error = engine.eval_str("x := 1;\ny := missing();").unwrap_err();
+    assert!(matches!(error, LegError::Interp(_)));
+    assert!(matches!(engine.get_global("x"), Some(InterpValue::InterpInt(1))));
+
+    assert!(matches!(engine.eval_str("x := (;"), Err(LegError::Parsing(_))));
+    assert!(matches!(engine.eval_str("x;"), Ok(InterpValue::InterpInt(1))));
+}
+
+#[test]
+fn hosts_provide_globals_functions_and_output() {
+    let mut engine = Engine::new();
+    let output = SharedBuffer::new();
+    engine.set_output(output.clone());
+    engine.set_global("base", InterpValue::InterpInt(20));
+    engine.register_fn("double", |args: &[InterpValue]| {
+        match args.first() {
+            Some(&InterpValue::InterpInt(n)) => Ok(InterpValue::InterpInt(n * 2)),
+            _ => Ok(InterpValue::InterpVoid)
+        }
+    });
+    engine.eval_str("println(double(base) + 2);").unwrap();
+    assert_eq!(output.take(), "42\n");
+}
+
+#[test]
+fn strict_engines_reject_truthy_conditions() {
+    let mut engine = Engine::new();
+    engine.set_strict(true);
+    assert!(engine.eval_str("if (1) { 2; };").is_err());
+    assert!(engine.eval_str("if (true) { 2; };").is_ok());
+}
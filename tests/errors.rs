@@ -0,0 +1,90 @@
+extern crate lang1;
+
+use lang1::{tokenize, Tokenizer, parse, Interp, TokenizationError};
+use lang1::diagnostic::render_interp_error;
+use lang1::run::run_to_string;
+
+use std::env;
+use std::fs;
+use std::process::{self, Command};
+
+#[test]
+fn runtime_error_messages_leave_the_location_to_the_span() {
+    let ast = parse(Tokenizer::new("x := [1];\nprintln(x[5]);")).unwrap();
+    let error = Interp::new().run(&ast).unwrap_err();
+    assert_eq!(error.message(), "Index 5 is out of bounds for a length of 1");
+    assert_eq!(error.span().unwrap().line_number_from, 2);
+
+    let ast = parse(Tokenizer::new("f :: (a) { return a; };\nf(1, 2);")).unwrap();
+    let error = Interp::new().run(&ast).unwrap_err();
+    assert!(!error.message().contains("line"), "{}", error.message());
+    assert_eq!(error.span().unwrap().line_number_from, 2);
+}
+
+#[test]
+fn tokenizer_error_messages_leave_the_location_to_the_span() {
+    let error: TokenizationError = tokenize("x := 1.2.3;").unwrap_err();
+    assert!(!error.message().contains("column"), "{}", error.message());
+    assert_eq!(error.get_file_info().column_number_from, 9);
+}
+
+#[test]
+fn runtime_errors_are_rendered_with_the_calls_they_happened_in() {
+    let source = "f :: (a) {\n    return a[3];\n};\nf([1]);";
+    let ast = parse(Tokenizer::new(source)).unwrap();
+    let error = Interp::new().run(&ast).unwrap_err();
+    let expected = "error[R0219]: Index 3 is out of bounds for a length of 1
+ --> script.leg:2:12
+  |
+2 |     return a[3];
+  |            ^^^^
+  = help: Index out of bounds, see `lang1 explain R0219`
+  = note: in f called at script.leg:4:1
+
+";
+    assert_eq!(render_interp_error(&error, source, "script.leg"), expected);
+}
+
+#[test]
+fn the_binary_renders_runtime_errors() {
+    let path = env::temp_dir().join(format!("lang1-errors-{}.leg", process::id()));
+    fs::write(&path, "x := [1];\nprintln(x[5]);").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lang1")).arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("error[R0219]: Index 5 is out of bounds for a length of 1\n"), "{}", stderr);
+    assert_eq!(stderr.matches(":2:").count() + stderr.matches("line 2").count(), 1, "{}", stderr);
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn arity_errors_count_their_arguments() {
+    let message = |source: &str| run_to_string(source).unwrap_err().to_string();
+    assert!(message("f :: (a) { return a; };\nf(1, 2);").contains("f expects 1 argument but was called with 2"));
+    assert!(message("P :: { x :Int };\nP(1, 2);").contains("The struct expects 1 field but was given 2"));
+    assert!(message("a, b := 1;").contains("Can not assign 1 value to 2 names"));
+}
+
+#[test]
+fn break_and_return_outside_their_construct_have_their_own_codes() {
+    let code = |source: &str| parse(Tokenizer::new(source)).unwrap_err()[0].code().code;
+    assert_eq!(code("f :: () { break; };"), "P0112");
+    assert_eq!(code("continue;"), "P0112");
+    assert_eq!(code("return 1;"), "P0113");
+    assert!(lang1::error_codes::explain("P0112").is_some());
+    assert!(lang1::error_codes::explain("P0113").is_some());
+}
+
+#[test]
+fn unknown_pragmas_are_errors() {
+    let error = run_to_string("#pragma fast\nprintln(1);").unwrap_err();
+    assert_eq!(error.code().code, "P0114");
+    assert!(error.to_string().contains("Unknown pragma fast, expected bignum or strict"), "{}", error);
+}
+
+#[test]
+#[cfg(not(feature = "bignum"))]
+fn the_bignum_pragma_needs_the_bignum_feature() {
+    assert_eq!(run_to_string("#pragma bignum\nprintln(1);").unwrap_err().code().code, "R0216");
+}
@@ -0,0 +1,32 @@
+use std::env;
+use std::fs;
+use std::process::{self, Command};
+
+/// The exit code of the lang1 binary run on `source` saved as a script.
+fn exit_code(name: &str, source: &str) -> Option<i32> {
+    let path = env::temp_dir().join(format!("lang1-exit-{}-{}.leg", name, process::id()));
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lang1")).arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+    output.status.code()
+}
+
+#[test]
+fn scripts_that_run_exit_with_zero() {
+    assert_eq!(exit_code("ok", "x := 1;\nprintln(x);"), Some(0));
+}
+
+#[test]
+fn every_error_exits_with_one() {
+    assert_eq!(exit_code("tokenize", "x := \"unterminated;"), Some(1));
+    assert_eq!(exit_code("parse", "x := (1 + ;"), Some(1));
+    assert_eq!(exit_code("resolve", "println(y);"), Some(1));
+    assert_eq!(exit_code("pragma", "#pragma unknown\nprintln(1);"), Some(1));
+    assert_eq!(exit_code("runtime", "x := [1];\nprintln(x[5]);"), Some(1));
+}
+
+#[test]
+fn missing_scripts_exit_with_one() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lang1")).arg("/nonexistent/script.leg").output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+}
@@ -0,0 +1,49 @@
+extern crate lang1;
+
+use lang1::run::run_to_string;
+
+fn output(source: &str) -> String {
+    run_to_string(source).unwrap().0
+}
+
+#[test]
+fn booleans_print_as_true_and_false() {
+    assert_eq!(output("println(true, !true);"), "true false\n");
+    assert_eq!(output("println(format(\"{}\", 1 < 2));"), "true\n");
+}
+
+#[test]
+fn functions_and_instances_print_readably() {
+    let source = "P :: { x :Int y :String };\nf :: (a) { return a; };\nprintln(f, P, P(1, \"two\"));";
+    assert_eq!(output(source), "<function> <struct> P(1, \"two\")\n");
+}
+
+#[test]
+fn collections_quote_their_strings() {
+    assert_eq!(output("println([true, \"s\", 1], \"s\");"), "[true, \"s\", 1] s\n");
+}
+
+#[test]
+fn debug_keeps_the_tagged_forms() {
+    let source = "P :: { x :Int };\nf :: (a) { return a; };\ndebug(true, f, P(1));\nprintln(format(\"{:?}\", false));";
+    assert_eq!(output(source), "BOOLEAN {true} FUNCTION 0 STRUCT 0 {1}\nBOOLEAN {false}\n");
+}
+
+#[test]
+fn format_fills_placeholders_and_unescapes_braces() {
+    assert_eq!(output("println(format(\"{{}} {} {:?}\", 1, \"s\"));"), "{} 1 \"s\"\n");
+}
+
+#[test]
+fn format_checks_its_template() {
+    let cases = [
+        ("format(\"{} {}\", 1);", "R0203", "The format string \"{} {}\" has 2 placeholders but was given 1 value"),
+        ("format(\"{x}\", 1);", "R0222", "Invalid format string \"{x}\", write {{ and }} for braces"),
+        ("format(1);", "R0204", "format expects a String to format into but was given an Int")
+    ];
+    for &(source, expected_code, expected_message) in &cases {
+        let error = run_to_string(source).unwrap_err();
+        assert_eq!(error.code().code, expected_code, "{}", source);
+        assert!(error.to_string().contains(expected_message), "{}", error);
+    }
+}
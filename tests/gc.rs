@@ -0,0 +1,54 @@
+extern crate lang1;
+
+use lang1::{Tokenizer, parse, Interp, InterpValue};
+
+fn run(source: &str) -> (Result<InterpValue, lang1::InterpError>, usize, usize) {
+    let ast = parse(Tokenizer::new(source)).unwrap();
+    let mut interp = Interp::new();
+    let res = interp.run(&ast);
+    let stats = interp.stats();
+    (res, stats.allocations, stats.live_closures)
+}
+
+#[test]
+fn closures_nothing_refers_to_are_freed() {
+    let (res, allocations, live) = run("f :: (n) { return n + 1; };\ni := 0;\nwhile (i < 5000) { f(i); i = i + 1; };\ni;");
+    assert!(matches!(res, Ok(InterpValue::InterpInt(5000))));
+    assert!(allocations > 5000, "{}", allocations);
+    assert!(live < 1000, "{} closures are still live", live);
+}
+
+#[test]
+fn closures_values_refer_to_are_kept() {
+    let source = "make :: (n) {
+    get :: () { return n; };
+    return get;
+};
+keep := [];
+i := 0;
+while (i < 5000) {
+    f := make(i);
+    if (i % 1000 == 0) { push(keep, f); };
+    i = i + 1;
+};
+g := keep[3];
+g();";
+    let (res, allocations, live) = run(source);
+    assert!(matches!(res, Ok(InterpValue::InterpInt(3000))));
+    assert!(allocations > 10000, "{}", allocations);
+    assert!(live < 2000, "{} closures are still live", live);
+}
+
+#[test]
+fn closures_that_refer_to_themselves_are_freed() {
+    let source = "count :: (n) {
+    down :: (k) { if (k == 0) { return 0; }; return down(k - 1); };
+    return down(n);
+};
+i := 0;
+while (i < 2000) { count(3); i = i + 1; };
+i;";
+    let (res, _, live) = run(source);
+    assert!(matches!(res, Ok(InterpValue::InterpInt(2000))));
+    assert!(live < 1000, "{} closures are still live", live);
+}
@@ -0,0 +1,71 @@
+extern crate lang1;
+
+use lang1::{Tokenizer, parse, Interp, InterpValue};
+
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::process;
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn reload_changed_swaps_in_changed_functions() {
+    let path = env::temp_dir().join(format!("lang1-hot-reload-{}.leg", process::id()));
+    fs::write(&path, "x := 40;\nf :: () { return x + 1; };").unwrap();
+
+    let script = parse(Tokenizer::new(&fs::read_to_string(&path).unwrap())).unwrap();
+    let call = parse(Tokenizer::new("f();")).unwrap();
+    let mut interp = Interp::new();
+    interp.run(&script).unwrap();
+    interp.watch_script(path.to_str().unwrap());
+    assert_eq!(interp.reload_changed(), Ok(Vec::new()));
+
+    fs::write(&path, "x := 0;\nf :: () { return x + 2; };").unwrap();
+    // File systems with coarse timestamps may not see the write as a change
+    let later = SystemTime::now() + Duration::from_secs(2);
+    File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+    assert_eq!(interp.reload_changed(), Ok(vec![String::from("f")]));
+
+    // The global keeps its value, only the function body changed
+    assert!(matches!(interp.run(&call), Ok(InterpValue::InterpInt(42))));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn reload_changed_keeps_the_old_code_when_the_script_does_not_parse() {
+    let path = env::temp_dir().join(format!("lang1-hot-reload-broken-{}.leg", process::id()));
+    fs::write(&path, "f :: () { return 1; };").unwrap();
+
+    let script = parse(Tokenizer::new(&fs::read_to_string(&path).unwrap())).unwrap();
+    let call = parse(Tokenizer::new("f();")).unwrap();
+    let mut interp = Interp::new();
+    interp.run(&script).unwrap();
+    interp.watch_script(path.to_str().unwrap());
+
+    fs::write(&path, "f :: () { return 2;").unwrap();
+    let later = SystemTime::now() + Duration::from_secs(2);
+    File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+    assert!(interp.reload_changed().is_err());
+    assert!(matches!(interp.run(&call), Ok(InterpValue::InterpInt(1))));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn reload_changed_declares_new_functions_as_globals() {
+    let path = env::temp_dir().join(format!("lang1-hot-reload-new-{}.leg", process::id()));
+    fs::write(&path, "f :: () { return 1; };").unwrap();
+
+    let script = parse(Tokenizer::new(&fs::read_to_string(&path).unwrap())).unwrap();
+    let call = parse(Tokenizer::new("f() + g();")).unwrap();
+    let mut interp = Interp::new();
+    interp.run(&script).unwrap();
+    interp.watch_script(path.to_str().unwrap());
+
+    fs::write(&path, "f :: () { return 1; };\ng :: () { return 2; };").unwrap();
+    let later = SystemTime::now() + Duration::from_secs(2);
+    File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+    assert_eq!(interp.reload_changed(), Ok(vec![String::from("f")]));
+    assert!(matches!(interp.get_global("g"), Some(InterpValue::InterpFunction{..})));
+    assert!(matches!(interp.run(&call), Ok(InterpValue::InterpInt(3))));
+    fs::remove_file(&path).unwrap();
+}
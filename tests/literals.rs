@@ -0,0 +1,59 @@
+extern crate lang1;
+
+use lang1::{Tokenizer, parse, ParsingError};
+use lang1::run::run_to_string;
+
+fn output(source: &str) -> String {
+    run_to_string(source).unwrap().0
+}
+
+fn first_error(source: &str) -> ParsingError {
+    parse(Tokenizer::new(source)).unwrap_err().remove(0)
+}
+
+#[test]
+fn string_escapes_are_decoded() {
+    assert_eq!(output("print(\"a\\tb\\\\c\\\"d\\u{41}\\n\");"), "a\tb\\c\"dA\n");
+}
+
+#[test]
+fn an_unknown_escape_is_reported_at_its_string() {
+    let error = first_error("x := \"a\\qb\";");
+    assert_eq!(error.code().code, "L0007");
+    assert_eq!(error.message(), "Unknown escape sequence \\q");
+    assert_eq!(error.get_file_info().column_number_from, 6);
+}
+
+#[test]
+fn numbers_can_be_written_in_hexadecimal_binary_and_octal() {
+    assert_eq!(output("println(0xFF, 0b1010, 0o755);"), "255 10 493\n");
+}
+
+#[test]
+fn e_is_a_hexadecimal_digit_rather_than_an_exponent() {
+    assert_eq!(output("println(0x1e-3);"), "27\n");
+}
+
+#[test]
+fn a_prefix_needs_digits_of_its_base() {
+    let error = first_error("x := 0b12;");
+    assert_eq!(error.code().code, "L0001");
+    assert_eq!(error.message(), "Malformed number 0b12: expected base 2 digits after 0b");
+    assert_eq!(first_error("x := 0x;").code().code, "L0001");
+}
+
+#[test]
+fn underscores_separate_digits_and_exponents_scale() {
+    assert_eq!(output("println(1_000_000, 0xFF_FF, 1.5e-3, 2E3);"), "1000000 65535 0.0015 2000\n");
+}
+
+#[test]
+fn number_errors_point_at_the_problem() {
+    let error = first_error("x := 1__0;");
+    assert_eq!(error.message(), "Malformed number 1__0: _ can only separate two digits");
+    assert_eq!(error.get_file_info().column_number_from, 7);
+
+    let error = first_error("x := 1e;");
+    assert_eq!(error.message(), "Malformed number 1e: expected digits in the exponent");
+    assert_eq!(error.get_file_info().column_number_from, 8);
+}
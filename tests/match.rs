@@ -0,0 +1,30 @@
+extern crate lang1;
+
+use lang1::{Tokenizer, parse};
+use lang1::run::run_to_string;
+
+fn output(source: &str) -> String {
+    run_to_string(source).unwrap().0
+}
+
+#[test]
+fn match_picks_the_first_arm_whose_literal_equals_the_value() {
+    let source = "name :: (n) { return match(n) { 1 -> \"one\"; -1 -> \"minus one\"; \"s\" -> \"string\"; true -> \"yes\"; null -> \"nothing\"; _ -> \"other\" }; };\nprintln(name(1), name(-1), name(\"s\"), name(true), name(null), name(2));";
+    assert_eq!(output(source), "one minus one string yes nothing other\n");
+}
+
+#[test]
+fn match_compares_ints_and_numbers_by_value() {
+    assert_eq!(output("println(match(1) { 1.0 -> \"one\"; _ -> \"other\" });"), "one\n");
+}
+
+#[test]
+fn match_without_a_matching_arm_is_null() {
+    assert_eq!(output("println(match(3) { 1 -> \"one\" });"), "null\n");
+}
+
+#[test]
+fn match_patterns_have_to_be_literals() {
+    let errors = parse(Tokenizer::new("x := match(1) { y -> 1 };")).unwrap_err();
+    assert_eq!(errors[0].message(), "Match patterns must be a number, string, boolean, null or _");
+}
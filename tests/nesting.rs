@@ -0,0 +1,45 @@
+extern crate lang1;
+
+use lang1::{Tokenizer, parse, Interp, InterpOptions};
+use std::thread;
+
+/// Runs `source` with room for `frames` frames, on a thread whose native
+/// stack is far too small to recurse that deep. Returns the debug form of
+/// the result, or the code of the error.
+fn run_nested(source: &'static str, frames: usize) -> Result<String, String> {
+    thread::Builder::new()
+        .stack_size(256 * 1024)
+        .spawn(move || {
+            let ast = parse(Tokenizer::new(source)).unwrap();
+            let mut interp = Interp::new();
+            interp.set_options(InterpOptions { stack_size: Some(frames), ..InterpOptions::default() });
+            interp.run(&ast)
+                .map(|value| format!("{:?}", value))
+                .map_err(|error| error.code().code.to_string())
+        })
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+#[test]
+fn recursion_is_limited_by_the_stack_size_only() {
+    let source = "depth :: (n) { if (n == 0) { return 0; }; return 1 + depth(n - 1); };\ndepth(20000);";
+    let res = run_nested(source, 50000);
+    assert_eq!(res.unwrap(), "InterpInt(20000)");
+}
+
+#[test]
+fn recursion_past_the_stack_size_is_an_error() {
+    let source = "depth :: (n) { if (n == 0) { return 0; }; return 1 + depth(n - 1); };\ndepth(20000);";
+    let res = run_nested(source, 1000);
+    assert_eq!(res.unwrap_err(), "R0210");
+}
+
+#[test]
+fn unwinding_deep_recursion_leaves_every_frame() {
+    let source = "fail :: (n) { if (n == 0) { return [][1]; }; for (i, 0, 1) { x := fail(n - 1); }; return 0; };
+fail(5000);";
+    let res = run_nested(source, 20000);
+    assert_eq!(res.unwrap_err(), "R0219");
+}
@@ -0,0 +1,90 @@
+extern crate lang1;
+
+use lang1::{Tokenizer, parse, Interp, InterpValue, InterpError};
+
+fn eval(source: &str, strict_overflow: bool, strict_division: bool) -> Result<InterpValue, InterpError> {
+    let ast = parse(Tokenizer::new(source)).unwrap();
+    let mut interp = Interp::new();
+    interp.set_strict_overflow(strict_overflow);
+    interp.set_strict_division(strict_division);
+    interp.run(&ast)
+}
+
+fn error_code(res: Result<InterpValue, InterpError>) -> &'static str {
+    match res {
+        Err(error) => { error.code().code }
+        Ok(value) => { panic!("expected an error, got {:?}", value) }
+    }
+}
+
+#[test]
+fn int_arithmetic_wraps_around_by_default() {
+    assert!(matches!(eval("9223372036854775807 + 1;", false, false), Ok(InterpValue::InterpInt(i64::MIN))));
+    assert!(matches!(eval("(-9223372036854775807 - 1) - 1;", false, false), Ok(InterpValue::InterpInt(i64::MAX))));
+    assert!(matches!(eval("4611686018427387904 * 4;", false, false), Ok(InterpValue::InterpInt(0))));
+    assert!(matches!(eval("2 ^ 64;", false, false), Ok(InterpValue::InterpInt(0))));
+    assert!(matches!(eval("-(-9223372036854775807 - 1);", false, false), Ok(InterpValue::InterpInt(i64::MIN))));
+    assert!(matches!(eval("(-9223372036854775807 - 1) % -1;", false, false), Ok(InterpValue::InterpInt(0))));
+}
+
+#[test]
+fn int_overflow_is_an_error_with_strict_overflow() {
+    assert_eq!(error_code(eval("9223372036854775807 + 1;", true, false)), "R0208");
+    assert_eq!(error_code(eval("(-9223372036854775807 - 1) - 1;", true, false)), "R0208");
+    assert_eq!(error_code(eval("4611686018427387904 * 4;", true, false)), "R0208");
+    assert_eq!(error_code(eval("2 ^ 64;", true, false)), "R0208");
+    assert_eq!(error_code(eval("-(-9223372036854775807 - 1);", true, false)), "R0208");
+    assert_eq!(error_code(eval("(-9223372036854775807 - 1) % -1;", true, false)), "R0208");
+
+    assert!(matches!(eval("2 ^ 62;", true, false), Ok(InterpValue::InterpInt(4611686018427387904))));
+    assert!(matches!(eval("1 ^ 10000000000;", true, false), Ok(InterpValue::InterpInt(1))));
+    assert!(matches!(eval("9223372036854775806 + 1;", true, false), Ok(InterpValue::InterpInt(i64::MAX))));
+}
+
+#[test]
+fn number_overflow_is_an_error_with_strict_overflow() {
+    assert!(matches!(eval("10.0 ^ 400;", false, false), Ok(InterpValue::InterpNumber(n)) if n.is_infinite()));
+    assert_eq!(error_code(eval("10.0 ^ 400;", true, false)), "R0208");
+}
+
+#[test]
+fn int_and_number_division_by_zero_follow_the_same_rule() {
+    assert!(matches!(eval("1 / 0;", false, false), Ok(InterpValue::InterpNumber(n)) if n == f64::INFINITY));
+    assert!(matches!(eval("1 % 0;", false, false), Ok(InterpValue::InterpNumber(n)) if n.is_nan()));
+    assert!(matches!(eval("1.0 % 0.0;", false, false), Ok(InterpValue::InterpNumber(n)) if n.is_nan()));
+
+    assert_eq!(error_code(eval("1 / 0;", false, true)), "R0209");
+    assert_eq!(error_code(eval("1 % 0;", false, true)), "R0209");
+    assert_eq!(error_code(eval("1.0 / 0.0;", false, true)), "R0209");
+    assert_eq!(error_code(eval("1.0 % 0.0;", false, true)), "R0209");
+}
+
+#[test]
+fn ints_promote_to_numbers_when_mixed() {
+    assert!(matches!(eval("1 + 0.5;", false, false), Ok(InterpValue::InterpNumber(n)) if n == 1.5));
+    assert!(matches!(eval("7 / 2;", false, false), Ok(InterpValue::InterpNumber(n)) if n == 3.5));
+    assert!(matches!(eval("2 ^ -1;", false, false), Ok(InterpValue::InterpNumber(n)) if n == 0.5));
+    assert!(matches!(eval("7 % 2;", false, false), Ok(InterpValue::InterpInt(1))));
+}
+
+#[test]
+fn bitwise_operators_work_on_integers() {
+    assert!(matches!(eval("6 & 3;", false, false), Ok(InterpValue::InterpInt(2))));
+    assert!(matches!(eval("6 | 3;", false, false), Ok(InterpValue::InterpInt(7))));
+    assert!(matches!(eval("1 << 4;", false, false), Ok(InterpValue::InterpInt(16))));
+    assert!(matches!(eval("-16 >> 2;", false, false), Ok(InterpValue::InterpInt(-4))));
+    assert!(matches!(eval("~5;", false, false), Ok(InterpValue::InterpInt(-6))));
+}
+
+#[test]
+fn shifts_bind_looser_than_sums_and_tighter_than_comparisons() {
+    assert!(matches!(eval("1 + 1 << 2;", false, false), Ok(InterpValue::InterpInt(8))));
+    assert!(matches!(eval("6 & 3 | 8;", false, false), Ok(InterpValue::InterpInt(10))));
+    assert!(matches!(eval("1 << 2 == 4;", false, false), Ok(InterpValue::InterpBoolean(true))));
+}
+
+#[test]
+fn bitwise_operators_reject_fractions_and_wide_shifts() {
+    assert_eq!(error_code(eval("1.5 & 1;", false, false)), "R0206");
+    assert_eq!(error_code(eval("1 << 64;", false, false)), "R0207");
+}
@@ -0,0 +1,27 @@
+extern crate lang1;
+
+use lang1::run::run_to_string;
+
+fn output(source: &str) -> String {
+    run_to_string(source).unwrap().0
+}
+
+#[test]
+fn print_writes_its_arguments_on_one_line_without_ending_it() {
+    assert_eq!(output("print(\"a\", 1);\nprint(\"b\");"), "a 1b");
+}
+
+#[test]
+fn println_ends_the_line() {
+    assert_eq!(output("println(\"x\", \"y\", 2.5);\nprintln();\nprintln(\"z\");"), "x y 2.5\n\nz\n");
+}
+
+#[test]
+fn print_formats_a_first_argument_with_placeholders() {
+    assert_eq!(output("println(\"{} and {}\", 1, \"two\");\nprintln(\"{}\");"), "1 and two\n{}\n");
+}
+
+#[test]
+fn debug_quotes_strings() {
+    assert_eq!(output("debug(\"s\", 1, [\"t\"]);"), "\"s\" 1 [\"t\"]\n");
+}
@@ -0,0 +1,58 @@
+extern crate lang1;
+
+use lang1::{tokenize, parse, Tokenizer, Token, TokenizationError, Ast, AstNodeType, ParsingError};
+
+fn statements(ast: &Ast) -> usize {
+    match &ast.root {
+        AstNodeType::Block(block) => { block.statements.len() }
+        other => { panic!("expected a block, found {:?}", other); }
+    }
+}
+
+fn codes(errors: &[ParsingError]) -> Vec<&'static str> {
+    errors.iter().map(|error| error.code().code).collect()
+}
+
+#[test]
+fn a_script_is_parsed_straight_from_the_tokenizer() {
+    let ast = parse(Tokenizer::new("x := 1;\n# a comment\nx + 1;")).unwrap();
+    assert_eq!(statements(&ast), 2);
+}
+
+#[test]
+fn tokens_that_cannot_be_read_are_parsing_errors() {
+    let errors = parse(Tokenizer::new("x := 1.2.3;\ny := 2;")).unwrap_err();
+    assert_eq!(codes(&errors), vec!["L0001"]);
+    assert_eq!(errors[0].get_file_info().line_number_from, 1);
+}
+
+#[test]
+fn parsing_goes_on_after_a_token_that_cannot_be_read() {
+    let errors = parse(Tokenizer::new("x := 1.2.3;\ny := );")).unwrap_err();
+    assert_eq!(codes(&errors), vec!["L0001", "P0101"]);
+    assert_eq!(errors[1].get_file_info().line_number_from, 2);
+}
+
+#[test]
+fn collected_tokens_parse_like_the_stream() {
+    let source = "f :: (a) { return a * 2; };\nf(21);";
+    let tokens = tokenize(source).unwrap();
+    let collected = parse(tokens.into_iter().map(Ok::<Token, TokenizationError>)).unwrap();
+    let streamed = parse(Tokenizer::new(source)).unwrap();
+    assert_eq!(format!("{:?}", collected), format!("{:?}", streamed));
+}
+
+#[test]
+fn a_repl_entry_may_leave_out_its_last_semicolon() {
+    use lang1::repl::parse_entry;
+    assert_eq!(statements(&parse_entry("x := 1; x + 1").unwrap()), 2);
+    assert_eq!(statements(&parse_entry("x + 1 # the sum\n").unwrap()), 1);
+    assert_eq!(statements(&parse_entry("x + 1;").unwrap()), 1);
+}
+
+#[test]
+fn assigning_to_an_index_is_reported_as_such() {
+    let errors = parse(Tokenizer::new("a := [1, 2];\na[1] = 9;")).unwrap_err();
+    assert_eq!(codes(&errors), vec!["P0111"]);
+    assert!(errors[0].message().starts_with("Can not assign to an index expression"), "{}", errors[0].message());
+}
@@ -0,0 +1,37 @@
+extern crate lang1;
+
+use lang1::{parse, Interp, SharedBuffer, Tokenizer};
+use lang1::run::run_to_string;
+
+#[test]
+fn shuffle_and_choice_follow_the_seed() {
+    let source = "seed(3);\na := [1, 2, 3, 4, 5];\nshuffle(a);\nprintln(a);\nprintln(choice(a), choice(ilist(7, 8, 9)));\nprintln(shuffle(ilist(1, 2, 3)));";
+    let (output, _) = run_to_string(source).unwrap();
+    assert_eq!(output, "[4, 2, 1, 3, 5]\n2 7\n[3, 1, 2]\n");
+}
+
+#[test]
+fn choice_of_nothing_is_an_error() {
+    let error = run_to_string("choice([]);").unwrap_err();
+    assert_eq!(error.code().code, "R0219");
+}
+
+#[test]
+fn a_recorded_shuffle_replays_without_the_seed() {
+    let ast = parse(Tokenizer::new("a := [1, 2, 3, 4, 5, 6];\nshuffle(a);\nprintln(a, choice(a));")).unwrap();
+
+    let recorded = SharedBuffer::new();
+    let mut interp = Interp::new();
+    interp.set_output(Box::new(recorded.clone()));
+    interp.record_effects();
+    interp.run(&ast).unwrap();
+    let effects = interp.recorded_effects().to_vec();
+    assert_eq!(effects.iter().filter(|effect| effect.builtin == "shuffle").count(), 5);
+
+    let replayed = SharedBuffer::new();
+    let mut interp = Interp::new();
+    interp.set_output(Box::new(replayed.clone()));
+    interp.replay_effects(effects);
+    interp.run(&ast).unwrap();
+    assert_eq!(replayed.take(), recorded.take());
+}
@@ -0,0 +1,65 @@
+extern crate lang1;
+
+use lang1::{Tokenizer, parse, Ast, Interp, InterpValue, SharedBuffer};
+use lang1::interp::RunState;
+
+fn parse_source(source: &str) -> Ast {
+    parse(Tokenizer::new(source)).unwrap()
+}
+
+/// Resumes `ast` until it finishes, returning its value and how many times
+/// it was interrupted on the way.
+fn run_in_slices<'a>(interp: &mut Interp<'a>, ast: &'a Ast, interval: usize) -> (InterpValue, usize) {
+    interp.set_yield_interval(Some(interval));
+    let mut execution = interp.start(ast);
+    let mut interrupts = 0;
+    loop {
+        match interp.resume(&mut execution).unwrap() {
+            RunState::Interrupted => { interrupts += 1; }
+            RunState::Finished(value) => { return (value, interrupts); }
+        }
+    }
+}
+
+#[test]
+fn a_long_loop_yields_inside_the_statement() {
+    let ast = parse_source("i := 0;\nwhile (i < 100000) { i = i + 1; };\ni;");
+    let mut interp = Interp::new();
+    let (value, interrupts) = run_in_slices(&mut interp, &ast, 10);
+    assert!(matches!(value, InterpValue::InterpInt(100000)), "{:?}", value);
+    assert!(interrupts > 10000, "only {} interrupts", interrupts);
+}
+
+#[test]
+fn a_long_call_yields_and_returns_its_value() {
+    let ast = parse_source("count :: (n) { i := 0; while (i < n) { i = i + 1; }; return i; };\ncount(1000) + 1;");
+    let mut interp = Interp::new();
+    let (value, interrupts) = run_in_slices(&mut interp, &ast, 10);
+    assert!(matches!(value, InterpValue::InterpInt(1001)), "{:?}", value);
+    assert!(interrupts > 100, "only {} interrupts", interrupts);
+}
+
+#[test]
+fn every_slice_evaluates_the_yield_interval() {
+    let ast = parse_source("i := 0;\nwhile (i < 100) { i = i + 1; };");
+    let mut interp = Interp::new();
+    interp.set_yield_interval(Some(7));
+    let mut execution = interp.start(&ast);
+    let mut evaluated = interp.stats().evaluated_nodes;
+    while let RunState::Interrupted = interp.resume(&mut execution).unwrap() {
+        let now = interp.stats().evaluated_nodes;
+        assert_eq!(now - evaluated, 7);
+        evaluated = now;
+    }
+}
+
+#[test]
+fn resuming_does_not_repeat_effects() {
+    let ast = parse_source("for (i, 0, 5) { println(i); };\nprintln(\"done\");");
+    let output = SharedBuffer::new();
+    let mut interp = Interp::new();
+    interp.set_output(Box::new(output.clone()));
+    let (_, interrupts) = run_in_slices(&mut interp, &ast, 2);
+    assert!(interrupts > 5, "only {} interrupts", interrupts);
+    assert_eq!(output.take(), "0\n1\n2\n3\n4\ndone\n");
+}
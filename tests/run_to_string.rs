@@ -0,0 +1,20 @@
+extern crate lang1;
+
+use lang1::run::run_to_string;
+use lang1::InterpValue;
+
+#[test]
+fn captures_the_output_and_the_last_value() {
+    let (output, value) = run_to_string("println(\"a\");\nprint(1, 2);\n40 + 2;").unwrap();
+    assert_eq!(output, "a\n1 2");
+    assert!(matches!(value, InterpValue::InterpInt(42)));
+}
+
+#[test]
+fn random_and_time_builtins_give_the_same_output_every_run() {
+    let source = "println(random(), rand_int(1, 100), now(), timer());\nt := timer();\nsleep(5);\nprintln(elapsed(t));";
+    let (first, _) = run_to_string(source).unwrap();
+    let (second, _) = run_to_string(source).unwrap();
+    assert_eq!(first, second);
+    assert!(first.ends_with("\n0\n"), "{}", first);
+}
@@ -0,0 +1,52 @@
+extern crate lang1;
+
+use lang1::{tokenize, parse, Token, TokenizationError};
+use lang1::semantic_tokens::{classify, encode_lsp, SemanticToken};
+
+fn classified(source: &str) -> Vec<SemanticToken> {
+    let tokens = tokenize(source).unwrap();
+    let ast = parse(tokens.iter().cloned().map(Ok::<Token, TokenizationError>)).ok();
+    classify(&tokens, ast.as_ref())
+}
+
+fn kinds_of(source: &str, name: &str) -> Vec<&'static str> {
+    classified(source).iter()
+        .filter(|token| token.text == name)
+        .map(|token| token.token_type.legend_name())
+        .collect()
+}
+
+#[test]
+fn a_parameter_is_only_a_parameter_inside_its_function() {
+    let source = "f :: (x) { return x + 1; };\nx := 2;\nf(x);";
+    assert_eq!(kinds_of(source, "x"), vec!["parameter", "parameter", "variable", "variable"]);
+}
+
+#[test]
+fn a_for_loop_variable_hides_a_parameter() {
+    let source = "f :: (x) { for (x, 0, 3) { print(x); }; return x; };";
+    assert_eq!(kinds_of(source, "x"), vec!["parameter", "variable", "variable", "parameter"]);
+}
+
+#[test]
+fn a_let_hides_a_parameter_from_then_on() {
+    let source = "f :: (y) { print(y); let y = 3; return y; };";
+    assert_eq!(kinds_of(source, "y"), vec!["parameter", "parameter", "variable", "variable"]);
+}
+
+#[test]
+fn a_plain_assignment_updates_the_parameter() {
+    let source = "f :: (y) { y = y + 1; return y; };";
+    assert_eq!(kinds_of(source, "y"), vec!["parameter", "parameter", "parameter", "parameter"]);
+}
+
+#[test]
+fn tokens_are_delta_encoded_for_the_lsp() {
+    let data = encode_lsp(&classified("let x = 1;\n  x;"));
+    assert_eq!(data, vec![
+        0, 0, 3, 0, 0,
+        0, 4, 1, 3, 0,
+        0, 4, 1, 6, 0,
+        1, 2, 1, 3, 0,
+    ]);
+}
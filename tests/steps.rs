@@ -0,0 +1,64 @@
+extern crate lang1;
+
+use lang1::{Tokenizer, parse, Ast, Interp, InterpValue, SharedBuffer};
+use lang1::interp::InterpEvent;
+
+fn parse_source(source: &str) -> Ast {
+    parse(Tokenizer::new(source)).unwrap()
+}
+
+#[test]
+fn a_script_that_never_ends_can_be_stepped() {
+    let ast = parse_source("i := 0;\nwhile (true) { i = i + 1; };");
+    let mut interp = Interp::new();
+    let events: Vec<_> = interp.steps(&ast).take(1000).collect();
+    assert_eq!(events.len(), 1000);
+    assert!(events.iter().all(|event| event.is_ok()));
+}
+
+#[test]
+fn evaluation_does_not_run_ahead_of_the_events() {
+    let ast = parse_source("println(\"a\");\nprintln(\"b\");");
+    let output = SharedBuffer::new();
+    let mut interp = Interp::new();
+    interp.set_output(Box::new(output.clone()));
+    let mut steps = interp.steps(&ast);
+
+    assert!(matches!(steps.next(), Some(Ok(InterpEvent::EnteredStatement(_)))));
+    assert_eq!(output.take(), "");
+    loop {
+        match steps.next() {
+            Some(Ok(InterpEvent::ProducedValue(InterpValue::InterpVoid))) => { break; }
+            Some(Ok(_)) => {}
+            other => { panic!("unexpected {:?}", other); }
+        }
+    }
+    assert_eq!(output.take(), "a\n");
+    assert!(matches!(steps.next(), Some(Ok(InterpEvent::EnteredStatement(_)))));
+    assert_eq!(output.take(), "");
+}
+
+#[test]
+fn calls_push_and_pop_a_frame() {
+    let ast = parse_source("f :: (n) { return n; };\nf(1);");
+    let mut interp = Interp::new();
+    let frames: Vec<String> = interp.steps(&ast)
+        .filter_map(|event| match event.unwrap() {
+            InterpEvent::PushedFrame { depth } => { Some(format!("push {}", depth)) }
+            InterpEvent::PoppedFrame { depth } => { Some(format!("pop {}", depth)) }
+            _ => { None }
+        })
+        .collect();
+    assert_eq!(frames, vec!["push 1", "pop 0"]);
+}
+
+#[test]
+fn an_error_comes_after_the_events_leading_up_to_it() {
+    let ast = parse_source("x := 1;\ny := x + missing;");
+    let mut interp = Interp::new();
+    let events: Vec<_> = interp.steps(&ast).collect();
+    assert!(events.len() > 2);
+    let last = events.last().unwrap();
+    assert!(matches!(last, Err(error) if error.code().code == "R0201"), "{:?}", last);
+    assert!(events[..events.len() - 1].iter().all(|event| event.is_ok()));
+}
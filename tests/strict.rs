@@ -0,0 +1,81 @@
+extern crate lang1;
+
+use lang1::{Tokenizer, parse, typecheck};
+
+use std::env;
+use std::fs;
+use std::process::{self, Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static SCRIPTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Runs the lang1 binary on `source` saved as a script, with `flags`.
+fn run(flags: &[&str], source: &str) -> Output {
+    let index = SCRIPTS.fetch_add(1, Ordering::SeqCst);
+    let path = env::temp_dir().join(format!("lang1-strict-{}-{}.leg", process::id(), index));
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lang1")).args(flags).arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+    output
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn typecheck_reports_mismatches_with_their_own_code() {
+    let ast = parse(Tokenizer::new("x :String := 5;\nf :: (a :Array) { return a; };\nf(\"s\");")).unwrap();
+    let diagnostics = typecheck::check(&ast);
+    let messages: Vec<&str> = diagnostics.iter().map(|diagnostic| &diagnostic.message[..]).collect();
+    assert_eq!(messages, vec!["x has type String but was given an Int", "Argument 1 of f has type Array but was given a String"]);
+    assert!(diagnostics.iter().all(|diagnostic| diagnostic.code.code == "T0301"));
+}
+
+#[test]
+fn strict_flag_type_checks_before_running() {
+    let source = "x :String := 5;\nprintln(x);";
+    let output = run(&["--strict"], source);
+    assert!(stderr(&output).contains("error[T0301]"));
+    assert_eq!(stdout(&output), "");
+
+    let output = run(&[], source);
+    assert_eq!(stdout(&output), "5\n");
+}
+
+#[test]
+fn strict_pragma_type_checks_before_running() {
+    let output = run(&[], "#pragma strict\nx :String := 5;\nprintln(x);");
+    assert!(stderr(&output).contains("error[T0301]"));
+}
+
+#[test]
+fn strict_flag_checks_overflow_and_conditions() {
+    let output = run(&["--strict"], "println(9223372036854775807 + 1);");
+    assert!(stderr(&output).contains("R0208"));
+
+    let output = run(&["--strict"], "if (1) { println(1); };");
+    assert!(stderr(&output).contains("R0204"));
+}
+
+#[test]
+fn strict_mode_makes_warnings_errors() {
+    let source = "f :: (a, b) { return a; };\nprintln(\"ran\", f(1, 2));";
+    let output = run(&[], source);
+    assert!(output.status.success());
+    assert!(stderr(&output).contains("warning[W0001]"));
+    assert_eq!(stdout(&output), "ran 1\n");
+
+    let output = run(&["--strict"], source);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("error[W0001]"));
+    assert_eq!(stdout(&output), "");
+
+    let output = run(&["--strict", "--allow=W0001"], source);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "ran 1\n");
+}
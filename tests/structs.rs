@@ -0,0 +1,34 @@
+extern crate lang1;
+
+use lang1::run::run_to_string;
+
+fn error_message(source: &str) -> (&'static str, String) {
+    match run_to_string(source) {
+        Ok((output, _)) => { panic!("expected an error, got {:?}", output) }
+        Err(error) => { (error.code().code, error.to_string()) }
+    }
+}
+
+#[test]
+fn a_field_can_hold_an_instance_of_the_struct_it_is_declared_with() {
+    let source = "Point :: { x :Int y :Int };\nLine :: { a :Point b :Point };\nl := Line(Point(0, 0), Point(1, 1));\nl.a = Point(2, 3);\nprintln(l.a.y, l.b.x);";
+    let (output, _) = run_to_string(source).unwrap();
+    assert_eq!(output, "3 1\n");
+}
+
+#[test]
+fn a_field_names_the_struct_it_was_given_instead() {
+    let (code, message) = error_message("Point :: { x :Int };\nOther :: { x :Int };\nLine :: { a :Point };\nLine(Other(1));");
+    assert_eq!(code, "R0204");
+    assert!(message.contains("The field a has type Point but was given an Other"), "{}", message);
+}
+
+#[test]
+fn a_field_of_an_unknown_type_is_reported_as_such() {
+    let (code, message) = error_message("P :: { x :int };\nP(1);");
+    assert_eq!(code, "R0224");
+    assert!(message.contains("unknown type int, did you mean `Int`?"), "{}", message);
+
+    let (code, _) = error_message("P :: { x :Foo };\nP(1);");
+    assert_eq!(code, "R0224");
+}